@@ -1,33 +1,43 @@
-use crate::types::{Path, StanceConfig, BehaviorCommand, Behavior, LogEntry, LogLevel};
+use crate::types::{Path, PipelineStage, StanceConfig, CommandId, ManualControl, BehaviorCommand, Behavior, CommandSource, LogLevel};
 use crate::infra::logger::create_log;
-use tokio::sync::{broadcast, mpsc};
+use crate::infra::log_levels::LogSender;
+use crate::infra::bus::{Publisher, Subscriber};
+use crate::infra::pipeline::Tracer;
+use tokio::sync::broadcast;
 use std::time::SystemTime;
 
 pub struct BehaviourModule {
-    goal_path_rx: mpsc::Receiver<Path>,
-    obstacle_path_rx: mpsc::Receiver<Path>,
-    stance_rx: mpsc::Receiver<StanceConfig>,
-    safety_controller_tx: mpsc::Sender<BehaviorCommand>,
-    log_tx: mpsc::Sender<LogEntry>,
+    goal_path_rx: Subscriber<Path>,
+    obstacle_path_rx: Subscriber<Path>,
+    stance_rx: Subscriber<StanceConfig>,
+    manual_rx: Subscriber<(CommandId, ManualControl)>,
+    arbiter_tx: Publisher<BehaviorCommand>,
+    log_tx: LogSender,
     shutdown_rx: broadcast::Receiver<()>,
+    tracer: Tracer,
 }
 
 impl BehaviourModule {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
-        goal_path_rx: mpsc::Receiver<Path>,
-        obstacle_path_rx: mpsc::Receiver<Path>,
-        stance_rx: mpsc::Receiver<StanceConfig>,
-        safety_controller_tx: mpsc::Sender<BehaviorCommand>,
-        log_tx: mpsc::Sender<LogEntry>,
+        goal_path_rx: Subscriber<Path>,
+        obstacle_path_rx: Subscriber<Path>,
+        stance_rx: Subscriber<StanceConfig>,
+        manual_rx: Subscriber<(CommandId, ManualControl)>,
+        arbiter_tx: Publisher<BehaviorCommand>,
+        log_tx: LogSender,
         shutdown_rx: broadcast::Receiver<()>,
+        tracer: Tracer,
     ) -> Self {
         Self {
             goal_path_rx,
             obstacle_path_rx,
             stance_rx,
-            safety_controller_tx,
+            manual_rx,
+            arbiter_tx,
             log_tx,
             shutdown_rx,
+            tracer,
         }
     }
 
@@ -49,14 +59,17 @@ impl BehaviourModule {
                     break;
                 }
                 Some(path) = self.goal_path_rx.recv() => {
-                    self.execute_path(path, "goal planning").await;
+                    self.execute_path(path, CommandSource::GoalPath).await;
                 }
                 Some(path) = self.obstacle_path_rx.recv() => {
-                    self.execute_path(path, "obstacle avoidance").await;
+                    self.execute_path(path, CommandSource::ObstaclePath).await;
                 }
                 Some(stance) = self.stance_rx.recv() => {
                     self.adjust_for_stance(stance).await;
                 }
+                Some((command_id, control)) = self.manual_rx.recv() => {
+                    self.execute_manual_control(command_id, control).await;
+                }
             }
         }
 
@@ -67,31 +80,52 @@ impl BehaviourModule {
         )).await;
     }
 
-    async fn execute_path(&mut self, path: Path, source: &str) {
+    async fn execute_path(&mut self, mut path: Path, source: CommandSource) {
         let _ = self.log_tx.send(create_log(
             "Behaviour",
             LogLevel::Info,
-            format!("Executing path from {} with {} waypoints", source, path.waypoints.len())
+            format!("Executing path from {:?} with {} waypoints", source, path.waypoints.len())
         )).await;
 
-        if let Some(first_waypoint) = path.waypoints.first() {
-            let behavior = BehaviorCommand {
-                timestamp: SystemTime::now(),
-                behavior: Behavior::MoveTowards {
-                    target: first_waypoint.position,
-                    speed: 0.5,
-                },
-                priority: 5,
-            };
-
-            if let Err(_) = self.safety_controller_tx.send(behavior).await {
-                let _ = self.log_tx.send(create_log(
-                    "Behaviour",
-                    LogLevel::Error,
-                    "Failed to send behavior command to safety controller".to_string()
-                )).await;
+        let behavior = match path.waypoints.as_slice() {
+            // An empty path means hold position: stop following any planned path
+            [] => {
+                for source in [CommandSource::GoalPath, CommandSource::ObstaclePath] {
+                    self.arbiter_tx.publish(BehaviorCommand {
+                        timestamp: SystemTime::now(),
+                        behavior: Behavior::Idle,
+                        priority: 5,
+                        source,
+                        command_id: None,
+                        trace: None,
+                    }).await;
+                }
+                return;
+            }
+            // A single stationary waypoint with a yaw rate means rotate in place
+            [waypoint] if waypoint.velocity == [0.0; 3] && waypoint.angular_velocity[2] != 0.0 => {
+                Behavior::Drive { linear: 0.0, angular: waypoint.angular_velocity[2].clamp(-1.0, 1.0) }
             }
+            [first_waypoint, ..] => Behavior::MoveTowards {
+                target: first_waypoint.position,
+                speed: 0.5,
+            },
+        };
+
+        if let Some(trace) = path.trace.as_mut() {
+            self.tracer.hop(trace, PipelineStage::Behaviour);
         }
+
+        let command = BehaviorCommand {
+            timestamp: SystemTime::now(),
+            behavior,
+            priority: 5,
+            source,
+            command_id: None,
+            trace: path.trace,
+        };
+
+        self.arbiter_tx.publish(command).await;
     }
 
     async fn adjust_for_stance(&mut self, stance: StanceConfig) {
@@ -105,8 +139,38 @@ impl BehaviourModule {
             timestamp: SystemTime::now(),
             behavior: Behavior::AdjustStance(stance),
             priority: 7,
+            source: CommandSource::Stance,
+            command_id: None,
+            trace: None,
+        };
+
+        self.arbiter_tx.publish(behavior).await;
+    }
+
+    async fn execute_manual_control(&mut self, command_id: CommandId, control: ManualControl) {
+        let (linear, angular) = match control {
+            ManualControl::MoveForward(speed) => (speed, 0.0),
+            ManualControl::MoveBackward(speed) => (-speed, 0.0),
+            ManualControl::TurnLeft(rate) => (0.0, rate),
+            ManualControl::TurnRight(rate) => (0.0, -rate),
+            ManualControl::Stop => (0.0, 0.0),
+        };
+
+        let _ = self.log_tx.send(create_log(
+            "Behaviour",
+            LogLevel::Info,
+            format!("Manual drive: linear={:.2}, angular={:.2}", linear, angular)
+        )).await;
+
+        let behavior = BehaviorCommand {
+            timestamp: SystemTime::now(),
+            behavior: Behavior::Drive { linear, angular },
+            priority: 8, // Operator input overrides autonomous paths and stance changes
+            source: CommandSource::Manual,
+            command_id: Some(command_id),
+            trace: None,
         };
 
-        let _ = self.safety_controller_tx.send(behavior).await;
+        self.arbiter_tx.publish(behavior).await;
     }
 }
@@ -0,0 +1,305 @@
+use crate::types::{BehaviorCommand, Behavior, CommandId, CommandSource, PipelineStage, CalibrationData, LogLevel};
+use crate::infra::logger::create_log;
+use crate::infra::log_levels::LogSender;
+use crate::infra::bus::{Publisher, Subscriber};
+use crate::infra::pipeline::Tracer;
+use crate::rlog;
+use crate::output::hardware_interface::motor_command_for;
+use crate::perception::model_calibration_storage::CalibrationRequest;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::SystemTime;
+use tokio::sync::broadcast;
+use tokio::time::{Duration, Instant, interval};
+
+// Module name for rlog! entries
+const LOG_MODULE: &str = "CommandArbiter";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ArbiterConfig {
+    pub control_period_ms: u64,
+    pub keepalive_ms: u64,               // Re-send an unchanged non-zero command this often
+    pub goal_path_timeout_ms: u64,
+    pub obstacle_path_timeout_ms: u64,
+    pub manual_timeout_ms: u64,
+    pub max_linear_acceleration: f32,    // m/s^2
+    pub max_angular_acceleration: f32,   // rad/s^2
+}
+
+impl Default for ArbiterConfig {
+    fn default() -> Self {
+        Self {
+            control_period_ms: 100,
+            keepalive_ms: 1000,
+            goal_path_timeout_ms: 10_000,
+            obstacle_path_timeout_ms: 3000,
+            manual_timeout_ms: 5000,
+            max_linear_acceleration: 1.0,
+            max_angular_acceleration: 3.0,
+        }
+    }
+}
+
+impl ArbiterConfig {
+    fn timeout_for(&self, source: CommandSource) -> Duration {
+        let ms = match source {
+            CommandSource::GoalPath => self.goal_path_timeout_ms,
+            CommandSource::ObstaclePath => self.obstacle_path_timeout_ms,
+            CommandSource::Manual => self.manual_timeout_ms,
+            // Stance and safety commands are passed straight through
+            CommandSource::Stance | CommandSource::Safety => 0,
+        };
+        Duration::from_millis(ms)
+    }
+}
+
+// Latest motion command from one source
+struct Candidate {
+    command: BehaviorCommand,
+    received: Instant,
+    linear: f32,  // normalised target velocity
+    angular: f32,
+}
+
+pub struct CommandArbiter {
+    behavior_rx: Subscriber<BehaviorCommand>,
+    calibration_rx: Subscriber<CalibrationData>,
+    calibration_tx: Publisher<CalibrationRequest>,
+    safety_controller_tx: Publisher<BehaviorCommand>,
+    log_tx: LogSender,
+    shutdown_rx: broadcast::Receiver<()>,
+    config: ArbiterConfig,
+    tracer: Tracer,
+    deadman: Duration,         // The Safety Controller's command deadman
+    max_speed: f32,            // m/s, from calibration
+    max_angular_velocity: f32, // rad/s, from calibration
+    candidates: HashMap<CommandSource, Candidate>,
+    winner: Option<CommandSource>,
+    output: (f32, f32),        // normalised (linear, angular) last sent
+    output_origin: (u8, CommandSource, Option<CommandId>), // Priority, source and command the output is attributed to
+    last_sent: Instant,
+}
+
+impl CommandArbiter {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        behavior_rx: Subscriber<BehaviorCommand>,
+        calibration_rx: Subscriber<CalibrationData>,
+        calibration_tx: Publisher<CalibrationRequest>,
+        safety_controller_tx: Publisher<BehaviorCommand>,
+        log_tx: LogSender,
+        shutdown_rx: broadcast::Receiver<()>,
+        config: ArbiterConfig,
+        tracer: Tracer,
+        deadman: Duration,
+    ) -> Self {
+        Self {
+            behavior_rx,
+            calibration_rx,
+            calibration_tx,
+            safety_controller_tx,
+            log_tx,
+            shutdown_rx,
+            config,
+            tracer,
+            deadman,
+            // Used until the calibration storage answers
+            max_speed: 2.0,
+            max_angular_velocity: 1.5,
+            candidates: HashMap::new(),
+            winner: None,
+            output: (0.0, 0.0),
+            output_origin: (0, CommandSource::Safety, None),
+            last_sent: Instant::now(),
+        }
+    }
+
+    pub async fn run(mut self) {
+        let _ = self.log_tx.send(create_log(
+            "CommandArbiter",
+            LogLevel::Info,
+            "Starting command arbiter".to_string()
+        )).await;
+
+        // Ramp limits are derived from the calibrated speed limits
+        self.calibration_tx.publish(CalibrationRequest::Get).await;
+
+        let mut control_tick = interval(Duration::from_millis(self.config.control_period_ms));
+
+        loop {
+            tokio::select! {
+                _ = self.shutdown_rx.recv() => {
+                    let _ = self.log_tx.send(create_log(
+                        "CommandArbiter",
+                        LogLevel::Info,
+                        "Shutdown signal received".to_string()
+                    )).await;
+                    break;
+                }
+                Some(cmd) = self.behavior_rx.recv() => {
+                    self.handle_command(cmd).await;
+                }
+                Some(calibration) = self.calibration_rx.recv() => {
+                    self.max_speed = calibration.max_speed;
+                    self.max_angular_velocity = calibration.max_angular_velocity;
+                    let _ = self.log_tx.send(create_log(
+                        "CommandArbiter",
+                        LogLevel::Info,
+                        format!("Using calibrated limits: {:.2} m/s, {:.2} rad/s",
+                            self.max_speed, self.max_angular_velocity)
+                    )).await;
+                }
+                _ = control_tick.tick() => {
+                    self.arbitrate().await;
+                }
+            }
+        }
+
+        let _ = self.log_tx.send(create_log(
+            "CommandArbiter",
+            LogLevel::Info,
+            "Stopped".to_string()
+        )).await;
+    }
+
+    async fn handle_command(&mut self, cmd: BehaviorCommand) {
+        match &cmd.behavior {
+            Behavior::EmergencyStop => {
+                // Never ramped or arbitrated, and nothing older may resurface afterwards
+                self.candidates.clear();
+                self.winner = None;
+                self.output = (0.0, 0.0);
+                self.send(cmd).await;
+            }
+            Behavior::AdjustStance(_) => {
+                // Stance changes do not compete with motion commands
+                self.send(cmd).await;
+            }
+            Behavior::Idle => {
+                self.candidates.remove(&cmd.source);
+            }
+            behavior => {
+                if let Some(motor_cmd) = motor_command_for(behavior) {
+                    let candidate = Candidate {
+                        linear: ((motor_cmd.left_speed + motor_cmd.right_speed) / 2.0).clamp(-1.0, 1.0),
+                        angular: ((motor_cmd.right_speed - motor_cmd.left_speed) / 2.0).clamp(-1.0, 1.0),
+                        received: Instant::now(),
+                        command: cmd,
+                    };
+                    self.candidates.insert(candidate.command.source, candidate);
+                }
+            }
+        }
+    }
+
+    async fn arbitrate(&mut self) {
+        self.expire_candidates().await;
+
+        // Highest priority wins, ties go to the most recent command
+        let winner = self.candidates.values()
+            .max_by(|a, b| a.command.priority.cmp(&b.command.priority).then(a.received.cmp(&b.received)))
+            .map(|candidate| candidate.command.source);
+
+        if winner != self.winner {
+            self.log_decision(winner);
+            self.winner = winner;
+        }
+
+        // With no winner the output ramps down, still attributed to the last winner
+        let target = match winner.and_then(|source| self.candidates.get(&source)) {
+            Some(candidate) => {
+                self.output_origin = (candidate.command.priority, candidate.command.source, candidate.command.command_id);
+                (candidate.linear, candidate.angular)
+            }
+            None => (0.0, 0.0),
+        };
+
+        let period = self.config.control_period_ms as f32 / 1000.0;
+        let max_linear_step = self.config.max_linear_acceleration * period / self.max_speed;
+        let max_angular_step = self.config.max_angular_acceleration * period / self.max_angular_velocity;
+
+        let output = (
+            ramp(self.output.0, target.0, max_linear_step),
+            ramp(self.output.1, target.1, max_angular_step),
+        );
+
+        let changed = (output.0 - self.output.0).abs() > 1e-3 || (output.1 - self.output.1).abs() > 1e-3;
+        let moving = output.0.abs() > 1e-3 || output.1.abs() > 1e-3;
+        // Keepalives only cover a source that is still sending. Once the
+        // winner's own input is older than the deadman, they stop and let
+        // the deadman catch a producer that has died.
+        let keepalive_due = self.last_sent.elapsed() >= Duration::from_millis(self.config.keepalive_ms)
+            && winner
+                .and_then(|source| self.candidates.get(&source))
+                .is_some_and(|candidate| candidate.received.elapsed() < self.deadman);
+
+        if changed || (moving && keepalive_due) {
+            self.output = output;
+            let (priority, source, command_id) = self.output_origin;
+            // A reading's trace goes out with the first command it shaped
+            let trace = winner
+                .and_then(|source| self.candidates.get_mut(&source))
+                .and_then(|candidate| candidate.command.trace.take());
+            self.send(BehaviorCommand {
+                timestamp: SystemTime::now(),
+                behavior: Behavior::Drive { linear: output.0, angular: output.1 },
+                priority,
+                source,
+                command_id,
+                trace,
+            }).await;
+        }
+    }
+
+    // Drops commands whose source has gone quiet so they cannot win again later
+    async fn expire_candidates(&mut self) {
+        let expired: Vec<CommandSource> = self.candidates.values()
+            .filter(|candidate| candidate.received.elapsed() > self.config.timeout_for(candidate.command.source))
+            .map(|candidate| candidate.command.source)
+            .collect();
+
+        for source in expired {
+            self.candidates.remove(&source);
+            rlog!(self.log_tx, Debug, "Dropped stale command from {:?}", source, source = format!("{:?}", source));
+        }
+    }
+
+    fn log_decision(&self, winner: Option<CommandSource>) {
+        rlog!(self.log_tx, Info, "{}", self.describe_decision(winner));
+    }
+
+    fn describe_decision(&self, winner: Option<CommandSource>) -> String {
+        match winner.and_then(|source| self.candidates.get(&source)) {
+            Some(candidate) => {
+                let others: Vec<String> = self.candidates.values()
+                    .filter(|other| other.command.source != candidate.command.source)
+                    .map(|other| format!("{:?} (priority {}, {} ms old)",
+                        other.command.source, other.command.priority, other.received.elapsed().as_millis()))
+                    .collect();
+
+                if others.is_empty() {
+                    format!("{:?} (priority {}) won - only active source",
+                        candidate.command.source, candidate.command.priority)
+                } else {
+                    format!("{:?} (priority {}) won over {}",
+                        candidate.command.source, candidate.command.priority, others.join(", "))
+                }
+            }
+            None => "No active source - ramping to stop".to_string(),
+        }
+    }
+
+    async fn send(&mut self, mut cmd: BehaviorCommand) {
+        self.last_sent = Instant::now();
+        if let Some(trace) = cmd.trace.as_mut() {
+            self.tracer.hop(trace, PipelineStage::CommandArbiter);
+        }
+        self.safety_controller_tx.publish(cmd).await;
+    }
+}
+
+// Moves `current` towards `target` by at most `max_step`
+fn ramp(current: f32, target: f32, max_step: f32) -> f32 {
+    current + (target - current).clamp(-max_step, max_step)
+}
@@ -1,2 +1,5 @@
 pub mod behaviour;
 pub mod safety_controller;
+pub mod protective_field;
+pub mod command_arbiter;
+pub mod stability;
@@ -0,0 +1,182 @@
+use crate::types::Behavior;
+use crate::output::hardware_interface::motor_command_for;
+use serde::{Deserialize, Serialize};
+
+// Which way a distance sensor looks, in the robot frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FieldDirection {
+    Front,
+    Rear,
+    Left,
+    Right,
+}
+
+// Which way each of the SensorArray's distance sensors looks, by index into
+// SensorData::distance_sensors
+pub const SENSOR_DIRECTIONS: [FieldDirection; 4] = [
+    FieldDirection::Front,
+    FieldDirection::Left,
+    FieldDirection::Right,
+    FieldDirection::Rear,
+];
+
+impl FieldDirection {
+    // Angle from the robot's heading, in radians, positive to the left
+    pub fn angle(self) -> f32 {
+        match self {
+            FieldDirection::Front => 0.0,
+            FieldDirection::Left => std::f32::consts::FRAC_PI_2,
+            FieldDirection::Right => -std::f32::consts::FRAC_PI_2,
+            FieldDirection::Rear => std::f32::consts::PI,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldConfig {
+    pub sensor: usize,               // Index into SensorData::distance_sensors
+    pub direction: FieldDirection,
+    pub protective_distance: f32,    // meters, protective field length at standstill
+    pub warning_distance: f32,       // meters, warning field extends this far past the protective field
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProtectiveFieldConfig {
+    pub fields: Vec<FieldConfig>,
+    pub max_speed: f32,            // m/s at a normalised speed of 1.0
+    pub max_angular_velocity: f32, // rad/s at a normalised turn rate of 1.0
+    pub turn_radius: f32,          // meters swept by the chassis corners when turning on the spot
+    pub reaction_time: f32,        // seconds before braking starts
+    pub max_deceleration: f32,     // m/s^2 achievable braking
+    pub warning_time: f32,         // seconds of extra look-ahead for the warning field
+    pub min_slowdown: f32,         // speed factor applied at the protective field edge
+}
+
+impl Default for ProtectiveFieldConfig {
+    fn default() -> Self {
+        Self {
+            fields: SENSOR_DIRECTIONS
+                .iter()
+                .enumerate()
+                .map(|(sensor, &direction)| {
+                    let (protective_distance, warning_distance) = match direction {
+                        FieldDirection::Front | FieldDirection::Rear => (0.3, 0.5),
+                        FieldDirection::Left | FieldDirection::Right => (0.15, 0.25),
+                    };
+                    FieldConfig { sensor, direction, protective_distance, warning_distance }
+                })
+                .collect(),
+            // Match the defaults in ModelCalibrationStorage
+            max_speed: 2.0,
+            max_angular_velocity: 1.5,
+            turn_radius: 0.25,
+            reaction_time: 0.2,
+            max_deceleration: 1.5,
+            warning_time: 1.0,
+            min_slowdown: 0.2,
+        }
+    }
+}
+
+// Physical motion a command produces
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Motion {
+    pub linear: f32,  // m/s, positive forward
+    pub angular: f32, // rad/s, positive turning left
+}
+
+impl Motion {
+    pub fn from_behavior(behavior: &Behavior, config: &ProtectiveFieldConfig) -> Option<Self> {
+        let motor_cmd = motor_command_for(behavior)?;
+
+        Some(Self {
+            linear: (motor_cmd.left_speed + motor_cmd.right_speed) / 2.0 * config.max_speed,
+            angular: (motor_cmd.right_speed - motor_cmd.left_speed) / 2.0 * config.max_angular_velocity,
+        })
+    }
+
+    pub fn is_moving(&self) -> bool {
+        self.linear.abs() > f32::EPSILON || self.angular.abs() > f32::EPSILON
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldResponse {
+    Clear,
+    SlowDown { factor: f32, sensor: usize, distance: f32, limit: f32 },
+    Stop { sensor: usize, distance: f32, limit: f32 },
+}
+
+impl ProtectiveFieldConfig {
+    // Speed at which the robot closes in on whatever the field's sensor sees
+    fn approach_speed(&self, direction: FieldDirection, motion: &Motion) -> f32 {
+        match direction {
+            FieldDirection::Front => motion.linear.max(0.0),
+            FieldDirection::Rear => (-motion.linear).max(0.0),
+            // Turning on the spot sweeps both sides of the chassis
+            FieldDirection::Left | FieldDirection::Right => motion.angular.abs() * self.turn_radius,
+        }
+    }
+
+    // Protective and warning field lengths for a given approach speed
+    pub fn field_lengths(&self, field: &FieldConfig, speed: f32) -> (f32, f32) {
+        let braking = speed * self.reaction_time + speed * speed / (2.0 * self.max_deceleration);
+        let protective = field.protective_distance + braking;
+        let warning = protective + field.warning_distance + speed * self.warning_time;
+        (protective, warning)
+    }
+
+    // Evaluates every field against the latest distances. The field size uses the
+    // faster of the commanded and current motion, since the robot has to brake
+    // from whichever it is actually doing.
+    pub fn evaluate(&self, commanded: &Motion, current: &Motion, distances: &[f32]) -> FieldResponse {
+        let mut response = FieldResponse::Clear;
+
+        for field in &self.fields {
+            let commanded_speed = self.approach_speed(field.direction, commanded);
+            if commanded_speed <= f32::EPSILON {
+                // Not moving towards this sensor's side
+                continue;
+            }
+
+            let Some(&distance) = distances.get(field.sensor) else {
+                continue;
+            };
+
+            let speed = commanded_speed.max(self.approach_speed(field.direction, current));
+            let (protective, warning) = self.field_lengths(field, speed);
+
+            if distance < protective {
+                return FieldResponse::Stop { sensor: field.sensor, distance, limit: protective };
+            }
+
+            if distance < warning {
+                let ratio = (distance - protective) / (warning - protective);
+                let factor = self.min_slowdown + (1.0 - self.min_slowdown) * ratio;
+
+                let more_restrictive = match response {
+                    FieldResponse::SlowDown { factor: current_factor, .. } => factor < current_factor,
+                    _ => true,
+                };
+                if more_restrictive {
+                    response = FieldResponse::SlowDown { factor, sensor: field.sensor, distance, limit: warning };
+                }
+            }
+        }
+
+        response
+    }
+}
+
+// Scales the speed of a motion command, leaving other behaviors untouched
+pub fn scale_behavior(behavior: Behavior, factor: f32) -> Behavior {
+    match behavior {
+        Behavior::MoveTowards { target, speed } => Behavior::MoveTowards { target, speed: speed * factor },
+        Behavior::AvoidObstacle { direction } => Behavior::AvoidObstacle {
+            direction: [direction[0] * factor, direction[1] * factor, direction[2] * factor],
+        },
+        Behavior::Drive { linear, angular } => Behavior::Drive { linear: linear * factor, angular: angular * factor },
+        other => other,
+    }
+}
@@ -1,36 +1,130 @@
-use crate::types::{BehaviorCommand, Behavior, SensorData, RobotState, LogEntry, LogLevel};
+use crate::types::{BehaviorCommand, Behavior, CommandSource, PipelineStage, CommandId, CommandAck, AckStatus, SensorData, RobotState, SafetyFault, FaultReport, PositionEstimate, StanceConfig, StanceType, DiagnosticStatus, DiagnosticLevel, LogLevel};
 use crate::infra::logger::create_log;
-use tokio::sync::{broadcast, mpsc};
+use crate::infra::log_levels::LogSender;
+use crate::rlog;
+use crate::infra::pipeline::Tracer;
+use crate::infra::metrics::{BlockReason, Metrics};
+use crate::infra::diagnostics::DiagnosticReporter;
+use crate::infra::bus::{Publisher, Subscriber};
+use crate::control::protective_field::{FieldResponse, Motion, ProtectiveFieldConfig, scale_behavior};
+use crate::control::stability::{StabilityConfig, StabilityLevel, StabilityMonitor, roll_pitch};
+use crate::perception::stance::StanceRequest;
+use crate::planning::geofence::SharedGeofence;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast, watch};
+use tokio::time::{Duration, Instant, interval};
+use std::time::SystemTime;
+
+// Module name for rlog! entries
+const LOG_MODULE: &str = "SafetyController";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SafetyConfig {
+    pub protective_fields: ProtectiveFieldConfig,
+    pub watchdog: WatchdogConfig,
+    pub stability: StabilityConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatchdogConfig {
+    pub sensor_timeout_ms: u64,  // Max age of the latest SensorData, by arrival and by timestamp
+    pub command_deadman_ms: u64, // Max time between BehaviorCommands while moving
+    pub check_interval_ms: u64,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self {
+            sensor_timeout_ms: 1500, // Three missed readings from the 2 Hz sensor array
+            command_deadman_ms: 5000,
+            check_interval_ms: 100,
+        }
+    }
+}
 
 pub struct SafetyController {
-    behavior_rx: mpsc::Receiver<BehaviorCommand>,
-    sensor_rx: mpsc::Receiver<SensorData>,
-    state_rx: mpsc::Receiver<RobotState>,
-    hardware_interface_tx: mpsc::Sender<BehaviorCommand>,
-    log_tx: mpsc::Sender<LogEntry>,
+    behavior_rx: Subscriber<BehaviorCommand>,
+    sensor_rx: Subscriber<SensorData>,
+    state_rx: Subscriber<RobotState>,
+    position_rx: watch::Receiver<Option<PositionEstimate>>,
+    hardware_interface_tx: Publisher<BehaviorCommand>,
+    fault_tx: Publisher<FaultReport>,
+    stance_tx: Publisher<StanceRequest>,
+    ack_tx: Publisher<CommandAck>,
+    diagnostics: DiagnosticReporter,
+    log_tx: LogSender,
     shutdown_rx: broadcast::Receiver<()>,
+    config: SafetyConfig,
+    geofence: SharedGeofence,
+    tracer: Tracer,
+    metrics: Metrics,
     emergency_stop: bool,
     latest_sensor_data: Option<SensorData>,
+    active_command: Option<BehaviorCommand>, // Latest motion command, before any slowdown
+    applied_factor: Option<f32>,             // Slowdown currently applied to the active command
+    current_motion: Motion,                  // Motion of the last command sent to hardware
+    last_sensor_received: Instant,
+    last_command_received: Instant,
+    sensor_fault: bool,
+    command_fault: bool,
+    geofence_fault: bool,
+    stability: StabilityMonitor,
+    back_off_direction: Option<f32>, // Sign of the only linear motion allowed past the tilt limit
+    rejected_command: Option<CommandId>, // Latest operator command reported as blocked
 }
 
 impl SafetyController {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
-        behavior_rx: mpsc::Receiver<BehaviorCommand>,
-        sensor_rx: mpsc::Receiver<SensorData>,
-        state_rx: mpsc::Receiver<RobotState>,
-        hardware_interface_tx: mpsc::Sender<BehaviorCommand>,
-        log_tx: mpsc::Sender<LogEntry>,
+        behavior_rx: Subscriber<BehaviorCommand>,
+        sensor_rx: Subscriber<SensorData>,
+        state_rx: Subscriber<RobotState>,
+        position_rx: watch::Receiver<Option<PositionEstimate>>,
+        hardware_interface_tx: Publisher<BehaviorCommand>,
+        fault_tx: Publisher<FaultReport>,
+        stance_tx: Publisher<StanceRequest>,
+        ack_tx: Publisher<CommandAck>,
+        diagnostics: DiagnosticReporter,
+        log_tx: LogSender,
         shutdown_rx: broadcast::Receiver<()>,
+        config: SafetyConfig,
+        geofence: SharedGeofence,
+        tracer: Tracer,
+        metrics: Metrics,
     ) -> Self {
+        let stability = StabilityMonitor::new(config.stability.clone());
+
         Self {
             behavior_rx,
             sensor_rx,
             state_rx,
+            position_rx,
             hardware_interface_tx,
+            fault_tx,
+            stance_tx,
+            ack_tx,
+            diagnostics,
             log_tx,
             shutdown_rx,
+            config,
+            geofence,
+            tracer,
+            metrics,
             emergency_stop: false,
             latest_sensor_data: None,
+            active_command: None,
+            applied_factor: None,
+            current_motion: Motion::default(),
+            last_sensor_received: Instant::now(),
+            last_command_received: Instant::now(),
+            sensor_fault: false,
+            command_fault: false,
+            geofence_fault: false,
+            stability,
+            back_off_direction: None,
+            rejected_command: None,
         }
     }
 
@@ -41,6 +135,11 @@ impl SafetyController {
             "Starting safety controller".to_string()
         )).await;
 
+        // Give the watchdogs a full timeout before the first data is expected
+        self.last_sensor_received = Instant::now();
+        self.last_command_received = Instant::now();
+        let mut watchdog = interval(Duration::from_millis(self.config.watchdog.check_interval_ms));
+
         loop {
             tokio::select! {
                 _ = self.shutdown_rx.recv() => {
@@ -52,11 +151,19 @@ impl SafetyController {
                     break;
                 }
                 Some(behavior_cmd) = self.behavior_rx.recv() => {
+                    self.last_command_received = Instant::now();
+                    if self.command_fault {
+                        self.clear_fault(SafetyFault::BehaviorCommandStale { age_ms: 0 }).await;
+                    }
                     self.validate_and_execute(behavior_cmd).await;
                 }
                 Some(sensor_data) = self.sensor_rx.recv() => {
-                    self.latest_sensor_data = Some(sensor_data.clone());
-                    self.check_safety(&sensor_data).await;
+                    self.handle_sensor_data(sensor_data).await;
+                }
+                _ = watchdog.tick() => {
+                    self.check_watchdogs().await;
+                    self.check_geofence().await;
+                    self.report_diagnostics().await;
                 }
                 Some(state) = self.state_rx.recv() => {
                     if matches!(state, RobotState::EmergencyStop) {
@@ -66,6 +173,7 @@ impl SafetyController {
                             LogLevel::Error,
                             "EMERGENCY STOP ACTIVATED".to_string()
                         )).await;
+                        self.active_command = None;
                         self.send_stop_command().await;
                     }
                 }
@@ -79,58 +187,397 @@ impl SafetyController {
         )).await;
     }
 
-    async fn validate_and_execute(&mut self, cmd: BehaviorCommand) {
+    async fn handle_sensor_data(&mut self, sensor_data: SensorData) {
+        self.last_sensor_received = Instant::now();
+        self.latest_sensor_data = Some(sensor_data.clone());
+
+        // A backed-up channel still delivers data, just too late to be trusted
+        if self.sensor_fault && self.sensor_age() <= self.sensor_timeout() {
+            self.clear_fault(SafetyFault::SensorDataStale { age_ms: 0 }).await;
+        }
+
+        self.check_safety(&sensor_data).await;
+        self.check_stability(&sensor_data).await;
+
+        // Re-evaluate the fields while moving so obstacles that appear
+        // between commands still slow or stop the robot
+        if self.current_motion.is_moving() {
+            self.enforce_fields().await;
+        }
+    }
+
+    async fn check_stability(&mut self, sensor_data: &SensorData) {
+        let (roll, pitch) = roll_pitch(sensor_data.imu.orientation);
+        let Some(level) = self.stability.update(roll, pitch) else {
+            return;
+        };
+
+        let config = self.stability.config();
+        let attitude = format!("roll {:.1}°, pitch {:.1}° (limits {:.1}°, {:.1}°)",
+            roll.to_degrees(), pitch.to_degrees(),
+            config.roll_limit().to_degrees(), config.pitch_limit().to_degrees());
+
+        match level {
+            StabilityLevel::Warning => {
+                // Compensate for whichever axis is closer to its limit
+                let angle = if roll.abs() / config.roll_limit() >= pitch.abs() / config.pitch_limit() {
+                    roll
+                } else {
+                    pitch
+                };
+                let stability = (1.0 - self.stability.tilt_ratio(roll, pitch)).clamp(0.0, 1.0);
+
+                let _ = self.log_tx.send(create_log(
+                    "SafetyController",
+                    LogLevel::Warn,
+                    format!("Approaching tilt limit - {} - requesting tilt compensation", attitude)
+                )).await;
+
+                self.back_off_direction = None;
+                self.stance_tx.publish(StanceRequest::Adjust(StanceConfig {
+                    stance_type: StanceType::TiltCompensation(angle),
+                    stability,
+                })).await;
+            }
+            StabilityLevel::Exceeded => {
+                // Back off the way we came; if we were not moving either straight direction is allowed
+                self.back_off_direction = (self.current_motion.linear.abs() > f32::EPSILON)
+                    .then(|| -self.current_motion.linear.signum());
+
+                let _ = self.log_tx.send(create_log(
+                    "SafetyController",
+                    LogLevel::Error,
+                    format!("Tilt limit exceeded - {} - stopping, only backing off allowed", attitude)
+                )).await;
+
+                self.active_command = None;
+                self.applied_factor = None;
+                self.send_protective_stop().await;
+            }
+            StabilityLevel::Stable => {
+                let _ = self.log_tx.send(create_log(
+                    "SafetyController",
+                    LogLevel::Info,
+                    format!("Attitude back within limits - {}", attitude)
+                )).await;
+
+                self.back_off_direction = None;
+                self.stance_tx.publish(StanceRequest::Adjust(StanceConfig {
+                    stance_type: StanceType::Normal,
+                    stability: 1.0,
+                })).await;
+            }
+        }
+    }
+
+    // Past the tilt limit the only motion allowed is slow and straight, in the
+    // back-off direction. Returns the limited behavior, or None if it must be blocked.
+    fn limit_to_back_off(&self, behavior: &Behavior) -> Option<Behavior> {
+        let fields = &self.config.protective_fields;
+        let motion = Motion::from_behavior(behavior, fields)?;
+
+        if motion.angular.abs() > 0.05 * fields.max_angular_velocity || motion.linear.abs() <= f32::EPSILON {
+            return None;
+        }
+
+        let direction = motion.linear.signum();
+        if self.back_off_direction.is_some_and(|allowed| allowed != direction) {
+            return None;
+        }
+
+        let speed = (motion.linear.abs() / fields.max_speed).min(self.stability.config().back_off_speed);
+        Some(Behavior::Drive { linear: direction * speed, angular: 0.0 })
+    }
+
+    fn sensor_timeout(&self) -> Duration {
+        Duration::from_millis(self.config.watchdog.sensor_timeout_ms)
+    }
+
+    // Age of the latest sensor data: time since it arrived or since it was
+    // measured, whichever is older
+    fn sensor_age(&self) -> Duration {
+        let data_age = self.latest_sensor_data.as_ref()
+            .and_then(|data| SystemTime::now().duration_since(data.timestamp).ok())
+            .unwrap_or_default();
+
+        self.last_sensor_received.elapsed().max(data_age)
+    }
+
+    async fn check_watchdogs(&mut self) {
+        let sensor_age = self.sensor_age();
+        if !self.sensor_fault && sensor_age > self.sensor_timeout() {
+            self.sensor_fault = true;
+            self.raise_fault(SafetyFault::SensorDataStale { age_ms: sensor_age.as_millis() as u64 }).await;
+        }
+
+        let command_age = self.last_command_received.elapsed();
+        let deadman = Duration::from_millis(self.config.watchdog.command_deadman_ms);
+        if !self.command_fault && self.current_motion.is_moving() && command_age > deadman {
+            self.command_fault = true;
+            self.raise_fault(SafetyFault::BehaviorCommandStale { age_ms: command_age.as_millis() as u64 }).await;
+        }
+    }
+
+    // Stops the robot when the fused position leaves the geofence. Only manual
+    // control may move it until it is back inside.
+    async fn check_geofence(&mut self) {
+        let Some(estimate) = self.position_rx.borrow().clone() else {
+            return;
+        };
+        let result = self.geofence.read().await.check(estimate.position);
+
+        match result {
+            Err(violation) if !self.geofence_fault => {
+                self.geofence_fault = true;
+                self.raise_fault(SafetyFault::GeofenceBreach { reason: violation.to_string() }).await;
+            }
+            Ok(()) if self.geofence_fault => {
+                self.clear_fault(SafetyFault::GeofenceBreach { reason: String::new() }).await;
+            }
+            _ => {}
+        }
+    }
+
+    // Watchdogs, geofence and attitude as component statuses
+    async fn report_diagnostics(&mut self) {
+        let sensor_age = self.sensor_age().as_millis();
+        let sensors = match self.sensor_fault {
+            true => DiagnosticStatus::new("sensors/data", DiagnosticLevel::Error, format!("Sensor data stale for {} ms", sensor_age)),
+            false => DiagnosticStatus::new("sensors/data", DiagnosticLevel::Ok, "Sensor data fresh"),
+        };
+        self.diagnostics.report(sensors.with("age_ms", sensor_age)).await;
+
+        let commands = match self.command_fault {
+            true => DiagnosticStatus::new("planning/commands", DiagnosticLevel::Error, "Behavior commands stopped while moving"),
+            false => DiagnosticStatus::new("planning/commands", DiagnosticLevel::Ok, "Behavior commands fresh"),
+        };
+        self.diagnostics.report(commands.with("age_ms", self.last_command_received.elapsed().as_millis())).await;
+
+        let geofence = match self.geofence_fault {
+            true => DiagnosticStatus::new("planning/geofence", DiagnosticLevel::Error, "Outside the geofence"),
+            false => DiagnosticStatus::new("planning/geofence", DiagnosticLevel::Ok, "Inside the geofence"),
+        };
+        self.diagnostics.report(geofence).await;
+
+        let (level, message) = match self.stability.level() {
+            StabilityLevel::Stable => (DiagnosticLevel::Ok, "Attitude within limits"),
+            StabilityLevel::Warning => (DiagnosticLevel::Warn, "Approaching tilt limit"),
+            StabilityLevel::Exceeded => (DiagnosticLevel::Error, "Tilt limit exceeded"),
+        };
+        let mut stability = DiagnosticStatus::new("motors/stability", level, message);
+        if let Some(sensor_data) = &self.latest_sensor_data {
+            let (roll, pitch) = roll_pitch(sensor_data.imu.orientation);
+            stability = stability
+                .with("roll_deg", format!("{:.1}", roll.to_degrees()))
+                .with("pitch_deg", format!("{:.1}", pitch.to_degrees()));
+        }
+        self.diagnostics.report(stability).await;
+    }
+
+    async fn raise_fault(&mut self, fault: SafetyFault) {
+        let _ = self.log_tx.send(create_log(
+            "SafetyController",
+            LogLevel::Error,
+            format!("Safety fault - {} - stopping motors", describe_fault(&fault))
+        )).await;
+
+        self.active_command = None;
+        self.applied_factor = None;
+        self.send_protective_stop().await;
+
+        self.report_fault(fault, true).await;
+    }
+
+    async fn clear_fault(&mut self, fault: SafetyFault) {
+        match fault {
+            SafetyFault::SensorDataStale { .. } => self.sensor_fault = false,
+            SafetyFault::BehaviorCommandStale { .. } => self.command_fault = false,
+            SafetyFault::GeofenceBreach { .. } => self.geofence_fault = false,
+            SafetyFault::ComponentFailure { .. } => {}
+        }
+
+        let message = match fault {
+            SafetyFault::GeofenceBreach { .. } => "Geofence fault cleared - back inside geofence".to_string(),
+            _ => format!("Watchdog fault cleared - {} fresh again", stale_input(&fault)),
+        };
+        let _ = self.log_tx.send(create_log(
+            "SafetyController",
+            LogLevel::Info,
+            message
+        )).await;
+
+        self.report_fault(fault, false).await;
+    }
+
+    // Faults go to the state manager and, as alerts, to operators
+    async fn report_fault(&self, fault: SafetyFault, active: bool) {
+        let report = FaultReport {
+            timestamp: SystemTime::now(),
+            fault,
+            active,
+        };
+        self.fault_tx.publish(report).await;
+    }
+
+    async fn validate_and_execute(&mut self, mut cmd: BehaviorCommand) {
         // Check emergency stop
         if self.emergency_stop {
-            let _ = self.log_tx.send(create_log(
-                "SafetyController",
-                LogLevel::Warn,
-                "Command blocked - emergency stop active".to_string()
-            )).await;
+            self.block(&cmd, LogLevel::Warn, BlockReason::EmergencyStop, "emergency stop active".to_string()).await;
+            return;
+        }
+
+        // Nothing but a stop may move on without trustworthy sensor data
+        if self.sensor_fault
+            && Motion::from_behavior(&cmd.behavior, &self.config.protective_fields).is_some_and(|m| m.is_moving())
+        {
+            self.block(&cmd, LogLevel::Warn, BlockReason::SensorStale, "sensor data stale".to_string()).await;
+            return;
+        }
+
+        // Outside the geofence only the operator may drive the robot back
+        if self.geofence_fault
+            && cmd.source != CommandSource::Manual
+            && Motion::from_behavior(&cmd.behavior, &self.config.protective_fields).is_some_and(|m| m.is_moving())
+        {
+            self.block(&cmd, LogLevel::Warn, BlockReason::Geofence, "geofence breached, only manual control allowed".to_string()).await;
             return;
         }
 
+        // Past the tilt limit, motion is limited to backing off
+        if self.stability.level() == StabilityLevel::Exceeded
+            && Motion::from_behavior(&cmd.behavior, &self.config.protective_fields).is_some_and(|m| m.is_moving())
+        {
+            match self.limit_to_back_off(&cmd.behavior) {
+                Some(back_off) => cmd.behavior = back_off,
+                None => {
+                    self.block(&cmd, LogLevel::Warn, BlockReason::TiltLimit, "tilt limit exceeded, only a straight back-off is allowed".to_string()).await;
+                    return;
+                }
+            }
+        }
+
         // Validate against sensor data
         if let Some(ref sensor_data) = self.latest_sensor_data {
             // Check for critical battery level
             if sensor_data.battery_level < 0.1 {
-                let _ = self.log_tx.send(create_log(
-                    "SafetyController",
-                    LogLevel::Error,
-                    format!("Command blocked - critical battery level: {:.1}%", sensor_data.battery_level * 100.0)
-                )).await;
+                let reason = format!("critical battery level: {:.1}%", sensor_data.battery_level * 100.0);
+                self.block(&cmd, LogLevel::Error, BlockReason::CriticalBattery, reason).await;
                 return;
             }
+        }
 
-            // Check for immediate obstacles in front
-            if let Behavior::MoveTowards { .. } = cmd.behavior {
-                if let Some(&front_distance) = sensor_data.distance_sensors.get(0) {
-                    if front_distance < 0.5 {
-                        let _ = self.log_tx.send(create_log(
-                            "SafetyController",
-                            LogLevel::Warn,
-                            format!("Command blocked - obstacle too close: {:.2}m", front_distance)
-                        )).await;
-                        return;
-                    }
-                }
+        // Motion commands are checked against the protective and warning fields
+        if Motion::from_behavior(&cmd.behavior, &self.config.protective_fields).is_some() {
+            self.active_command = Some(cmd);
+            self.applied_factor = None;
+            self.enforce_fields().await;
+        } else {
+            self.forward(cmd).await;
+        }
+    }
+
+    async fn block(&mut self, cmd: &BehaviorCommand, level: LogLevel, kind: BlockReason, reason: String) {
+        self.metrics.safety_block(kind);
+        rlog!(
+            self.log_tx,
+            LOG_MODULE,
+            level,
+            format!("Command blocked - {}", reason),
+            reason = kind.label(),
+            source = format!("{:?}", cmd.source),
+        );
+        self.reject(cmd.command_id, reason).await;
+    }
+
+    // Tells the operator a command of theirs was blocked. Commands are re-sent
+    // while they are active, so each one is only reported once.
+    async fn reject(&mut self, command_id: Option<CommandId>, reason: String) {
+        let Some(command_id) = command_id else {
+            return;
+        };
+        if self.rejected_command == Some(command_id) {
+            return;
+        }
+        self.rejected_command = Some(command_id);
+        self.ack_tx.publish(CommandAck::new("SafetyController", command_id, AckStatus::Rejected(reason))).await;
+    }
+
+    // Applies the protective and warning fields to the active motion command,
+    // re-issuing it whenever the required response changes
+    async fn enforce_fields(&mut self) {
+        let Some(cmd) = self.active_command.clone() else {
+            return;
+        };
+        let fields = &self.config.protective_fields;
+        let Some(commanded) = Motion::from_behavior(&cmd.behavior, fields) else {
+            return;
+        };
+
+        let response = match &self.latest_sensor_data {
+            Some(sensor_data) => fields.evaluate(&commanded, &self.current_motion, &sensor_data.distance_sensors),
+            None => FieldResponse::Clear,
+        };
+
+        let factor = match response {
+            FieldResponse::Stop { sensor, distance, limit } => {
+                rlog!(
+                    self.log_tx,
+                    Warn,
+                    "Protective stop - obstacle on sensor {} at {:.2}m (field {:.2}m)",
+                    sensor, distance, limit,
+                    sensor = sensor,
+                    distance = distance,
+                    limit = limit,
+                );
+                self.metrics.safety_block(BlockReason::ProtectiveStop);
+                self.reject(cmd.command_id, format!("protective stop, obstacle at {:.2}m", distance)).await;
+                self.active_command = None;
+                self.applied_factor = None;
+                self.send_protective_stop().await;
+                return;
             }
+            FieldResponse::SlowDown { factor, .. } => factor,
+            FieldResponse::Clear => 1.0,
+        };
+
+        // Avoid flooding the hardware with near-identical commands
+        if let Some(applied) = self.applied_factor
+            && (applied - factor).abs() < 0.05
+        {
+            return;
+        }
+
+        if let FieldResponse::SlowDown { factor, sensor, distance, limit } = response {
+            rlog!(
+                self.log_tx,
+                Warn,
+                "Slowing to {:.0}% - obstacle on sensor {} at {:.2}m (warning field {:.2}m)",
+                factor * 100.0, sensor, distance, limit,
+                factor = factor,
+                sensor = sensor,
+                distance = distance,
+                limit = limit,
+            );
+        }
+
+        self.applied_factor = Some(factor);
+        let mut scaled = cmd;
+        scaled.behavior = scale_behavior(scaled.behavior, factor);
+        self.forward(scaled).await;
+    }
+
+    async fn forward(&mut self, mut cmd: BehaviorCommand) {
+        let motion = Motion::from_behavior(&cmd.behavior, &self.config.protective_fields);
+        if let Some(trace) = cmd.trace.as_mut() {
+            self.tracer.hop(trace, PipelineStage::Safety);
         }
 
         // Command is safe, forward to Hardware Interface
-        if let Err(_) = self.hardware_interface_tx.send(cmd).await {
-            let _ = self.log_tx.send(create_log(
-                "SafetyController",
-                LogLevel::Error,
-                "Failed to send validated command to hardware interface".to_string()
-            )).await;
-        } else {
-            let _ = self.log_tx.send(create_log(
-                "SafetyController",
-                LogLevel::Debug,
-                "Command validated and forwarded to hardware interface".to_string()
-            )).await;
+        self.hardware_interface_tx.publish(cmd).await;
+        if let Some(motion) = motion {
+            self.current_motion = motion;
         }
+        rlog!(self.log_tx, "SafetyController", LogLevel::Debug, "Command validated and forwarded to hardware interface");
     }
 
     async fn check_safety(&mut self, sensor_data: &SensorData) {
@@ -160,7 +607,44 @@ impl SafetyController {
             timestamp: std::time::SystemTime::now(),
             behavior: Behavior::EmergencyStop,
             priority: 10, // Highest priority for emergency stop
+            source: CommandSource::Safety,
+            command_id: None,
+            trace: None,
         };
-        let _ = self.hardware_interface_tx.send(stop_cmd).await;
+        self.hardware_interface_tx.publish(stop_cmd).await;
+        self.current_motion = Motion::default();
+    }
+
+    async fn send_protective_stop(&mut self) {
+        let stop_cmd = BehaviorCommand {
+            timestamp: std::time::SystemTime::now(),
+            behavior: Behavior::Drive { linear: 0.0, angular: 0.0 },
+            priority: 10,
+            source: CommandSource::Safety,
+            command_id: None,
+            trace: None,
+        };
+        self.hardware_interface_tx.publish(stop_cmd).await;
+        self.current_motion = Motion::default();
+    }
+}
+
+// Name of the input a watchdog fault refers to
+fn stale_input(fault: &SafetyFault) -> &'static str {
+    match fault {
+        SafetyFault::SensorDataStale { .. } => "sensor data",
+        SafetyFault::BehaviorCommandStale { .. } => "behavior commands",
+        SafetyFault::GeofenceBreach { .. } => "position",
+        SafetyFault::ComponentFailure { .. } => "component status",
+    }
+}
+
+fn describe_fault(fault: &SafetyFault) -> String {
+    match fault {
+        SafetyFault::SensorDataStale { age_ms } | SafetyFault::BehaviorCommandStale { age_ms } => {
+            format!("{} stale for {} ms", stale_input(fault), age_ms)
+        }
+        SafetyFault::GeofenceBreach { reason } => format!("geofence breached, {}", reason),
+        SafetyFault::ComponentFailure { component, message } => format!("{} failed, {}", component, message),
     }
 }
@@ -0,0 +1,111 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StabilityConfig {
+    pub track_width: f32,      // meters between left and right wheels
+    pub wheelbase: f32,        // meters between front and rear axles
+    pub cg_height: f32,        // meters, centre of gravity above ground
+    pub safety_factor: f32,    // fraction of the static tip-over angle allowed
+    pub warning_fraction: f32, // fraction of the limit at which stance compensation starts
+    pub hysteresis: f32,       // radians an angle must drop below a threshold to clear it
+    pub back_off_speed: f32,   // normalised speed allowed while backing off
+}
+
+impl Default for StabilityConfig {
+    fn default() -> Self {
+        Self {
+            track_width: 0.30, // Matches CalibrationData::wheel_base
+            wheelbase: 0.40,
+            cg_height: 0.25,
+            safety_factor: 0.6,
+            warning_fraction: 0.7,
+            hysteresis: 0.05,
+            back_off_speed: 0.2,
+        }
+    }
+}
+
+impl StabilityConfig {
+    // Static tip-over happens when the centre of gravity passes over the
+    // wheel contact line, at atan(half track / cg height) for roll
+    pub fn roll_limit(&self) -> f32 {
+        (self.track_width / 2.0).atan2(self.cg_height) * self.safety_factor
+    }
+
+    pub fn pitch_limit(&self) -> f32 {
+        (self.wheelbase / 2.0).atan2(self.cg_height) * self.safety_factor
+    }
+}
+
+// Roll and pitch in radians from an IMU quaternion [w, x, y, z]. Axes follow
+// REP-103 (x forward, y left, z up): positive roll lifts the left side,
+// positive pitch puts the nose down.
+pub fn roll_pitch(q: [f32; 4]) -> (f32, f32) {
+    let [w, x, y, z] = q;
+
+    let roll = (2.0 * (w * x + y * z)).atan2(1.0 - 2.0 * (x * x + y * y));
+    let pitch = (2.0 * (w * y - z * x)).clamp(-1.0, 1.0).asin();
+
+    (roll, pitch)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StabilityLevel {
+    Stable,
+    Warning,  // Approaching the limit, compensate with stance
+    Exceeded, // Past the limit, stop or back off
+}
+
+pub struct StabilityMonitor {
+    config: StabilityConfig,
+    level: StabilityLevel,
+}
+
+impl StabilityMonitor {
+    pub fn new(config: StabilityConfig) -> Self {
+        Self {
+            config,
+            level: StabilityLevel::Stable,
+        }
+    }
+
+    pub fn config(&self) -> &StabilityConfig {
+        &self.config
+    }
+
+    pub fn level(&self) -> StabilityLevel {
+        self.level
+    }
+
+    // Fraction of the roll or pitch limit used, whichever is closer to its limit
+    pub fn tilt_ratio(&self, roll: f32, pitch: f32) -> f32 {
+        (roll.abs() / self.config.roll_limit()).max(pitch.abs() / self.config.pitch_limit())
+    }
+
+    // Updates the level from the latest attitude, returning the new level when it
+    // changes. Levels only drop once the tilt is `hysteresis` below the threshold.
+    pub fn update(&mut self, roll: f32, pitch: f32) -> Option<StabilityLevel> {
+        let ratio = self.tilt_ratio(roll, pitch);
+        // Hysteresis as a fraction of the tighter of the two limits
+        let margin = self.config.hysteresis / self.config.roll_limit().min(self.config.pitch_limit());
+        let warning = self.config.warning_fraction;
+
+        let level = match self.level {
+            StabilityLevel::Stable if ratio >= 1.0 => StabilityLevel::Exceeded,
+            StabilityLevel::Stable if ratio >= warning => StabilityLevel::Warning,
+            StabilityLevel::Warning if ratio >= 1.0 => StabilityLevel::Exceeded,
+            StabilityLevel::Warning if ratio < warning - margin => StabilityLevel::Stable,
+            StabilityLevel::Exceeded if ratio < warning - margin => StabilityLevel::Stable,
+            StabilityLevel::Exceeded if ratio < 1.0 - margin => StabilityLevel::Warning,
+            current => current,
+        };
+
+        if level != self.level {
+            self.level = level;
+            Some(level)
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,555 @@
+use crate::types::{
+    BehaviorCommand, CalibrationData, CommandAck, CommandId, CommandRequest, DiagnosticStatus, DiagnosticSummary,
+    EnvironmentState, FaultReport, Goal, HardwareStatus, ManualControl, MissionEvent, Path, RobotState, ScanResult,
+    SensorData, StanceConfig, StatusUpdate, UserFeedback,
+};
+use crate::perception::environment_understanding::ScanRequest;
+use crate::perception::model_calibration_storage::CalibrationRequest;
+use crate::perception::stance::StanceRequest;
+use crate::planning::goal_planning::PathRequest;
+use crate::infra::pipeline::PipelineDiagnostics;
+use crate::infra::log_levels::LogLinkStats;
+use serde::Serialize;
+use std::any::Any;
+use std::collections::{BTreeMap, VecDeque};
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Instant, SystemTime};
+use tokio::sync::{broadcast, Notify};
+
+// Messages held for taps before a slow tap starts missing them
+const TAP_BUFFER: usize = 1024;
+
+// Topics published on the bus
+pub mod topics {
+    use super::*;
+
+    // Readings straight from the sensor array
+    pub const SENSOR_RAW: Topic<SensorData> = Topic::new("sensor/raw");
+    // Readings forwarded by the hardware interface to the input manager
+    pub const SENSOR_HARDWARE: Topic<SensorData> = Topic::new("sensor/hardware");
+    // Readings routed by the input manager after the hardware interface
+    pub const SENSOR_DATA: Topic<SensorData> = Topic::new("sensor/data");
+    // Obstacles and terrain from environment understanding, once per reading
+    pub const ENVIRONMENT: Topic<EnvironmentState> = Topic::new("perception/environment");
+    // Robot state from the state manager, on every transition
+    pub const ROBOT_STATE: Topic<RobotState> = Topic::new("state/robot");
+    // Statistics of every topic and link, published by the bus monitor
+    pub const BUS_DIAGNOSTICS: Topic<BusDiagnostics> = Topic::new("diagnostics/bus");
+    // Component statuses, reported by the modules that own them
+    pub const DIAGNOSTIC_STATUS: Topic<DiagnosticStatus> = Topic::new("diagnostics/status");
+    // Component statuses rolled up by group, published by the diagnostics aggregator
+    pub const DIAGNOSTIC_SUMMARY: Topic<DiagnosticSummary> = Topic::new("diagnostics/summary");
+    // Latency and rates of the sensor to motor pipeline, published by the pipeline monitor
+    pub const PIPELINE_DIAGNOSTICS: Topic<PipelineDiagnostics> = Topic::new("diagnostics/pipeline");
+
+    // Commands from direct user input and user instructions to the input manager
+    pub const USER_COMMANDS: Topic<CommandRequest> = Topic::new("commands/user");
+    // Operator commands from the communication module to user instructions
+    pub const OPERATOR_COMMANDS: Topic<CommandRequest> = Topic::new("commands/operator");
+    // Commands routed by the input manager to the state and task/mission managers
+    pub const ROUTED_COMMANDS: Topic<CommandRequest> = Topic::new("commands/routed");
+    // Manual control accepted by the state manager, with the command it came from
+    pub const MANUAL_CONTROL: Topic<(CommandId, ManualControl)> = Topic::new("commands/manual");
+    // Command acknowledgements for the communication module
+    pub const COMMAND_ACKS: Topic<CommandAck> = Topic::new("commands/acks");
+    // Ids of missions the task/mission manager accepted or resumed
+    pub const MISSIONS_ACCEPTED: Topic<u64> = Topic::new("missions/accepted");
+    // Task progress, resume offers and cleared faults from the task/mission manager
+    pub const MISSION_EVENTS: Topic<MissionEvent> = Topic::new("missions/events");
+    // Goals from the task/mission manager to goal planning
+    pub const GOALS: Topic<Goal> = Topic::new("planning/goals");
+    // Path requests from goal planning to obstacle avoidance, and the planned paths back
+    pub const PATH_REQUESTS: Topic<PathRequest> = Topic::new("planning/path_requests");
+    pub const PATH_REPLIES: Topic<Path> = Topic::new("planning/path_replies");
+    // Paths for behaviour from goal planning and from obstacle avoidance
+    pub const PLANNED_PATHS: Topic<Path> = Topic::new("planning/paths");
+    pub const AVOIDANCE_PATHS: Topic<Path> = Topic::new("perception/avoidance_paths");
+    // Scans from the task/mission manager to environment understanding, and their results
+    pub const SCAN_REQUESTS: Topic<ScanRequest> = Topic::new("perception/scan_requests");
+    pub const SCAN_RESULTS: Topic<ScanResult> = Topic::new("perception/scan_results");
+    // Stance queries and adjustments, one topic per asking module, and the replies
+    pub const STANCE_OBSTACLE_REQUESTS: Topic<StanceRequest> = Topic::new("stance/requests/obstacle");
+    pub const STANCE_GOAL_REQUESTS: Topic<StanceRequest> = Topic::new("stance/requests/goal");
+    pub const STANCE_SAFETY_REQUESTS: Topic<StanceRequest> = Topic::new("stance/requests/safety");
+    pub const STANCE_OBSTACLE_REPLIES: Topic<StanceConfig> = Topic::new("stance/replies/obstacle");
+    pub const STANCE_GOAL_REPLIES: Topic<StanceConfig> = Topic::new("stance/replies/goal");
+    // Stance changes for behaviour
+    pub const STANCE: Topic<StanceConfig> = Topic::new("stance/current");
+    // Behaviour -> command arbiter -> safety controller -> hardware interface
+    pub const BEHAVIOR_COMMANDS: Topic<BehaviorCommand> = Topic::new("control/behavior");
+    pub const ARBITRATED_COMMANDS: Topic<BehaviorCommand> = Topic::new("control/arbitrated");
+    pub const VALIDATED_COMMANDS: Topic<BehaviorCommand> = Topic::new("control/validated");
+    // Calibration requests from the command arbiter, and the calibration back
+    pub const CALIBRATION_REQUESTS: Topic<CalibrationRequest> = Topic::new("calibration/requests");
+    pub const CALIBRATION_DATA: Topic<CalibrationData> = Topic::new("calibration/data");
+    // Faults raised and cleared by the safety controller and the diagnostics aggregator
+    pub const SAFETY_FAULTS: Topic<FaultReport> = Topic::new("safety/faults");
+    // Hardware status from the hardware interface, and as passed on by the input manager
+    pub const HARDWARE_STATUS: Topic<HardwareStatus> = Topic::new("hardware/status");
+    pub const HARDWARE_HEALTH: Topic<HardwareStatus> = Topic::new("hardware/health");
+    // Status updates from the output manager: significant changes for user
+    // feedback, changes and periodic updates for the communication module
+    pub const STATUS_CHANGES: Topic<StatusUpdate> = Topic::new("status/changes");
+    pub const STATUS_UPDATES: Topic<StatusUpdate> = Topic::new("status/updates");
+    // Feedback from the user feedback module to the communication module
+    pub const USER_FEEDBACK: Topic<UserFeedback> = Topic::new("feedback/user");
+}
+
+// A topic name tied to the type of its messages
+pub struct Topic<T> {
+    pub name: &'static str,
+    _message: PhantomData<fn() -> T>,
+}
+
+impl<T> Topic<T> {
+    pub const fn new(name: &'static str) -> Self {
+        Self { name, _message: PhantomData }
+    }
+}
+
+impl<T> Clone for Topic<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Topic<T> {}
+
+// How a subscriber's queue handles messages it has not read yet, and so
+// what a slow subscriber costs: with LatestOnly and Bounded it misses
+// messages, with Lossless its publisher waits
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum QueuePolicy {
+    LatestOnly,      // Keep latest: only the newest message is kept
+    Bounded(usize),  // Drop oldest: up to n messages, the oldest dropped for a new one
+    Lossless(usize), // Block: up to n messages, publishers wait for room
+}
+
+// One subscriber's queue on a topic
+#[derive(Debug, Clone, Serialize)]
+pub struct LinkStats {
+    pub subscriber: &'static str,
+    pub policy: QueuePolicy,
+    pub depth: usize,      // Messages waiting now
+    pub peak_depth: usize, // Most messages ever waiting
+    pub sent: u64,
+    pub received: u64,
+    pub dropped: u64,
+    pub send_time_us: u64,     // Total time publishers spent handing messages over
+    pub max_send_time_us: u64, // Longest single hand-over
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TopicStats {
+    pub topic: &'static str,
+    pub message_type: &'static str,
+    pub publishers: usize,
+    pub published: u64,
+    pub dropped: u64, // Messages subscribers lost to their queue policy
+    pub queued: usize, // Messages waiting in subscriber queues
+    pub last_published: Option<SystemTime>,
+    pub links: Vec<LinkStats>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BusDiagnostics {
+    pub timestamp: SystemTime,
+    pub topics: Vec<TopicStats>,
+    pub log: LogLinkStats, // The log channel, which is not a bus topic
+}
+
+// A message as seen by taps, serialized to JSON
+#[derive(Debug, Clone)]
+pub struct TapMessage {
+    pub topic: &'static str,
+    pub timestamp: SystemTime,
+    pub json: Arc<str>,
+}
+
+// In-process publish/subscribe bus. Cloning gives another handle to the same
+// bus. Every topic is also sent to taps, serialized, while any are attached.
+#[derive(Clone)]
+pub struct Bus {
+    topics: Arc<Mutex<BTreeMap<&'static str, Arc<dyn AnyTopic>>>>,
+    tap_tx: broadcast::Sender<TapMessage>,
+}
+
+impl Default for Bus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Bus {
+    pub fn new() -> Self {
+        let (tap_tx, _) = broadcast::channel(TAP_BUFFER);
+        Self {
+            topics: Arc::new(Mutex::new(BTreeMap::new())),
+            tap_tx,
+        }
+    }
+
+    pub fn publisher<T>(&self, topic: Topic<T>) -> Publisher<T>
+    where
+        T: Clone + Serialize + Send + Sync + 'static,
+    {
+        let inner = self.topic(topic);
+        inner.publishers.fetch_add(1, Ordering::Relaxed);
+        inner.closed.store(false, Ordering::Release);
+        Publisher { inner, tap_tx: self.tap_tx.clone() }
+    }
+
+    // Subscribes with a queue of its own, named after the subscribing module
+    // in link statistics
+    pub fn subscribe<T>(&self, topic: Topic<T>, subscriber: &'static str, policy: QueuePolicy) -> Subscriber<T>
+    where
+        T: Clone + Serialize + Send + Sync + 'static,
+    {
+        let inner = self.topic(topic);
+        let queue = Arc::new(Queue::new(subscriber, policy));
+        inner.subscribers.lock().unwrap().push(Arc::downgrade(&queue));
+        Subscriber { queue, topic: inner }
+    }
+
+    // Every message on every topic, for recorders and visualization
+    pub fn tap(&self) -> broadcast::Receiver<TapMessage> {
+        self.tap_tx.subscribe()
+    }
+
+    pub fn stats(&self) -> Vec<TopicStats> {
+        self.topics.lock().unwrap().values().map(|topic| topic.stats()).collect()
+    }
+
+    fn topic<T>(&self, topic: Topic<T>) -> Arc<TopicInner<T>>
+    where
+        T: Clone + Serialize + Send + Sync + 'static,
+    {
+        let mut topics = self.topics.lock().unwrap();
+        let entry = topics.entry(topic.name)
+            .or_insert_with(|| Arc::new(TopicInner::<T>::new(topic.name)));
+        entry.clone().as_any().downcast::<TopicInner<T>>()
+            .unwrap_or_else(|_| panic!("bus topic {} used with two message types", topic.name))
+    }
+}
+
+trait AnyTopic: Send + Sync {
+    fn stats(&self) -> TopicStats;
+    fn as_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync>;
+}
+
+struct TopicInner<T> {
+    name: &'static str,
+    subscribers: Mutex<Vec<Weak<Queue<T>>>>,
+    publishers: AtomicUsize,
+    closed: AtomicBool, // Set once every publisher is dropped
+    published: AtomicU64,
+    last_published: Mutex<Option<SystemTime>>,
+}
+
+impl<T> TopicInner<T> {
+    fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            subscribers: Mutex::new(Vec::new()),
+            publishers: AtomicUsize::new(0),
+            closed: AtomicBool::new(false),
+            published: AtomicU64::new(0),
+            last_published: Mutex::new(None),
+        }
+    }
+
+    // Queues of subscribers still alive, forgetting dropped ones
+    fn queues(&self) -> Vec<Arc<Queue<T>>> {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        subscribers.retain(|queue| queue.strong_count() > 0);
+        subscribers.iter().filter_map(Weak::upgrade).collect()
+    }
+}
+
+impl<T: Send + Sync + 'static> AnyTopic for TopicInner<T> {
+    fn stats(&self) -> TopicStats {
+        let links: Vec<LinkStats> = self.queues().iter().map(|queue| queue.stats()).collect();
+        TopicStats {
+            topic: self.name,
+            message_type: std::any::type_name::<T>().rsplit("::").next().unwrap_or_default(),
+            publishers: self.publishers.load(Ordering::Relaxed),
+            published: self.published.load(Ordering::Relaxed),
+            dropped: links.iter().map(|link| link.dropped).sum(),
+            queued: links.iter().map(|link| link.depth).sum(),
+            last_published: *self.last_published.lock().unwrap(),
+            links,
+        }
+    }
+
+    fn as_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
+        self
+    }
+}
+
+struct Queue<T> {
+    subscriber: &'static str,
+    policy: QueuePolicy,
+    items: Mutex<VecDeque<T>>,
+    readable: Notify, // A message arrived or the topic closed
+    writable: Notify, // A message was taken, for publishers waiting on a lossless queue
+    peak_depth: AtomicUsize,
+    sent: AtomicU64,
+    received: AtomicU64,
+    dropped: AtomicU64,
+    send_time_us: AtomicU64,
+    max_send_time_us: AtomicU64,
+}
+
+impl<T> Queue<T> {
+    fn new(subscriber: &'static str, policy: QueuePolicy) -> Self {
+        Self {
+            subscriber,
+            policy,
+            items: Mutex::new(VecDeque::new()),
+            readable: Notify::new(),
+            writable: Notify::new(),
+            peak_depth: AtomicUsize::new(0),
+            sent: AtomicU64::new(0),
+            received: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+            send_time_us: AtomicU64::new(0),
+            max_send_time_us: AtomicU64::new(0),
+        }
+    }
+
+    fn stats(&self) -> LinkStats {
+        LinkStats {
+            subscriber: self.subscriber,
+            policy: self.policy,
+            depth: self.items.lock().unwrap().len(),
+            peak_depth: self.peak_depth.load(Ordering::Relaxed),
+            sent: self.sent.load(Ordering::Relaxed),
+            received: self.received.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            send_time_us: self.send_time_us.load(Ordering::Relaxed),
+            max_send_time_us: self.max_send_time_us.load(Ordering::Relaxed),
+        }
+    }
+
+    async fn push(&self, message: T) {
+        let started = Instant::now();
+        self.deliver(message).await;
+
+        let elapsed = started.elapsed().as_micros() as u64;
+        self.sent.fetch_add(1, Ordering::Relaxed);
+        self.send_time_us.fetch_add(elapsed, Ordering::Relaxed);
+        self.max_send_time_us.fetch_max(elapsed, Ordering::Relaxed);
+    }
+
+    async fn deliver(&self, message: T) {
+        match self.policy {
+            QueuePolicy::LatestOnly => self.push_dropping(message, 1),
+            QueuePolicy::Bounded(capacity) => self.push_dropping(message, capacity.max(1)),
+            QueuePolicy::Lossless(capacity) => {
+                loop {
+                    {
+                        let mut items = self.items.lock().unwrap();
+                        if items.len() < capacity.max(1) {
+                            items.push_back(message);
+                            self.peak_depth.fetch_max(items.len(), Ordering::Relaxed);
+                            break;
+                        }
+                    }
+                    self.writable.notified().await;
+                }
+                self.readable.notify_one();
+            }
+        }
+    }
+
+    fn push_dropping(&self, message: T, capacity: usize) {
+        let mut items = self.items.lock().unwrap();
+        while items.len() >= capacity {
+            items.pop_front();
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        items.push_back(message);
+        self.peak_depth.fetch_max(items.len(), Ordering::Relaxed);
+        drop(items);
+        self.readable.notify_one();
+    }
+
+    fn pop(&self) -> Option<T> {
+        let message = self.items.lock().unwrap().pop_front();
+        if message.is_some() {
+            self.received.fetch_add(1, Ordering::Relaxed);
+            self.writable.notify_one();
+        }
+        message
+    }
+}
+
+pub struct Publisher<T> {
+    inner: Arc<TopicInner<T>>,
+    tap_tx: broadcast::Sender<TapMessage>,
+}
+
+impl<T: Clone + Serialize + Send + Sync + 'static> Publisher<T> {
+    // Delivers to every subscriber by its queue policy. Only waits while a
+    // lossless subscriber's queue is full.
+    pub async fn publish(&self, message: T) {
+        let now = SystemTime::now();
+        self.inner.published.fetch_add(1, Ordering::Relaxed);
+        *self.inner.last_published.lock().unwrap() = Some(now);
+
+        if self.tap_tx.receiver_count() > 0
+            && let Ok(json) = serde_json::to_string(&message)
+        {
+            let _ = self.tap_tx.send(TapMessage { topic: self.inner.name, timestamp: now, json: json.into() });
+        }
+
+        let mut queues = self.inner.queues();
+        if let Some(last) = queues.pop() {
+            for queue in queues {
+                queue.push(message.clone()).await;
+            }
+            last.push(message).await;
+        }
+    }
+}
+
+impl<T> Clone for Publisher<T> {
+    fn clone(&self) -> Self {
+        self.inner.publishers.fetch_add(1, Ordering::Relaxed);
+        Self { inner: self.inner.clone(), tap_tx: self.tap_tx.clone() }
+    }
+}
+
+impl<T> Drop for Publisher<T> {
+    fn drop(&mut self) {
+        if self.inner.publishers.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.inner.closed.store(true, Ordering::Release);
+            for queue in self.inner.queues() {
+                queue.readable.notify_one();
+            }
+        }
+    }
+}
+
+pub struct Subscriber<T> {
+    queue: Arc<Queue<T>>,
+    topic: Arc<TopicInner<T>>,
+}
+
+impl<T> Subscriber<T> {
+    // Next message, or None once the queue is empty and every publisher of
+    // the topic has been dropped
+    pub async fn recv(&mut self) -> Option<T> {
+        loop {
+            if let Some(message) = self.queue.pop() {
+                return Some(message);
+            }
+            if self.topic.closed.load(Ordering::Acquire) {
+                return None;
+            }
+            self.queue.readable.notified().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    const NUMBERS: Topic<u32> = Topic::new("test/numbers");
+
+    fn link(bus: &Bus, subscriber: &str) -> LinkStats {
+        bus.stats().into_iter()
+            .flat_map(|topic| topic.links)
+            .find(|link| link.subscriber == subscriber)
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn latest_only_keeps_the_newest_message() {
+        let bus = Bus::new();
+        let publisher = bus.publisher(NUMBERS);
+        let mut subscriber = bus.subscribe(NUMBERS, "Latest", QueuePolicy::LatestOnly);
+
+        for n in 1..=5 {
+            publisher.publish(n).await;
+        }
+
+        assert_eq!(subscriber.recv().await, Some(5));
+        let stats = link(&bus, "Latest");
+        assert_eq!((stats.sent, stats.received, stats.dropped, stats.depth), (5, 1, 4, 0));
+    }
+
+    #[tokio::test]
+    async fn bounded_drops_the_oldest_at_capacity() {
+        let bus = Bus::new();
+        let publisher = bus.publisher(NUMBERS);
+        let mut subscriber = bus.subscribe(NUMBERS, "Bounded", QueuePolicy::Bounded(3));
+
+        for n in 1..=5 {
+            publisher.publish(n).await;
+        }
+
+        let stats = link(&bus, "Bounded");
+        assert_eq!((stats.sent, stats.dropped, stats.depth, stats.peak_depth), (5, 2, 3, 3));
+        for n in 3..=5 {
+            assert_eq!(subscriber.recv().await, Some(n));
+        }
+        assert_eq!(bus.stats()[0].dropped, 2);
+    }
+
+    #[tokio::test]
+    async fn lossless_waits_instead_of_dropping() {
+        let bus = Bus::new();
+        let publisher = bus.publisher(NUMBERS);
+        let mut subscriber = bus.subscribe(NUMBERS, "Lossless", QueuePolicy::Lossless(2));
+
+        let sending = tokio::spawn(async move {
+            for n in 1..=5 {
+                publisher.publish(n).await;
+            }
+        });
+
+        // The publisher stalls on the full queue until the subscriber reads
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!sending.is_finished());
+        assert_eq!(link(&bus, "Lossless").depth, 2);
+
+        for n in 1..=5 {
+            assert_eq!(subscriber.recv().await, Some(n));
+        }
+        sending.await.unwrap();
+
+        let stats = link(&bus, "Lossless");
+        assert_eq!((stats.sent, stats.received, stats.dropped, stats.peak_depth), (5, 5, 0, 2));
+    }
+
+    #[tokio::test]
+    async fn topic_closes_when_every_publisher_is_dropped() {
+        let bus = Bus::new();
+        let publisher = bus.publisher(NUMBERS);
+        let second = publisher.clone();
+        let mut subscriber = bus.subscribe(NUMBERS, "Closing", QueuePolicy::Bounded(4));
+
+        publisher.publish(1).await;
+        drop(publisher);
+        second.publish(2).await;
+
+        let waiting = tokio::spawn(async move {
+            let mut received = Vec::new();
+            while let Some(n) = subscriber.recv().await {
+                received.push(n);
+            }
+            received
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!waiting.is_finished());
+
+        drop(second);
+        let received = tokio::time::timeout(Duration::from_secs(1), waiting).await.unwrap().unwrap();
+        assert_eq!(received, vec![1, 2]);
+    }
+}
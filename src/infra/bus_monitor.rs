@@ -0,0 +1,183 @@
+use crate::types::{DiagnosticStatus, DiagnosticLevel, LogLevel};
+use crate::infra::logger::create_log;
+use crate::infra::log_levels::{LogLinkStats, LogSender};
+use crate::infra::bus::{Bus, BusDiagnostics, LinkStats, Publisher, QueuePolicy, topics};
+use crate::infra::diagnostics::DiagnosticReporter;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::SystemTime;
+use tokio::sync::broadcast;
+use tokio::time::{Duration, interval};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BusConfig {
+    pub diagnostics_interval_ms: u64, // Time between diagnostics/bus messages
+    pub slow_send_ms: f64,            // Mean hand-over time on a Lossless link that is warned about
+}
+
+impl Default for BusConfig {
+    fn default() -> Self {
+        Self {
+            diagnostics_interval_ms: 1000,
+            slow_send_ms: 10.0,
+        }
+    }
+}
+
+// Link counters at the previous report, to tell what changed since
+#[derive(Default)]
+struct LinkSnapshot {
+    sent: u64,
+    dropped: u64,
+    send_time_us: u64,
+}
+
+// Publishes bus statistics on diagnostics/bus and warns about links that
+// drop messages or hold up their publishers
+pub struct BusMonitor {
+    bus: Bus,
+    diagnostics_pub: Publisher<BusDiagnostics>,
+    status: DiagnosticReporter,
+    log_tx: LogSender,
+    shutdown_rx: broadcast::Receiver<()>,
+    config: BusConfig,
+    previous: HashMap<(&'static str, &'static str), LinkSnapshot>,
+    previous_log: LogLinkStats,
+}
+
+impl BusMonitor {
+    pub fn new(
+        bus: Bus,
+        log_tx: LogSender,
+        shutdown_rx: broadcast::Receiver<()>,
+        config: BusConfig,
+    ) -> Self {
+        Self {
+            diagnostics_pub: bus.publisher(topics::BUS_DIAGNOSTICS),
+            status: DiagnosticReporter::new(bus.publisher(topics::DIAGNOSTIC_STATUS)),
+            bus,
+            log_tx,
+            shutdown_rx,
+            config,
+            previous: HashMap::new(),
+            previous_log: LogLinkStats::default(),
+        }
+    }
+
+    pub async fn run(mut self) {
+        let _ = self.log_tx.send(create_log(
+            "BusMonitor",
+            LogLevel::Info,
+            "Starting bus monitor".to_string()
+        )).await;
+
+        let mut report = interval(Duration::from_millis(self.config.diagnostics_interval_ms.max(1)));
+
+        loop {
+            tokio::select! {
+                _ = self.shutdown_rx.recv() => {
+                    let _ = self.log_tx.send(create_log(
+                        "BusMonitor",
+                        LogLevel::Info,
+                        "Shutdown signal received".to_string()
+                    )).await;
+                    break;
+                }
+                _ = report.tick() => {
+                    self.report().await;
+                }
+            }
+        }
+
+        let _ = self.log_tx.send(create_log(
+            "BusMonitor",
+            LogLevel::Info,
+            "Stopped".to_string()
+        )).await;
+    }
+
+    async fn report(&mut self) {
+        let topics = self.bus.stats();
+        let mut warnings = Vec::new();
+
+        for topic in &topics {
+            for link in &topic.links {
+                if let Some(warning) = self.check_link(topic.topic, link) {
+                    let _ = self.log_tx.send(create_log(
+                        "BusMonitor",
+                        LogLevel::Warn,
+                        warning.clone()
+                    )).await;
+                    warnings.push(warning);
+                }
+            }
+        }
+
+        let log = self.log_tx.stats();
+        if let Some(warning) = self.check_log(&log) {
+            let _ = self.log_tx.send(create_log(
+                "BusMonitor",
+                LogLevel::Warn,
+                warning.clone()
+            )).await;
+            warnings.push(warning);
+        }
+
+        let status = match warnings.first() {
+            Some(warning) => DiagnosticStatus::new("comms/bus", DiagnosticLevel::Warn, warning.clone()),
+            None => DiagnosticStatus::new("comms/bus", DiagnosticLevel::Ok, "All links keeping up"),
+        };
+        self.status.report(status
+            .with("topics", topics.len())
+            .with("published", topics.iter().map(|topic| topic.published).sum::<u64>())
+            .with("dropped", topics.iter().map(|topic| topic.dropped).sum::<u64>())
+            .with("log_dropped", log.dropped)
+            .with("warnings", warnings.len())).await;
+
+        self.diagnostics_pub.publish(BusDiagnostics {
+            timestamp: SystemTime::now(),
+            topics,
+            log,
+        }).await;
+    }
+
+    // Drops on a Bounded link mean its subscriber falls behind; a slow
+    // Lossless link holds up its publisher. LatestOnly links drop by design.
+    fn check_link(&mut self, topic: &'static str, link: &LinkStats) -> Option<String> {
+        let previous = self.previous.entry((topic, link.subscriber)).or_default();
+        // A link recreated under the same names starts its counters again
+        let sent = link.sent.saturating_sub(previous.sent);
+        let dropped = link.dropped.saturating_sub(previous.dropped);
+        let send_time_us = link.send_time_us.saturating_sub(previous.send_time_us);
+        *previous = LinkSnapshot { sent: link.sent, dropped: link.dropped, send_time_us: link.send_time_us };
+
+        match link.policy {
+            QueuePolicy::Bounded(capacity) if dropped > 0 => Some(format!(
+                "{} -> {} dropped {} of {} messages, queue of {} full",
+                topic, link.subscriber, dropped, sent, capacity
+            )),
+            QueuePolicy::Lossless(_) if sent > 0 => {
+                let mean_ms = send_time_us as f64 / sent as f64 / 1000.0;
+                (mean_ms > self.config.slow_send_ms).then(|| format!(
+                    "{} -> {} is holding up its publisher, {:.1} ms per message with {} queued",
+                    topic, link.subscriber, mean_ms, link.depth
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    // Log entries are dropped rather than waited for, so any drop means the
+    // Logger has fallen behind
+    fn check_log(&mut self, log: &LogLinkStats) -> Option<String> {
+        let sent = log.sent.saturating_sub(self.previous_log.sent);
+        let dropped = log.dropped.saturating_sub(self.previous_log.dropped);
+        self.previous_log = *log;
+
+        (dropped > 0).then(|| format!(
+            "log -> Logger dropped {} of {} entries, channel of {} full",
+            dropped, sent + dropped, log.capacity
+        ))
+    }
+}
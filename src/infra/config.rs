@@ -0,0 +1,51 @@
+use crate::types::Geofence;
+use crate::control::command_arbiter::ArbiterConfig;
+use crate::control::safety_controller::SafetyConfig;
+use crate::infra::bus_monitor::BusConfig;
+use crate::infra::pipeline::PipelineConfig;
+use crate::infra::diagnostics::DiagnosticsConfig;
+use crate::infra::metrics::MetricsConfig;
+use crate::infra::log_levels::LogConfig;
+use crate::infra::recording::RecordingConfig;
+use crate::output::communication_module::OperatorLinkConfig;
+use crate::output::output_manager::StatusConfig;
+use crate::planning::energy_manager::EnergyConfig;
+use crate::planning::task_mission_manager::MissionConfig;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+// Default config file, used when present and no path is given
+pub const DEFAULT_CONFIG_PATH: &str = "rover.toml";
+
+// Top-level configuration, loaded from a TOML file. Every section and field is
+// optional and falls back to its default.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RoverConfig {
+    pub safety: SafetyConfig,
+    pub arbiter: ArbiterConfig,
+    pub geofence: Geofence,
+    pub mission: MissionConfig,
+    pub energy: EnergyConfig,
+    pub operator_link: OperatorLinkConfig,
+    pub status: StatusConfig,
+    pub bus: BusConfig,
+    pub pipeline: PipelineConfig,
+    pub diagnostics: DiagnosticsConfig,
+    pub metrics: MetricsConfig,
+    pub log: LogConfig,
+    pub recording: RecordingConfig,
+}
+
+impl RoverConfig {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let config: Self = toml::from_str(&contents)
+            .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+        config.operator_link.telemetry.validate()
+            .map_err(|e| format!("Invalid {}: operator_link.telemetry.{}", path.display(), e))?;
+        Ok(config)
+    }
+}
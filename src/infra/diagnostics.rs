@@ -0,0 +1,266 @@
+use crate::types::{DiagnosticLevel, DiagnosticStatus, DiagnosticGroup, DiagnosticSummary, FaultReport, SafetyFault, LogLevel};
+use crate::infra::logger::create_log;
+use crate::infra::log_levels::LogSender;
+use crate::infra::bus::{Publisher, Subscriber};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::SystemTime;
+use tokio::sync::broadcast;
+use tokio::time::{Duration, Instant, interval};
+
+// An unchanged status is still re-published this often, so the aggregator
+// can tell a quiet component from a silent one
+const HEARTBEAT: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DiagnosticsConfig {
+    pub publish_interval_ms: u64, // Time between diagnostics/summary messages
+    pub stale_ms: u64,            // Time without a report before a component is stale
+    pub critical: Vec<String>,    // Groups or components whose error or staleness puts the robot in Error
+}
+
+impl Default for DiagnosticsConfig {
+    fn default() -> Self {
+        Self {
+            publish_interval_ms: 1000,
+            stale_ms: 5000,
+            critical: vec!["motors/driver".to_string(), "motors/battery".to_string()],
+        }
+    }
+}
+
+impl DiagnosticsConfig {
+    fn is_critical(&self, name: &str) -> bool {
+        self.critical.iter().any(|critical| {
+            name == critical || name.strip_prefix(critical.as_str()).is_some_and(|rest| rest.starts_with('/'))
+        })
+    }
+}
+
+impl DiagnosticStatus {
+    pub fn new(name: &str, level: DiagnosticLevel, message: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            level,
+            message: message.into(),
+            values: Vec::new(),
+        }
+    }
+
+    pub fn with(mut self, key: &str, value: impl ToString) -> Self {
+        self.values.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    fn group(&self) -> &str {
+        self.name.split_once('/').map_or("other", |(group, _)| group)
+    }
+}
+
+// Publishes a module's component statuses on diagnostics/status. A status
+// goes out at once when its level or message changes, and otherwise at
+// most once per heartbeat.
+pub struct DiagnosticReporter {
+    status_pub: Publisher<DiagnosticStatus>,
+    last: HashMap<String, (DiagnosticLevel, String, Instant)>,
+}
+
+impl DiagnosticReporter {
+    pub fn new(status_pub: Publisher<DiagnosticStatus>) -> Self {
+        Self {
+            status_pub,
+            last: HashMap::new(),
+        }
+    }
+
+    pub async fn report(&mut self, status: DiagnosticStatus) {
+        let now = Instant::now();
+        let due = self.last.get(&status.name).is_none_or(|(level, message, sent)| {
+            *level != status.level || *message != status.message || now.duration_since(*sent) >= HEARTBEAT
+        });
+        if !due {
+            return;
+        }
+
+        self.last.insert(status.name.clone(), (status.level, status.message.clone(), now));
+        self.status_pub.publish(status).await;
+    }
+}
+
+struct Component {
+    status: DiagnosticStatus,
+    received: Instant,
+}
+
+// Collects component statuses, marks silent components stale and rolls them
+// up by group. Critical components in error or stale are reported as faults,
+// which puts the robot in Error until they recover.
+pub struct DiagnosticAggregator {
+    status_rx: Subscriber<DiagnosticStatus>,
+    summary_pub: Publisher<DiagnosticSummary>,
+    fault_tx: Publisher<FaultReport>,
+    log_tx: LogSender,
+    shutdown_rx: broadcast::Receiver<()>,
+    config: DiagnosticsConfig,
+    components: BTreeMap<String, Component>,
+    levels: HashMap<String, DiagnosticLevel>, // Level of each component at the last summary
+    failed: HashSet<String>,                  // Critical components reported as faults
+}
+
+impl DiagnosticAggregator {
+    pub fn new(
+        status_rx: Subscriber<DiagnosticStatus>,
+        summary_pub: Publisher<DiagnosticSummary>,
+        fault_tx: Publisher<FaultReport>,
+        log_tx: LogSender,
+        shutdown_rx: broadcast::Receiver<()>,
+        config: DiagnosticsConfig,
+    ) -> Self {
+        Self {
+            status_rx,
+            summary_pub,
+            fault_tx,
+            log_tx,
+            shutdown_rx,
+            config,
+            components: BTreeMap::new(),
+            levels: HashMap::new(),
+            failed: HashSet::new(),
+        }
+    }
+
+    pub async fn run(mut self) {
+        let _ = self.log_tx.send(create_log(
+            "Diagnostics",
+            LogLevel::Info,
+            "Starting diagnostics aggregator".to_string()
+        )).await;
+
+        let mut publish = interval(Duration::from_millis(self.config.publish_interval_ms.max(1)));
+
+        loop {
+            tokio::select! {
+                _ = self.shutdown_rx.recv() => {
+                    let _ = self.log_tx.send(create_log(
+                        "Diagnostics",
+                        LogLevel::Info,
+                        "Shutdown signal received".to_string()
+                    )).await;
+                    break;
+                }
+                Some(status) = self.status_rx.recv() => {
+                    self.components.insert(status.name.clone(), Component { status, received: Instant::now() });
+                }
+                _ = publish.tick() => {
+                    let summary = self.summarize();
+                    self.log_changes(&summary).await;
+                    self.check_critical(&summary).await;
+                    self.summary_pub.publish(summary).await;
+                }
+            }
+        }
+
+        let _ = self.log_tx.send(create_log(
+            "Diagnostics",
+            LogLevel::Info,
+            "Stopped".to_string()
+        )).await;
+    }
+
+    fn summarize(&self) -> DiagnosticSummary {
+        let stale_after = Duration::from_millis(self.config.stale_ms);
+        let mut groups: BTreeMap<&str, Vec<DiagnosticStatus>> = BTreeMap::new();
+
+        for component in self.components.values() {
+            let mut status = component.status.clone();
+            let silent = component.received.elapsed();
+            if silent > stale_after {
+                status.level = DiagnosticLevel::Stale;
+                status.message = format!("No report for {:.0} s, last: {}", silent.as_secs_f32(), status.message);
+            }
+            groups.entry(component.status.group()).or_default().push(status);
+        }
+
+        let groups: Vec<DiagnosticGroup> = groups.into_iter()
+            .map(|(name, components)| roll_up(name, components))
+            .collect();
+
+        DiagnosticSummary {
+            timestamp: SystemTime::now(),
+            level: groups.iter().map(|group| group.level).max().unwrap_or(DiagnosticLevel::Ok),
+            groups,
+        }
+    }
+
+    async fn log_changes(&mut self, summary: &DiagnosticSummary) {
+        for status in summary.groups.iter().flat_map(|group| &group.components) {
+            let previous = self.levels.insert(status.name.clone(), status.level);
+            if previous == Some(status.level) || (previous.is_none() && status.level == DiagnosticLevel::Ok) {
+                continue;
+            }
+
+            let level = match status.level {
+                DiagnosticLevel::Ok => LogLevel::Info,
+                DiagnosticLevel::Warn | DiagnosticLevel::Stale => LogLevel::Warn,
+                DiagnosticLevel::Error => LogLevel::Error,
+            };
+            let _ = self.log_tx.send(create_log(
+                "Diagnostics",
+                level,
+                format!("{} {:?}: {}", status.name, status.level, status.message)
+            )).await;
+        }
+    }
+
+    async fn check_critical(&mut self, summary: &DiagnosticSummary) {
+        for status in summary.groups.iter().flat_map(|group| &group.components) {
+            if !self.config.is_critical(&status.name) {
+                continue;
+            }
+
+            let failing = status.level >= DiagnosticLevel::Stale;
+            let reported = self.failed.contains(&status.name);
+            if failing == reported {
+                continue;
+            }
+
+            if failing {
+                self.failed.insert(status.name.clone());
+            } else {
+                self.failed.remove(&status.name);
+            }
+
+            let report = FaultReport {
+                timestamp: SystemTime::now(),
+                fault: SafetyFault::ComponentFailure {
+                    component: status.name.clone(),
+                    message: status.message.clone(),
+                },
+                active: failing,
+            };
+            self.fault_tx.publish(report).await;
+        }
+    }
+}
+
+// A group takes the level of its worst component and names it
+fn roll_up(name: &str, components: Vec<DiagnosticStatus>) -> DiagnosticGroup {
+    let level = components.iter().map(|status| status.level).max().unwrap_or(DiagnosticLevel::Ok);
+    let not_ok = components.iter().filter(|status| status.level != DiagnosticLevel::Ok).count();
+
+    let message = match components.iter().find(|status| status.level == level) {
+        Some(worst) if level != DiagnosticLevel::Ok => {
+            let others = if not_ok > 1 { format!(" (+{} more)", not_ok - 1) } else { String::new() };
+            format!("{}: {}{}", worst.name, worst.message, others)
+        }
+        _ => format!("All {} OK", components.len()),
+    };
+
+    DiagnosticGroup {
+        name: name.to_string(),
+        level,
+        message,
+        components,
+    }
+}
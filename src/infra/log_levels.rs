@@ -0,0 +1,286 @@
+use crate::types::{LogEntry, LogLevel, LoggingCommand};
+use crate::infra::log_sinks::SinkConfig;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use tokio::sync::mpsc;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogConfig {
+    pub level: LogLevel,                     // Level for modules without one of their own
+    pub modules: BTreeMap<String, LogLevel>, // Per-module levels, by module name
+    pub sinks: Vec<SinkConfig>,              // Console, stderr and file outputs next to the MCAP recording
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            level: LogLevel::Debug,
+            modules: BTreeMap::new(),
+            sinks: Vec::new(),
+        }
+    }
+}
+
+impl LogConfig {
+    // Applies a `--log-level` spec: comma-separated `level` or `Module=level`
+    // entries, such as `info,SensorArray=warn`
+    pub fn apply_spec(&mut self, spec: &str) -> Result<(), String> {
+        for entry in spec.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+            match entry.split_once('=') {
+                Some((module, level)) => {
+                    self.modules.insert(module.trim().to_string(), level.parse()?);
+                }
+                None => self.level = entry.parse()?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for LogLevel {
+    type Err = String;
+
+    fn from_str(level: &str) -> Result<Self, Self::Err> {
+        match level.trim().to_ascii_lowercase().as_str() {
+            "debug" => Ok(LogLevel::Debug),
+            "info" => Ok(LogLevel::Info),
+            "warn" | "warning" => Ok(LogLevel::Warn),
+            "error" => Ok(LogLevel::Error),
+            _ => Err(format!("unknown log level {:?}, expected debug, info, warn or error", level)),
+        }
+    }
+}
+
+// Every module that logs, so each has a level to list and set from the start
+// rather than only once it has logged. A module missing here is added when its
+// first entry reaches the Logger.
+pub const MODULES: &[&str] = &[
+    "Behaviour",
+    "BusMonitor",
+    "CalibrationStorage",
+    "CommandArbiter",
+    "CommunicationModule",
+    "Diagnostics",
+    "DirectUserInput",
+    "EnergyManager",
+    "EnvUnderstanding",
+    "GoalPlanning",
+    "HardwareInterface",
+    "InputManager",
+    "Logger",
+    "Metrics",
+    "ObstacleAvoidance",
+    "OutputManager",
+    "PipelineMonitor",
+    "SafetyController",
+    "SensorArray",
+    "Stance",
+    "StateManager",
+    "TaskMissionManager",
+    "UserFeedback",
+    "UserInstructions",
+];
+
+struct Levels {
+    default: LogLevel,
+    modules: BTreeMap<String, LogLevel>,
+    seen: BTreeSet<String>, // Modules in MODULES and any others that have logged, so they can be listed
+}
+
+impl Levels {
+    fn lowest(&self) -> u8 {
+        self.modules.values().chain([&self.default]).map(|level| level.clone() as u8).min().unwrap_or(0)
+    }
+}
+
+// Minimum level of each module, shared by every log sender and changeable
+// at runtime. Cloning gives another handle to the same levels.
+#[derive(Clone)]
+pub struct LogLevels {
+    levels: Arc<RwLock<Levels>>,
+    // Lowest level any module logs at. Entries below it are dropped without
+    // looking the module up.
+    lowest: Arc<AtomicU8>,
+    configured: Arc<LogConfig>,
+}
+
+impl LogLevels {
+    pub fn new(config: &LogConfig) -> Self {
+        let levels = Levels {
+            default: config.level.clone(),
+            modules: config.modules.clone(),
+            seen: MODULES.iter().map(|module| module.to_string()).collect(),
+        };
+        Self {
+            lowest: Arc::new(AtomicU8::new(levels.lowest())),
+            levels: Arc::new(RwLock::new(levels)),
+            configured: Arc::new(config.clone()),
+        }
+    }
+
+    pub fn enabled(&self, module: &str, level: &LogLevel) -> bool {
+        if (level.clone() as u8) < self.lowest.load(Ordering::Relaxed) {
+            return false;
+        }
+        let levels = self.levels.read().unwrap();
+        *level >= *levels.modules.get(module).unwrap_or(&levels.default)
+    }
+
+    // Level of `module`, or the default level for None
+    pub fn level(&self, module: Option<&str>) -> LogLevel {
+        let levels = self.levels.read().unwrap();
+        module.and_then(|module| levels.modules.get(module)).unwrap_or(&levels.default).clone()
+    }
+
+    // Sets the level of `module`, or the default level for None
+    pub fn set(&self, module: Option<&str>, level: LogLevel) {
+        let mut levels = self.levels.write().unwrap();
+        match module {
+            Some(module) => {
+                levels.modules.insert(module.to_string(), level);
+            }
+            None => levels.default = level,
+        }
+        self.lowest.store(levels.lowest(), Ordering::Relaxed);
+    }
+
+    // Makes `module` follow the default level again
+    pub fn clear(&self, module: &str) {
+        let mut levels = self.levels.write().unwrap();
+        levels.modules.remove(module);
+        self.lowest.store(levels.lowest(), Ordering::Relaxed);
+    }
+
+    // Back to the levels from config and the command line
+    pub fn reset(&self) {
+        let mut levels = self.levels.write().unwrap();
+        levels.default = self.configured.level.clone();
+        levels.modules = self.configured.modules.clone();
+        self.lowest.store(levels.lowest(), Ordering::Relaxed);
+    }
+
+    pub fn apply(&self, command: LoggingCommand) -> String {
+        match command {
+            LoggingCommand::SetLevel { module: Some(module), level } => {
+                self.set(Some(&module), level.clone());
+                format!("{} logs at {:?} and above", module, level)
+            }
+            LoggingCommand::SetLevel { module: None, level } => {
+                self.set(None, level.clone());
+                format!("default level set to {:?}", level)
+            }
+            LoggingCommand::ClearLevel(module) => {
+                self.clear(&module);
+                format!("{} follows the default level", module)
+            }
+            LoggingCommand::Reset => {
+                self.reset();
+                "levels reset to the configured ones".to_string()
+            }
+        }
+    }
+
+    // Records that `module` logs, for listing a module missing from MODULES
+    // with `modules()`
+    pub fn register(&self, module: &str) {
+        self.levels.write().unwrap().seen.insert(module.to_string());
+    }
+
+    // Every known module, including those that have not logged yet, and any
+    // with a level of its own
+    pub fn modules(&self) -> Vec<String> {
+        let levels = self.levels.read().unwrap();
+        levels.seen.iter().chain(levels.modules.keys()).cloned().collect::<BTreeSet<_>>().into_iter().collect()
+    }
+}
+
+// State of the channel from the modules to the Logger, reported on
+// diagnostics/bus
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct LogLinkStats {
+    pub capacity: usize,
+    pub depth: usize,  // Entries waiting now
+    pub sent: u64,
+    pub dropped: u64,  // Entries that found the channel full
+}
+
+// Sends log entries to the logger, dropping those below their module's level
+// before they reach the channel. Sending never waits: an entry that finds the
+// channel full is dropped and counted, so a Logger that falls behind costs
+// log entries rather than holding up the modules.
+#[derive(Clone)]
+pub struct LogSender {
+    tx: mpsc::Sender<LogEntry>,
+    levels: LogLevels,
+    sent: Arc<AtomicU64>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl LogSender {
+    pub fn channel(buffer: usize, levels: LogLevels) -> (Self, mpsc::Receiver<LogEntry>) {
+        let (tx, rx) = mpsc::channel(buffer);
+        let sender = Self {
+            tx,
+            levels,
+            sent: Arc::new(AtomicU64::new(0)),
+            dropped: Arc::new(AtomicU64::new(0)),
+        };
+        (sender, rx)
+    }
+
+    // Whether `module` logs at `level`. Guards entries that are costly to
+    // build, such as those in hot loops.
+    pub fn enabled(&self, module: &str, level: LogLevel) -> bool {
+        self.levels.enabled(module, &level)
+    }
+
+    // Returns at once like try_send, and is async so it reads like the other
+    // channel sends in the modules. Fails only once the Logger has stopped.
+    pub async fn send(&self, entry: LogEntry) -> Result<(), mpsc::error::SendError<LogEntry>> {
+        self.offer(entry)
+    }
+
+    // For callers outside async code, such as callbacks and rlog!
+    pub fn try_send(&self, entry: LogEntry) {
+        let _ = self.offer(entry);
+    }
+
+    fn offer(&self, entry: LogEntry) -> Result<(), mpsc::error::SendError<LogEntry>> {
+        if !self.levels.enabled(&entry.module, &entry.level) {
+            return Ok(());
+        }
+        match self.tx.try_send(entry) {
+            Ok(()) => {
+                self.sent.fetch_add(1, Ordering::Relaxed);
+                Ok(())
+            }
+            Err(mpsc::error::TrySendError::Full(_)) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                Ok(())
+            }
+            Err(mpsc::error::TrySendError::Closed(entry)) => Err(mpsc::error::SendError(entry)),
+        }
+    }
+
+    pub fn levels(&self) -> &LogLevels {
+        &self.levels
+    }
+
+    // Entries waiting for the logger
+    pub fn depth(&self) -> usize {
+        self.tx.max_capacity() - self.tx.capacity()
+    }
+
+    pub fn stats(&self) -> LogLinkStats {
+        LogLinkStats {
+            capacity: self.tx.max_capacity(),
+            depth: self.depth(),
+            sent: self.sent.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use crate::types::{LogEntry, LogLevel};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, IsTerminal, Write};
+use std::path::Path;
+use chrono::{DateTime, Local, SecondsFormat};
+use crossterm::style::Stylize;
+
+// Where a sink writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SinkKind {
+    Console, // Coloured, one line per entry, to stdout
+    Stderr,  // Plain text, one line per entry, with the full timestamp
+    File,    // JSON lines, appended to `path`
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SinkConfig {
+    #[serde(rename = "type")]
+    pub kind: SinkKind,
+    pub level: LogLevel,      // Lowest level written
+    pub modules: Vec<String>, // Only entries from these modules, all if empty
+    pub exclude: Vec<String>, // Never entries from these modules
+    pub path: String,         // File the `file` sink appends to, created with its directory if missing
+}
+
+impl Default for SinkConfig {
+    fn default() -> Self {
+        Self {
+            kind: SinkKind::Console,
+            level: LogLevel::Info,
+            modules: Vec::new(),
+            exclude: Vec::new(),
+            path: "rover.jsonl".to_string(),
+        }
+    }
+}
+
+enum Output {
+    Console,
+    Stderr,
+    File(BufWriter<File>),
+}
+
+// Writes log entries that pass its level and module filter to the console,
+// stderr or a JSON lines file, next to the MCAP recording
+pub struct LogSink {
+    config: SinkConfig,
+    output: Output,
+}
+
+impl LogSink {
+    pub fn open(config: SinkConfig) -> Result<Self, String> {
+        let output = match config.kind {
+            SinkKind::Console => Output::Console,
+            SinkKind::Stderr => Output::Stderr,
+            SinkKind::File => {
+                let path = Path::new(&config.path);
+                if let Some(directory) = path.parent().filter(|directory| !directory.as_os_str().is_empty()) {
+                    fs::create_dir_all(directory)
+                        .map_err(|e| format!("Failed to create {}: {}", directory.display(), e))?;
+                }
+                let file = OpenOptions::new().create(true).append(true).open(path)
+                    .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+                Output::File(BufWriter::new(file))
+            }
+        };
+        Ok(Self { config, output })
+    }
+
+    pub fn describe(&self) -> String {
+        match self.config.kind {
+            SinkKind::Console => "console".to_string(),
+            SinkKind::Stderr => "stderr".to_string(),
+            SinkKind::File => self.config.path.clone(),
+        }
+    }
+
+    pub fn accepts(&self, entry: &LogEntry) -> bool {
+        entry.level >= self.config.level
+            && (self.config.modules.is_empty() || self.config.modules.contains(&entry.module))
+            && !self.config.exclude.contains(&entry.module)
+    }
+
+    // Writes `entry`, whose JSON form is `json`, if it passes the filter
+    pub fn write(&mut self, entry: &LogEntry, json: &str) -> std::io::Result<()> {
+        if !self.accepts(entry) {
+            return Ok(());
+        }
+        match &mut self.output {
+            Output::Console => {
+                let mut stdout = std::io::stdout().lock();
+                let line = format!("{}{}", console_line(entry), line_end(stdout.is_terminal()));
+                stdout.write_all(line.as_bytes())
+            }
+            Output::Stderr => {
+                let mut stderr = std::io::stderr().lock();
+                let line = format!("{}{}", text_line(entry), line_end(stderr.is_terminal()));
+                stderr.write_all(line.as_bytes())
+            }
+            Output::File(file) => {
+                file.write_all(json.as_bytes())?;
+                file.write_all(b"\n")
+            }
+        }
+    }
+
+    // Writes out buffered lines, called with the MCAP flush
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        match &mut self.output {
+            Output::File(file) => file.flush(),
+            Output::Console | Output::Stderr => Ok(()),
+        }
+    }
+}
+
+// The terminal is in raw mode while 'q' is listened for, where a bare newline
+// does not return to the start of the line
+fn line_end(terminal: bool) -> &'static str {
+    if terminal && crossterm::terminal::is_raw_mode_enabled().unwrap_or(false) {
+        "\r\n"
+    } else {
+        "\n"
+    }
+}
+
+// Message with the fields appended, as in the foxglove.Log channels
+fn message(entry: &LogEntry) -> String {
+    let mut message = entry.message.clone();
+    for (key, value) in &entry.fields {
+        message.push_str(&format!(" {}={}", key, value));
+    }
+    message
+}
+
+fn level_name(level: &LogLevel) -> String {
+    format!("{:<5}", format!("{:?}", level).to_uppercase())
+}
+
+fn location(entry: &LogEntry) -> String {
+    entry.file.as_ref().map(|file| format!(" ({}:{})", file, entry.line)).unwrap_or_default()
+}
+
+// 14:40:35.335 WARN  [SafetyController] Protective stop distance=0.42 sensor=0 (src/control/safety_controller.rs:521)
+fn console_line(entry: &LogEntry) -> String {
+    let time = DateTime::<Local>::from(entry.timestamp).format("%H:%M:%S%.3f").to_string();
+    let level = level_name(&entry.level);
+    let level = match entry.level {
+        LogLevel::Debug => level.blue(),
+        LogLevel::Info => level.green(),
+        LogLevel::Warn => level.yellow().bold(),
+        LogLevel::Error => level.red().bold(),
+    };
+    let mut line = format!("{} {} {} {}", time.dark_grey(), level, format!("[{}]", entry.module).cyan(), message(entry));
+    if entry.file.is_some() {
+        line.push_str(&location(entry).dark_grey().to_string());
+    }
+    line
+}
+
+// 2026-10-18T14:40:35.335812+00:00 WARN  [SafetyController] Protective stop distance=0.42 sensor=0 (src/control/safety_controller.rs:521)
+fn text_line(entry: &LogEntry) -> String {
+    format!(
+        "{} {} [{}] {}{}",
+        DateTime::<Local>::from(entry.timestamp).to_rfc3339_opts(SecondsFormat::Micros, false),
+        level_name(&entry.level),
+        entry.module,
+        message(entry),
+        location(entry)
+    )
+}
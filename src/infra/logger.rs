@@ -1,85 +1,92 @@
-use crate::types::{LogEntry, LogLevel};
+use crate::types::{LogEntry, LogLevel, LogValue, LoggingCommand};
 use crate::infra::foxglove as foxglove_schemas;
+use crate::infra::bus::TapMessage;
+use crate::infra::metrics::Metrics;
+use crate::infra::log_levels::LogSender;
+use crate::infra::recording::{Encoding, Recorder, RecordingConfig};
+use crate::infra::log_sinks::{LogSink, SinkConfig};
 use foxglove_schemas::{Log, LogArgs, LogLevel as FoxgloveLogLevel, Time, TimeArgs};
 use tokio::sync::{broadcast, mpsc};
 use std::time::{SystemTime, UNIX_EPOCH};
-use std::fs::File;
-use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io::Write;
+use std::path::Path;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::sync::Arc;
-use mcap::{Writer, records::MessageHeader};
 use flatbuffers::FlatBufferBuilder;
-use chrono::Local;
+use chrono::{DateTime, Local, SecondsFormat};
 use crossterm::style::Stylize;
 use foxglove::{Context, RawChannel, Schema, WebSocketServer, WebSocketServerHandle};
+use foxglove::websocket::{Capability, Client, Parameter, ParameterValue, ServerListener};
+
+// Topic the structured form of every log entry is recorded on, as JSON
+pub const LOG_TOPIC: &str = "log";
+// Foxglove parameter holding the default log level, and the prefix of the
+// per-module ones, such as log_level/SensorArray
+const LEVEL_PARAMETER: &str = "log_level";
 
 pub struct Logger {
     log_rx: mpsc::Receiver<LogEntry>,
+    log_tx: LogSender, // For the log levels, set from Foxglove parameters
+    tap_rx: broadcast::Receiver<TapMessage>,
     shutdown_rx: broadcast::Receiver<()>,
-    min_level: LogLevel,
-    mcap_writer: Option<Writer<File>>,
-    schema_id: u16,
-    module_channels: HashMap<String, u16>,
+    recorder: Recorder,
+    modules: HashSet<String>, // Modules that have logged
+    sinks: Vec<LogSink>,
     foxglove_context: Option<Arc<Context>>,
     ws_server_handle: Option<WebSocketServerHandle>,
     ws_channels: HashMap<String, Arc<RawChannel>>,
+    ws_topic_channels: HashMap<&'static str, Arc<RawChannel>>,
     message_count: u64,
+    metrics: Metrics,
 }
 
 impl Logger {
     pub fn new(
         log_rx: mpsc::Receiver<LogEntry>,
+        log_tx: LogSender,
+        tap_rx: broadcast::Receiver<TapMessage>,
         shutdown_rx: broadcast::Receiver<()>,
+        metrics: Metrics,
+        config: RecordingConfig,
+        sinks: Vec<SinkConfig>,
     ) -> Self {
-        // Create MCAP file with human-readable timestamp
-        let timestamp = Local::now().format("%y%m%d_%H%M%S");
-        let filename = format!("log_{}.mcap", timestamp);
-
-        let (mcap_writer, schema_id) = match Self::create_mcap_writer(&filename) {
-            Ok(writer_info) => {
-                println!("{} Created MCAP log file: {}", "[Logger]".dark_grey(), filename.magenta().bold());
-                println!("{} Press 'q' to quit gracefully for proper file indexing!", "[Logger]".dark_grey());
-                writer_info
-            }
-            Err(e) => {
-                eprintln!("{} Failed to create MCAP file: {}. Logging disabled.", "[Logger]".dark_grey(), e);
-                (None, 0)
-            }
-        };
+        let recorder = Recorder::new(config);
+        if recorder.path().is_some() {
+            println!("{} Press 'q' to quit gracefully for proper file indexing!", "[Logger]".dark_grey());
+        }
+
+        let sinks = sinks.into_iter()
+            .filter_map(|config| match LogSink::open(config) {
+                Ok(sink) => {
+                    println!("{} Writing log entries to {}", "[Logger]".dark_grey(), sink.describe().magenta().bold());
+                    Some(sink)
+                }
+                Err(e) => {
+                    eprintln!("{} {}. Log sink disabled.", "[Logger]".dark_grey(), e);
+                    None
+                }
+            })
+            .collect();
 
         Self {
             log_rx,
+            log_tx,
+            tap_rx,
             shutdown_rx,
-            min_level: LogLevel::Debug,
-            mcap_writer,
-            schema_id,
-            module_channels: HashMap::new(),
+            recorder,
+            modules: HashSet::new(),
+            sinks,
             foxglove_context: None,
             ws_server_handle: None,
             ws_channels: HashMap::new(),
+            ws_topic_channels: HashMap::new(),
             message_count: 0,
+            metrics,
         }
     }
 
-    fn create_mcap_writer(filename: &str) -> Result<(Option<Writer<File>>, u16), Box<dyn std::error::Error>> {
-        let file = File::create(filename)?;
-
-        // Use default options which enable chunking and indexing
-        let mut writer = Writer::new(file)?;
-
-        // Read the Foxglove Log FlatBuffer binary schema
-        let schema_data = include_bytes!("../../schemas/Log.bfbs");
-
-        // Add schema to MCAP file
-        let schema_id = writer.add_schema(
-            "foxglove.Log",
-            "flatbuffer",
-            schema_data,
-        )?;
-
-        Ok((Some(writer), schema_id))
-    }
-
-    async fn create_websocket_server() -> Result<(Arc<Context>, WebSocketServerHandle, String), Box<dyn std::error::Error>> {
+    async fn create_websocket_server(log_tx: LogSender) -> Result<(Arc<Context>, WebSocketServerHandle, String), Box<dyn std::error::Error>> {
         let context = Context::new();
         let host = "127.0.0.1";
         let port = 8765;
@@ -88,6 +95,8 @@ impl Logger {
             .name("RoverOS Logger")
             .bind(host, port)
             .context(&context)
+            .capabilities([Capability::Parameters])
+            .listener(Arc::new(LevelParameters { log_tx }))
             .start()
             .await?;
 
@@ -100,7 +109,7 @@ impl Logger {
         println!("{} Starting logger module", "[Logger]".dark_grey());
 
         // Initialize WebSocket server asynchronously
-        match Self::create_websocket_server().await {
+        match Self::create_websocket_server(self.log_tx.clone()).await {
             Ok((context, server_handle, addr)) => {
                 println!(
                     "{} {} {}",
@@ -116,29 +125,42 @@ impl Logger {
             }
         }
 
+        // Flushes the MCAP file and starts the next one when it is due
+        let mut maintain = tokio::time::interval(self.recorder.maintain_interval());
+        maintain.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
         loop {
             tokio::select! {
                 _ = self.shutdown_rx.recv() => {
                     println!("{} Shutdown signal received", "[Logger]".dark_grey());
                     break;
                 }
+                // Entries below their module's level never reach the channel
                 Some(entry) = self.log_rx.recv() => {
-                    if entry.level >= self.min_level {
-                        self.log_entry(&entry);
+                    self.metrics.log_message(&entry.module, entry.level.clone());
+                    self.log_entry(&entry);
+                    let json = entry.to_json();
+                    self.write_sinks(&entry, &json);
+                    self.record_json(LOG_TOPIC, entry.timestamp, &json);
+                }
+                result = self.tap_rx.recv() => match result {
+                    Ok(message) => self.record_bus_message(&message),
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        eprintln!("{} Bus tap too slow, skipped {} messages", "[Logger]".dark_grey(), skipped);
                     }
+                    Err(broadcast::error::RecvError::Closed) => {}
+                },
+                _ = maintain.tick() => {
+                    self.recorder.maintain();
+                    self.flush_sinks();
                 }
             }
         }
 
         // Finalize MCAP file - write summary section and footer
-        if let Some(mut writer) = self.mcap_writer.take() {
-            println!("{} Finalizing MCAP file with {} messages...", "[Logger]".dark_grey(), self.message_count);
-            if let Err(e) = writer.finish() {
-                eprintln!("{} Error finishing MCAP file: {}", "[Logger]".dark_grey(), e);
-            } else {
-                println!("{} MCAP file finalized successfully (indexed)", "[Logger]".dark_grey());
-            }
-        }
+        println!("{} Finalizing MCAP file after {} messages...", "[Logger]".dark_grey(), self.message_count);
+        self.recorder.finish();
+        self.flush_sinks();
 
         println!("{} Stopped", "[Logger]".dark_grey());
     }
@@ -167,9 +189,14 @@ impl Logger {
             LogLevel::Error => FoxgloveLogLevel::ERROR,
         };
 
-        // Create strings
-        let message_str = builder.create_string(&entry.message);
+        // Create strings, with the fields appended so they show in the log panel
+        let mut message = entry.message.clone();
+        for (key, value) in &entry.fields {
+            message.push_str(&format!(" {}={}", key, value));
+        }
+        let message_str = builder.create_string(&message);
         let name_str = builder.create_string(&entry.module);
+        let file_str = entry.file.as_deref().map(|file| builder.create_string(file));
 
         // Create Log message
         let log = Log::create(&mut builder, &LogArgs {
@@ -177,8 +204,8 @@ impl Logger {
             level: fb_level,
             message: Some(message_str),
             name: Some(name_str),
-            file: None,
-            line: 0,
+            file: file_str,
+            line: entry.line,
         });
 
         builder.finish(log, None);
@@ -187,6 +214,13 @@ impl Logger {
         // Copy the data so we can use it for both MCAP and WebSocket
         let message_data_copy = message_data.to_vec();
 
+        let topic = format!("roverOS/{}", entry.module);
+        if !self.modules.contains(&entry.module) {
+            // A module that has not logged before
+            self.log_tx.levels().register(&entry.module);
+            self.modules.insert(entry.module.clone());
+        }
+
         // Publish to WebSocket FIRST (before MCAP to ensure real-time delivery)
         if let Some(context) = &self.foxglove_context {
             // Get or create WebSocket channel for this module
@@ -195,10 +229,9 @@ impl Logger {
                 let schema_data = include_bytes!("../../schemas/Log.bfbs");
                 
                 // Create a new WebSocket channel
-                let topic = format!("roverOS/{}", entry.module);
                 let schema = Schema::new("foxglove.Log", "flatbuffer", schema_data);
                 match context
-                    .channel_builder(topic)
+                    .channel_builder(topic.clone())
                     .schema(schema)
                     .message_encoding("flatbuffer")
                     .build_raw()
@@ -220,55 +253,129 @@ impl Logger {
         }
 
         // Write to MCAP file
-        if let Some(writer) = &mut self.mcap_writer {
-            // Get or create MCAP channel for this module
-            let channel_id = match self.module_channels.get(&entry.module) {
-                Some(&channel_id) => channel_id,
-                None => {
-                    // Need to create a new channel
-                    let topic = format!("roverOS/{}", entry.module);
-                    match writer.add_channel(
-                        self.schema_id,
-                        &topic,
-                        "flatbuffer",
-                        &BTreeMap::new(),
-                    ) {
-                        Ok(channel_id) => {
-                            self.module_channels.insert(entry.module.clone(), channel_id);
-                            channel_id
-                        }
-                        Err(e) => {
-                            eprintln!("{} Failed to create MCAP channel for module {}: {}", "[Logger]".dark_grey(), entry.module, e);
-                            return;
-                        }
-                    }
-                }
-            };
+        self.recorder.write(&topic, Encoding::Log, timestamp_nanos, &message_data_copy);
 
-            let header = MessageHeader {
-                channel_id,
-                sequence: 0,
-                log_time: timestamp_nanos,
-                publish_time: timestamp_nanos,
-            };
+        self.message_count += 1;
+        self.metrics.set_logger_messages(self.message_count);
+    }
 
-            if let Err(e) = writer.write_to_known_channel(&header, &message_data_copy) {
-                eprintln!("{} Error writing to MCAP: {}", "[Logger]".dark_grey(), e);
+    // Writes an entry to every sink whose filter it passes. A sink that fails
+    // to write is closed, so a full disk does not fill the terminal with errors.
+    fn write_sinks(&mut self, entry: &LogEntry, json: &str) {
+        self.sinks.retain_mut(|sink| match sink.write(entry, json) {
+            Ok(()) => true,
+            Err(e) => {
+                eprintln!("{} Failed to write to {}: {}. Log sink disabled.", "[Logger]".dark_grey(), sink.describe(), e);
+                false
+            }
+        });
+    }
+
+    fn flush_sinks(&mut self) {
+        self.sinks.retain_mut(|sink| match sink.flush() {
+            Ok(()) => true,
+            Err(e) => {
+                eprintln!("{} Failed to write to {}: {}. Log sink disabled.", "[Logger]".dark_grey(), sink.describe(), e);
+                false
+            }
+        });
+    }
+
+    // Records a bus message as schemaless JSON on its own topic, for example
+    // roverOS/sensor/raw
+    fn record_bus_message(&mut self, message: &TapMessage) {
+        self.record_json(message.topic, message.timestamp, &message.json);
+    }
+
+    fn record_json(&mut self, topic_name: &'static str, timestamp: SystemTime, json: &str) {
+        let timestamp_nanos = timestamp
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos() as u64;
+        let topic = format!("roverOS/{}", topic_name);
+
+        if let Some(context) = &self.foxglove_context {
+            if !self.ws_topic_channels.contains_key(topic_name) {
+                match context.channel_builder(topic.clone()).message_encoding("json").build_raw() {
+                    Ok(channel) => {
+                        self.ws_topic_channels.insert(topic_name, channel);
+                    }
+                    Err(e) => {
+                        eprintln!("{} Failed to create WebSocket channel for topic {}: {}", "[Logger]".dark_grey(), topic_name, e);
+                        return;
+                    }
+                }
+            }
+            if let Some(ws_channel) = self.ws_topic_channels.get(topic_name) {
+                ws_channel.log(json.as_bytes());
             }
         }
 
+        self.recorder.write(&topic, Encoding::Json, timestamp_nanos, json.as_bytes());
+
         self.message_count += 1;
+        self.metrics.set_logger_messages(self.message_count);
+    }
+}
+
+// Shows the log levels as Foxglove parameters: log_level for the default and
+// log_level/<Module> for each module. Setting a module's parameter to an
+// empty string makes it follow the default again.
+struct LevelParameters {
+    log_tx: LogSender,
+}
+
+impl LevelParameters {
+    fn parameter(&self, name: &str) -> Option<Parameter> {
+        let levels = self.log_tx.levels();
+        let level = match name.strip_prefix(LEVEL_PARAMETER)? {
+            "" => levels.level(None),
+            module => levels.level(Some(module.strip_prefix('/')?)),
+        };
+        Some(Parameter::string(name, format!("{:?}", level).to_lowercase()))
+    }
+
+    fn command(name: &str, value: Option<&ParameterValue>) -> Option<LoggingCommand> {
+        let module = match name.strip_prefix(LEVEL_PARAMETER)? {
+            "" => None,
+            module => Some(module.strip_prefix('/')?.to_string()),
+        };
+        match (module, value) {
+            (Some(module), None) => Some(LoggingCommand::ClearLevel(module)),
+            (Some(module), Some(ParameterValue::String(level))) if level.is_empty() => Some(LoggingCommand::ClearLevel(module)),
+            (module, Some(ParameterValue::String(level))) => Some(LoggingCommand::SetLevel { module, level: level.parse().ok()? }),
+            _ => None,
+        }
     }
 }
 
-impl Drop for Logger {
-    fn drop(&mut self) {
-        // Ensure MCAP file is properly finalized when logger is dropped
-        if let Some(mut writer) = self.mcap_writer.take() {
-            if let Err(e) = writer.finish() {
-                eprintln!("{} Error finishing MCAP file in Drop: {}", "[Logger]".dark_grey(), e);
+impl ServerListener for LevelParameters {
+    fn on_get_parameters(&self, _client: Client, param_names: Vec<String>, _request_id: Option<&str>) -> Vec<Parameter> {
+        let names = if param_names.is_empty() {
+            let modules = self.log_tx.levels().modules().into_iter()
+                .map(|module| format!("{}/{}", LEVEL_PARAMETER, module));
+            std::iter::once(LEVEL_PARAMETER.to_string()).chain(modules).collect()
+        } else {
+            param_names
+        };
+        names.iter().filter_map(|name| self.parameter(name)).collect()
+    }
+
+    fn on_set_parameters(&self, _client: Client, parameters: Vec<Parameter>, _request_id: Option<&str>) -> Vec<Parameter> {
+        for parameter in &parameters {
+            match Self::command(&parameter.name, parameter.value.as_ref()) {
+                Some(command) => {
+                    let change = self.log_tx.levels().apply(command);
+                    self.log_tx.try_send(create_log("Logger", LogLevel::Info, format!("Foxglove: {}", change)));
+                }
+                None => self.log_tx.try_send(create_log(
+                    "Logger",
+                    LogLevel::Warn,
+                    format!("Ignoring parameter {} = {:?}", parameter.name, parameter.value)
+                )),
             }
         }
+        parameters.iter().filter_map(|parameter| self.parameter(&parameter.name)).collect()
     }
 }
 
@@ -279,5 +386,174 @@ pub fn create_log(module: &str, level: LogLevel, message: String) -> LogEntry {
         level,
         module: module.to_string(),
         message,
+        file: None,
+        line: 0,
+        fields: BTreeMap::new(),
+    }
+}
+
+// Sends a log entry like create_log, with its source location and structured
+// fields. The message and fields are only evaluated when the module logs at
+// that level, so a filtered-out entry in a hot loop costs one level check.
+//
+// The short form takes a bare level and a format string. The module name comes
+// from the calling module's LOG_MODULE const, and trailing `key = value` pairs
+// are fields rather than format arguments:
+//
+//     const LOG_MODULE: &str = "SafetyController";
+//     rlog!(self.log_tx, Warn, "Command blocked", distance = 0.42, sensor = 0);
+//     rlog!(self.log_tx, Debug, "Published sensor reading #{}", counter, reading = counter);
+//
+// The long form takes the module and level as expressions, for a level that is
+// only known at runtime:
+//
+//     rlog!(self.log_tx, LOG_MODULE, level, format!("Command blocked - {}", reason));
+#[macro_export]
+macro_rules! rlog {
+    // Splits the arguments after the format string into format arguments and
+    // fields, one at a time
+    (@split [$($head:tt)*] [$($arg:expr),*] [$($field:tt)*] $(,)?) => {
+        $crate::rlog!(@emit [$($head)*] [$($arg),*] [$($field)*])
+    };
+    (@split [$($head:tt)*] [$($arg:expr),*] [$($field:tt)*] , $key:ident = $value:expr $(, $($rest:tt)*)?) => {
+        $crate::rlog!(@split [$($head)*] [$($arg),*] [$($field)* $key = $value,] $(, $($rest)*)?)
+    };
+    (@split [$($head:tt)*] [$($arg:expr),*] [$($field:tt)*] , $next:expr $(, $($rest:tt)*)?) => {
+        $crate::rlog!(@split [$($head)*] [$($arg,)* $next] [$($field)*] $(, $($rest)*)?)
+    };
+    (@emit [$log_tx:expr, $level:ident, $format:literal] [$($arg:expr),*] [$($key:ident = $value:expr,)*]) => {
+        $crate::rlog!(
+            $log_tx,
+            LOG_MODULE,
+            $crate::types::LogLevel::$level,
+            format!($format $(, $arg)*)
+            $(, $key = $value)*
+        )
+    };
+    ($log_tx:expr, $level:ident, $format:literal $($rest:tt)*) => {
+        $crate::rlog!(@split [$log_tx, $level, $format] [] [] $($rest)*)
+    };
+    ($log_tx:expr, $module:expr, $level:expr, $message:expr $(, $key:ident = $value:expr)* $(,)?) => {{
+        let log_tx: &$crate::infra::log_levels::LogSender = &$log_tx;
+        let module: &str = $module;
+        let level: $crate::types::LogLevel = $level;
+        if log_tx.enabled(module, level.clone()) {
+            #[allow(unused_mut)]
+            let mut entry = $crate::infra::logger::create_log(
+                module,
+                level,
+                ::std::string::ToString::to_string(&$message),
+            );
+            entry.file = Some(file!().to_string());
+            entry.line = line!();
+            $(
+                entry.fields.insert(stringify!($key).to_string(), $crate::types::LogValue::from($value));
+            )*
+            log_tx.try_send(entry);
+        }
+    }};
+}
+
+impl LogEntry {
+    // One line of JSON, as recorded on roverOS/log and exported as JSON lines
+    pub fn to_json(&self) -> String {
+        let mut json = serde_json::json!({
+            "timestamp": DateTime::<Local>::from(self.timestamp).to_rfc3339_opts(SecondsFormat::Micros, false),
+            "level": self.level,
+            "module": self.module,
+            "message": self.message,
+            "fields": self.fields,
+        });
+        if let Some(file) = &self.file {
+            json["file"] = file.as_str().into();
+            json["line"] = self.line.into();
+        }
+        json.to_string()
+    }
+}
+
+impl fmt::Display for LogValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogValue::Bool(value) => write!(f, "{}", value),
+            LogValue::Int(value) => write!(f, "{}", value),
+            LogValue::Float(value) => write!(f, "{}", value),
+            LogValue::Text(value) if value.contains(char::is_whitespace) => write!(f, "{:?}", value),
+            LogValue::Text(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+impl From<bool> for LogValue {
+    fn from(value: bool) -> Self {
+        LogValue::Bool(value)
+    }
+}
+
+macro_rules! log_value_from_int {
+    ($($int:ty),*) => {$(
+        impl From<$int> for LogValue {
+            fn from(value: $int) -> Self {
+                i64::try_from(value).map_or(LogValue::Float(value as f64), LogValue::Int)
+            }
+        }
+    )*};
+}
+
+log_value_from_int!(i8, i16, i32, i64, u8, u16, u32, u64, usize);
+
+impl From<f32> for LogValue {
+    fn from(value: f32) -> Self {
+        // Through the shortest decimal form, so 0.42 stays 0.42 rather than
+        // becoming 0.41999998688697815
+        LogValue::Float(value.to_string().parse().unwrap_or(value as f64))
+    }
+}
+
+impl From<f64> for LogValue {
+    fn from(value: f64) -> Self {
+        LogValue::Float(value)
+    }
+}
+
+impl From<&str> for LogValue {
+    fn from(value: &str) -> Self {
+        LogValue::Text(value.to_string())
+    }
+}
+
+impl From<String> for LogValue {
+    fn from(value: String) -> Self {
+        LogValue::Text(value)
+    }
+}
+
+// Writes the log entries recorded in an MCAP file to `out` as JSON lines,
+// returning how many were written. Files that were not finalized are read up
+// to where they end.
+pub fn export_json_lines(path: &Path, out: &mut impl Write) -> Result<usize, String> {
+    let data = std::fs::read(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let messages = mcap::MessageStream::new_with_options(&data, mcap::read::Options::IgnoreEndMagic.into())
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+    let topic = format!("roverOS/{}", LOG_TOPIC);
+    let mut exported = 0;
+    for message in messages {
+        let message = match message {
+            Ok(message) => message,
+            Err(e) => {
+                eprintln!("{} {} ends early: {}", "[Logger]".dark_grey(), path.display(), e);
+                break;
+            }
+        };
+        if message.channel.topic != topic {
+            continue;
+        }
+        out.write_all(&message.data)
+            .and_then(|_| out.write_all(b"\n"))
+            .map_err(|e| format!("Failed to write: {}", e))?;
+        exported += 1;
     }
+    Ok(exported)
 }
@@ -0,0 +1,411 @@
+use crate::types::{HardwareStatus, LogLevel};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "metrics")]
+use crate::types::MissionProgress;
+#[cfg(feature = "metrics")]
+use crate::infra::bus::Bus;
+#[cfg(feature = "metrics")]
+use crate::infra::log_levels::LogLinkStats;
+#[cfg(feature = "metrics")]
+use std::collections::BTreeMap;
+#[cfg(feature = "metrics")]
+use std::fmt::Write;
+#[cfg(feature = "metrics")]
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetricsConfig {
+    pub address: String, // Address the OpenMetrics endpoint listens on, with the `metrics` feature
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            // Clear of node_exporter's 9100 and the operator link's ports
+            address: "127.0.0.1:9872".to_string(),
+        }
+    }
+}
+
+// Why the safety controller blocked a command, as a metric label
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BlockReason {
+    EmergencyStop,
+    SensorStale,
+    Geofence,
+    TiltLimit,
+    CriticalBattery,
+    ProtectiveStop,
+}
+
+impl BlockReason {
+    pub fn label(self) -> &'static str {
+        match self {
+            BlockReason::EmergencyStop => "emergency_stop",
+            BlockReason::SensorStale => "sensor_stale",
+            BlockReason::Geofence => "geofence",
+            BlockReason::TiltLimit => "tilt_limit",
+            BlockReason::CriticalBattery => "critical_battery",
+            BlockReason::ProtectiveStop => "protective_stop",
+        }
+    }
+}
+
+#[cfg(feature = "metrics")]
+#[derive(Default)]
+struct Registry {
+    log_messages: BTreeMap<(String, LogLevel), u64>,
+    logger_messages: u64,
+    hardware: Option<HardwareStatus>,
+    safety_blocks: BTreeMap<BlockReason, u64>,
+}
+
+// Counters and gauges updated by the modules that own them. Cloning gives
+// another handle to the same registry.
+#[cfg(feature = "metrics")]
+#[derive(Clone, Default)]
+pub struct Metrics {
+    registry: Arc<Mutex<Registry>>,
+}
+
+// Without the `metrics` feature nothing serves the registry, so there is
+// none and updating it costs nothing
+#[cfg(not(feature = "metrics"))]
+#[derive(Clone, Default)]
+pub struct Metrics;
+
+#[cfg(not(feature = "metrics"))]
+impl Metrics {
+    pub fn new() -> Self {
+        Self
+    }
+
+    #[inline(always)]
+    pub fn log_message(&self, _module: &str, _level: LogLevel) {}
+
+    #[inline(always)]
+    pub fn set_logger_messages(&self, _count: u64) {}
+
+    #[inline(always)]
+    pub fn set_hardware(&self, _status: &HardwareStatus) {}
+
+    #[inline(always)]
+    pub fn safety_block(&self, _reason: BlockReason) {}
+}
+
+#[cfg(feature = "metrics")]
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // A log entry sent by `module`
+    pub fn log_message(&self, module: &str, level: LogLevel) {
+        let mut registry = self.registry.lock().unwrap();
+        *registry.log_messages.entry((module.to_string(), level)).or_default() += 1;
+    }
+
+    // Messages the logger has written so far
+    pub fn set_logger_messages(&self, count: u64) {
+        self.registry.lock().unwrap().logger_messages = count;
+    }
+
+    pub fn set_hardware(&self, status: &HardwareStatus) {
+        self.registry.lock().unwrap().hardware = Some(status.clone());
+    }
+
+    pub fn safety_block(&self, reason: BlockReason) {
+        *self.registry.lock().unwrap().safety_blocks.entry(reason).or_default() += 1;
+    }
+
+    // The registry in OpenMetrics text format, with bus queue depths, the log
+    // channel and mission progress read as of now
+    pub fn render(&self, bus: &Bus, log: LogLinkStats, mission: Option<&MissionProgress>) -> String {
+        let registry = self.registry.lock().unwrap();
+        let mut out = String::new();
+
+        family(&mut out, "rover_log_messages", "counter", "Log entries sent by each module");
+        for ((module, level), count) in &registry.log_messages {
+            let level = format!("{:?}", level).to_lowercase();
+            sample(&mut out, "rover_log_messages_total", &[("module", module), ("level", &level)], *count);
+        }
+
+        family(&mut out, "rover_logger_messages", "counter", "Log entries and bus messages written by the logger");
+        sample(&mut out, "rover_logger_messages_total", &[], registry.logger_messages);
+
+        if let Some(hardware) = &registry.hardware {
+            family(&mut out, "rover_battery_voltage_volts", "gauge", "Battery voltage from the hardware interface");
+            sample(&mut out, "rover_battery_voltage_volts", &[], hardware.battery_voltage);
+
+            family(&mut out, "rover_motor_temperature_celsius", "gauge", "Motor temperatures from the hardware interface");
+            for (motor, temp) in hardware.motor_temps.iter().enumerate() {
+                sample(&mut out, "rover_motor_temperature_celsius", &[("motor", &motor.to_string())], temp);
+            }
+        }
+
+        family(&mut out, "rover_safety_blocks", "counter", "Commands blocked by the safety controller, by reason");
+        for (reason, count) in &registry.safety_blocks {
+            sample(&mut out, "rover_safety_blocks_total", &[("reason", reason.label())], *count);
+        }
+
+        family(&mut out, "rover_mission_active", "gauge", "1 while a mission is running");
+        sample(&mut out, "rover_mission_active", &[], u8::from(mission.is_some()));
+        if let Some(mission) = mission {
+            let labels = [("mission", mission.mission_name.as_str())];
+            family(&mut out, "rover_mission_task_index", "gauge", "0-based task the running mission is at");
+            sample(&mut out, "rover_mission_task_index", &labels, mission.task_index);
+            family(&mut out, "rover_mission_task_count", "gauge", "Tasks in the running mission");
+            sample(&mut out, "rover_mission_task_count", &labels, mission.task_count);
+            family(&mut out, "rover_mission_loop", "gauge", "1-based pass through the running mission's tasks");
+            sample(&mut out, "rover_mission_loop", &labels, mission.loop_number);
+            if let Some(distance) = mission.distance_to_target {
+                family(&mut out, "rover_mission_distance_to_target_meters", "gauge", "Distance to the current navigation target");
+                sample(&mut out, "rover_mission_distance_to_target_meters", &labels, distance);
+            }
+        }
+
+        let topics = bus.stats();
+        family(&mut out, "rover_bus_published", "counter", "Messages published on each bus topic");
+        for topic in &topics {
+            sample(&mut out, "rover_bus_published_total", &[("topic", topic.topic)], topic.published);
+        }
+        family(&mut out, "rover_bus_dropped", "counter", "Messages each subscriber lost to its queue policy");
+        for topic in &topics {
+            for link in &topic.links {
+                sample(&mut out, "rover_bus_dropped_total", &[("topic", topic.topic), ("subscriber", link.subscriber)], link.dropped);
+            }
+        }
+        sample(&mut out, "rover_bus_dropped_total", &[("topic", "log"), ("subscriber", "Logger")], log.dropped);
+        family(&mut out, "rover_channel_depth", "gauge", "Messages waiting in each bus link and the log channel");
+        for topic in &topics {
+            for link in &topic.links {
+                sample(&mut out, "rover_channel_depth", &[("channel", topic.topic), ("subscriber", link.subscriber)], link.depth);
+            }
+        }
+        sample(&mut out, "rover_channel_depth", &[("channel", "log"), ("subscriber", "Logger")], log.depth);
+
+        out.push_str("# EOF\n");
+        out
+    }
+}
+
+#[cfg(feature = "metrics")]
+fn family(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+}
+
+#[cfg(feature = "metrics")]
+fn sample(out: &mut String, name: &str, labels: &[(&str, &str)], value: impl std::fmt::Display) {
+    out.push_str(name);
+    if !labels.is_empty() {
+        let labels: Vec<String> = labels.iter()
+            .map(|(key, value)| format!("{}=\"{}\"", key, escape(value)))
+            .collect();
+        let _ = write!(out, "{{{}}}", labels.join(","));
+    }
+    let _ = writeln!(out, " {}", value);
+}
+
+#[cfg(feature = "metrics")]
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[cfg(feature = "metrics")]
+pub use server::MetricsServer;
+
+#[cfg(feature = "metrics")]
+mod server {
+    use super::{Metrics, MetricsConfig};
+    use crate::types::{LogLevel, MissionProgress};
+    use crate::infra::logger::create_log;
+    use crate::infra::log_levels::LogSender;
+    use crate::infra::bus::Bus;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::sync::{broadcast, watch};
+
+    // Longest request head read before the connection is dropped
+    const MAX_REQUEST: usize = 8192;
+    const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+    // Serves the metrics in OpenMetrics text format on GET /metrics, one
+    // request per connection
+    pub struct MetricsServer {
+        metrics: Metrics,
+        bus: Bus,
+        mission_rx: watch::Receiver<Option<MissionProgress>>,
+        log_tx: LogSender,
+        shutdown_rx: broadcast::Receiver<()>,
+        config: MetricsConfig,
+    }
+
+    impl MetricsServer {
+        pub fn new(
+            metrics: Metrics,
+            bus: Bus,
+            mission_rx: watch::Receiver<Option<MissionProgress>>,
+            log_tx: LogSender,
+            shutdown_rx: broadcast::Receiver<()>,
+            config: MetricsConfig,
+        ) -> Self {
+            Self {
+                metrics,
+                bus,
+                mission_rx,
+                log_tx,
+                shutdown_rx,
+                config,
+            }
+        }
+
+        pub async fn run(self) {
+            let listener = match TcpListener::bind(&self.config.address).await {
+                Ok(listener) => {
+                    let _ = self.log_tx.send(create_log(
+                        "Metrics",
+                        LogLevel::Info,
+                        format!("Serving metrics on http://{}/metrics", self.config.address)
+                    )).await;
+                    listener
+                }
+                Err(e) => {
+                    let _ = self.log_tx.send(create_log(
+                        "Metrics",
+                        LogLevel::Error,
+                        format!("Cannot listen on {}: {}", self.config.address, e)
+                    )).await;
+                    return;
+                }
+            };
+            self.serve(listener).await;
+        }
+
+        async fn serve(mut self, listener: TcpListener) {
+            loop {
+                tokio::select! {
+                    _ = self.shutdown_rx.recv() => {
+                        let _ = self.log_tx.send(create_log(
+                            "Metrics",
+                            LogLevel::Info,
+                            "Shutdown signal received".to_string()
+                        )).await;
+                        break;
+                    }
+                    accepted = listener.accept() => match accepted {
+                        Ok((stream, _)) => {
+                            let body = self.render();
+                            tokio::spawn(respond(stream, body));
+                        }
+                        Err(e) => {
+                            let _ = self.log_tx.send(create_log(
+                                "Metrics",
+                                LogLevel::Warn,
+                                format!("Failed to accept connection: {}", e)
+                            )).await;
+                        }
+                    },
+                }
+            }
+
+            let _ = self.log_tx.send(create_log(
+                "Metrics",
+                LogLevel::Info,
+                "Stopped".to_string()
+            )).await;
+        }
+
+        fn render(&self) -> String {
+            let mission = self.mission_rx.borrow();
+            self.metrics.render(&self.bus, self.log_tx.stats(), mission.as_ref())
+        }
+    }
+
+    // Answers a single request. Only the request line matters, so the head is
+    // read until the blank line and any body is ignored.
+    async fn respond(mut stream: TcpStream, body: String) {
+        let mut request = Vec::new();
+        let mut buffer = [0u8; 1024];
+        while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+            match stream.read(&mut buffer).await {
+                Ok(0) | Err(_) => return,
+                Ok(n) => request.extend_from_slice(&buffer[..n]),
+            }
+            if request.len() > MAX_REQUEST {
+                return;
+            }
+        }
+
+        let request = String::from_utf8_lossy(&request);
+        let mut request_line = request.lines().next().unwrap_or_default().split_whitespace();
+        let response = match (request_line.next(), request_line.next()) {
+            (Some("GET"), Some("/metrics")) => format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                CONTENT_TYPE, body.len(), body
+            ),
+            (Some("GET"), Some(_)) => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string(),
+            _ => "HTTP/1.1 405 Method Not Allowed\r\nAllow: GET\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string(),
+        };
+
+        let _ = stream.write_all(response.as_bytes()).await;
+        let _ = stream.shutdown().await;
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::infra::log_levels::{LogConfig, LogLevels};
+        use crate::infra::metrics::BlockReason;
+
+        // Serves `metrics` on a loopback port and sends it `request`,
+        // returning the whole response
+        async fn request(metrics: Metrics, request: &str) -> String {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let address = listener.local_addr().unwrap();
+            let (_mission_tx, mission_rx) = watch::channel(None);
+            let (log_tx, _log_rx) = LogSender::channel(256, LogLevels::new(&LogConfig::default()));
+            let (shutdown_tx, shutdown_rx) = broadcast::channel(1);
+            let config = MetricsConfig { address: address.to_string() };
+            let server = MetricsServer::new(metrics, Bus::new(), mission_rx, log_tx, shutdown_rx, config);
+            let handle = tokio::spawn(server.serve(listener));
+
+            let mut stream = TcpStream::connect(address).await.unwrap();
+            stream.write_all(request.as_bytes()).await.unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+
+            let _ = shutdown_tx.send(());
+            handle.await.unwrap();
+            response
+        }
+
+        #[tokio::test]
+        async fn serves_openmetrics() {
+            let metrics = Metrics::new();
+            metrics.safety_block(BlockReason::ProtectiveStop);
+            metrics.safety_block(BlockReason::ProtectiveStop);
+            metrics.log_message("SensorArray", LogLevel::Warn);
+
+            let response = request(metrics, "GET /metrics HTTP/1.1\r\nHost: rover\r\n\r\n").await;
+            let (head, body) = response.split_once("\r\n\r\n").unwrap();
+            assert!(head.starts_with("HTTP/1.1 200 OK\r\n"), "{}", head);
+            assert!(head.contains(&format!("Content-Type: {}\r\n", CONTENT_TYPE)), "{}", head);
+            assert!(head.contains(&format!("Content-Length: {}\r\n", body.len())), "{}", head);
+            assert!(body.lines().any(|line| line == "rover_safety_blocks_total{reason=\"protective_stop\"} 2"), "{}", body);
+            assert!(body.lines().any(|line| line == "rover_log_messages_total{module=\"SensorArray\",level=\"warn\"} 1"), "{}", body);
+            assert!(body.ends_with("# EOF\n"), "{}", body);
+        }
+
+        #[tokio::test]
+        async fn rejects_other_paths_and_methods() {
+            let response = request(Metrics::new(), "GET /other HTTP/1.1\r\n\r\n").await;
+            assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"), "{}", response);
+
+            let response = request(Metrics::new(), "POST /metrics HTTP/1.1\r\nContent-Length: 0\r\n\r\n").await;
+            assert!(response.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"), "{}", response);
+            assert!(response.contains("Allow: GET\r\n"), "{}", response);
+        }
+    }
+}
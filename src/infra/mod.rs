@@ -1,2 +1,11 @@
 pub mod logger;
+pub mod log_levels;
+pub mod log_sinks;
 pub mod foxglove;
+pub mod config;
+pub mod bus;
+pub mod bus_monitor;
+pub mod pipeline;
+pub mod diagnostics;
+pub mod metrics;
+pub mod recording;
@@ -0,0 +1,288 @@
+use crate::types::{PipelineStage, Trace, LogLevel};
+use crate::infra::logger::create_log;
+use crate::infra::log_levels::LogSender;
+use crate::infra::bus::{Bus, Publisher, topics};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime};
+use tokio::sync::broadcast;
+use tokio::time::{Duration, interval};
+
+// Upper bounds of the latency histogram buckets, in milliseconds. A last
+// bucket counts everything slower.
+pub const BUCKETS_MS: [f64; 10] = [1.0, 2.0, 5.0, 10.0, 20.0, 50.0, 100.0, 200.0, 500.0, 1000.0];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PipelineConfig {
+    pub report_interval_ms: u64, // Time between diagnostics/pipeline messages
+    pub deadline_ms: f64,        // Longest acceptable time from a sensor reading to a motor command
+}
+
+impl Default for PipelineConfig {
+    fn default() -> Self {
+        Self {
+            report_interval_ms: 5000,
+            deadline_ms: 1000.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LatencyStats {
+    pub count: u64,
+    pub mean_ms: f64,
+    pub p50_ms: f64, // Upper bound of the bucket holding the median, or the maximum
+    pub p95_ms: f64,
+    pub max_ms: f64,
+    pub buckets: Vec<u64>, // Counts per `BUCKETS_MS` bucket, then the overflow
+}
+
+// Readings through one stage during a report window
+#[derive(Debug, Clone, Serialize)]
+pub struct StageStats {
+    pub stage: PipelineStage,
+    pub rate_hz: f64,
+    pub latency: LatencyStats, // Since the reading left the previous stage
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PipelineDiagnostics {
+    pub timestamp: SystemTime,
+    pub window_ms: u64,
+    pub stages: Vec<StageStats>,
+    pub end_to_end: LatencyStats, // From the sensor to the hardware interface
+    pub deadline_ms: f64,
+    pub deadline_misses: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Histogram {
+    buckets: [u64; BUCKETS_MS.len() + 1],
+    count: u64,
+    sum_ms: f64,
+    max_ms: f64,
+}
+
+impl Histogram {
+    fn record(&mut self, ms: f64) {
+        let bucket = BUCKETS_MS.iter().position(|&bound| ms <= bound).unwrap_or(BUCKETS_MS.len());
+        self.buckets[bucket] += 1;
+        self.count += 1;
+        self.sum_ms += ms;
+        self.max_ms = self.max_ms.max(ms);
+    }
+
+    fn quantile(&self, q: f64) -> f64 {
+        let target = (self.count as f64 * q).ceil() as u64;
+        let mut seen = 0;
+        for (bucket, &count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= target.max(1) {
+                return BUCKETS_MS.get(bucket).map_or(self.max_ms, |&bound| bound.min(self.max_ms));
+            }
+        }
+        self.max_ms
+    }
+
+    fn stats(&self) -> LatencyStats {
+        if self.count == 0 {
+            return LatencyStats { buckets: self.buckets.to_vec(), ..LatencyStats::default() };
+        }
+        LatencyStats {
+            count: self.count,
+            mean_ms: self.sum_ms / self.count as f64,
+            p50_ms: self.quantile(0.5),
+            p95_ms: self.quantile(0.95),
+            max_ms: self.max_ms,
+            buckets: self.buckets.to_vec(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct StageRecord {
+    passed: u64,
+    latency: Histogram,
+    last_sequence: Option<u64>,
+}
+
+struct Recorder {
+    stages: BTreeMap<PipelineStage, StageRecord>,
+    end_to_end: Histogram,
+    deadline_misses: u64,
+    window_start: Instant,
+}
+
+// Stamps traces as they leave each stage and keeps latency histograms and
+// rates per stage. Cloning gives another handle to the same recorder.
+#[derive(Clone)]
+pub struct Tracer {
+    recorder: Arc<Mutex<Recorder>>,
+    deadline_ms: f64,
+}
+
+impl Tracer {
+    pub fn new(config: &PipelineConfig) -> Self {
+        Self {
+            recorder: Arc::new(Mutex::new(Recorder {
+                stages: BTreeMap::new(),
+                end_to_end: Histogram::default(),
+                deadline_misses: 0,
+                window_start: Instant::now(),
+            })),
+            deadline_ms: config.deadline_ms,
+        }
+    }
+
+    // Trace of a new reading, leaving the sensor now
+    pub fn start(&self, sequence: u64) -> Trace {
+        let mut trace = Trace { sequence, hops: Vec::new() };
+        self.hop(&mut trace, PipelineStage::Sensor);
+        trace
+    }
+
+    // Stamps the trace as leaving `stage` now. A reading that reaches a stage
+    // more than once, such as a path sent along two routes, counts once.
+    pub fn hop(&self, trace: &mut Trace, stage: PipelineStage) {
+        let now = SystemTime::now();
+        let since = |time: Option<&(PipelineStage, SystemTime)>| {
+            time.map(|(_, time)| now.duration_since(*time).unwrap_or_default().as_secs_f64() * 1000.0)
+        };
+        let latency = since(trace.hops.last());
+        let end_to_end = since(trace.hops.first());
+        trace.hops.push((stage, now));
+
+        let mut recorder = self.recorder.lock().unwrap();
+        let record = recorder.stages.entry(stage).or_default();
+        if record.last_sequence.is_some_and(|last| last >= trace.sequence) {
+            return;
+        }
+        record.last_sequence = Some(trace.sequence);
+        record.passed += 1;
+        if let Some(latency) = latency {
+            record.latency.record(latency);
+        }
+
+        if stage == PipelineStage::Hardware
+            && let Some(end_to_end) = end_to_end
+        {
+            recorder.end_to_end.record(end_to_end);
+            if end_to_end > self.deadline_ms {
+                recorder.deadline_misses += 1;
+            }
+        }
+    }
+
+    // Statistics since the last call, starting a new window
+    pub fn take(&self) -> PipelineDiagnostics {
+        let mut recorder = self.recorder.lock().unwrap();
+        let window = recorder.window_start.elapsed().as_secs_f64().max(1e-3);
+
+        let stages = recorder.stages.iter_mut()
+            .map(|(&stage, record)| {
+                let stats = StageStats {
+                    stage,
+                    rate_hz: record.passed as f64 / window,
+                    latency: record.latency.stats(),
+                };
+                record.passed = 0;
+                record.latency = Histogram::default();
+                stats
+            })
+            .collect();
+
+        let diagnostics = PipelineDiagnostics {
+            timestamp: SystemTime::now(),
+            window_ms: (window * 1000.0) as u64,
+            stages,
+            end_to_end: recorder.end_to_end.stats(),
+            deadline_ms: self.deadline_ms,
+            deadline_misses: recorder.deadline_misses,
+        };
+
+        recorder.end_to_end = Histogram::default();
+        recorder.deadline_misses = 0;
+        recorder.window_start = Instant::now();
+        diagnostics
+    }
+}
+
+// Publishes pipeline latency and rates on diagnostics/pipeline and warns
+// when readings reach the motors later than the deadline
+pub struct PipelineMonitor {
+    tracer: Tracer,
+    diagnostics_pub: Publisher<PipelineDiagnostics>,
+    log_tx: LogSender,
+    shutdown_rx: broadcast::Receiver<()>,
+    config: PipelineConfig,
+}
+
+impl PipelineMonitor {
+    pub fn new(
+        tracer: Tracer,
+        bus: &Bus,
+        log_tx: LogSender,
+        shutdown_rx: broadcast::Receiver<()>,
+        config: PipelineConfig,
+    ) -> Self {
+        Self {
+            tracer,
+            diagnostics_pub: bus.publisher(topics::PIPELINE_DIAGNOSTICS),
+            log_tx,
+            shutdown_rx,
+            config,
+        }
+    }
+
+    pub async fn run(mut self) {
+        let _ = self.log_tx.send(create_log(
+            "PipelineMonitor",
+            LogLevel::Info,
+            format!("Starting pipeline monitor, deadline {} ms", self.config.deadline_ms)
+        )).await;
+
+        let mut report = interval(Duration::from_millis(self.config.report_interval_ms.max(1)));
+        // The first tick completes at once, with nothing recorded yet
+        report.tick().await;
+
+        loop {
+            tokio::select! {
+                _ = self.shutdown_rx.recv() => {
+                    let _ = self.log_tx.send(create_log(
+                        "PipelineMonitor",
+                        LogLevel::Info,
+                        "Shutdown signal received".to_string()
+                    )).await;
+                    break;
+                }
+                _ = report.tick() => {
+                    self.report().await;
+                }
+            }
+        }
+
+        let _ = self.log_tx.send(create_log(
+            "PipelineMonitor",
+            LogLevel::Info,
+            "Stopped".to_string()
+        )).await;
+    }
+
+    async fn report(&mut self) {
+        let diagnostics = self.tracer.take();
+
+        if diagnostics.deadline_misses > 0 {
+            let _ = self.log_tx.send(create_log(
+                "PipelineMonitor",
+                LogLevel::Warn,
+                format!("{} of {} readings reached the motors after the {} ms deadline, slowest {:.1} ms",
+                    diagnostics.deadline_misses, diagnostics.end_to_end.count,
+                    diagnostics.deadline_ms, diagnostics.end_to_end.max_ms)
+            )).await;
+        }
+
+        self.diagnostics_pub.publish(diagnostics).await;
+    }
+}
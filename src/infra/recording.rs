@@ -0,0 +1,407 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use mcap::{Writer, records::MessageHeader};
+use chrono::Local;
+use crossterm::style::Stylize;
+
+// Foxglove Log FlatBuffer binary schema, for the per-module log channels
+const LOG_SCHEMA: &[u8] = include_bytes!("../../schemas/Log.bfbs");
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RecordingConfig {
+    pub directory: String,      // Where the log_<timestamp>.mcap files are written, created if missing
+    pub max_file_mb: f32,       // Size at which the next file is started, 0 for no limit
+    pub max_file_s: u64,        // Seconds after which the next file is started, 0 for no limit
+    pub max_total_mb: f32,      // Oldest files are deleted to keep the directory under this, 0 for no limit
+    pub flush_interval_ms: u64, // How often buffered messages are written out, 0 to only write full chunks
+}
+
+impl Default for RecordingConfig {
+    fn default() -> Self {
+        Self {
+            directory: ".".to_string(),
+            max_file_mb: 512.0,
+            max_file_s: 3600,
+            max_total_mb: 0.0,
+            flush_interval_ms: 1000,
+        }
+    }
+}
+
+// Encoding of a recorded channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Log,  // foxglove.Log FlatBuffers
+    Json, // Schemaless JSON
+}
+
+struct McapFile {
+    writer: Writer<File>,
+    path: PathBuf,
+    opened: Instant,
+    log_schema: Option<u16>,
+    channels: HashMap<String, u16>, // By topic, added to each file as it is first written
+}
+
+// Writes the Logger's messages to MCAP files in `directory`, starting a new
+// file when the current one is too big or too old and deleting the oldest
+// files beyond the retention limit. Each file is finished, with its summary
+// and index, before the next one is started.
+pub struct Recorder {
+    config: RecordingConfig,
+    file: Option<McapFile>,
+}
+
+impl Recorder {
+    pub fn new(config: RecordingConfig) -> Self {
+        let mut recorder = Self { config, file: None };
+        recorder.open();
+        recorder
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.file.as_ref().map(|file| file.path.as_path())
+    }
+
+    // How often `maintain` should run, for flushing and rotation
+    pub fn maintain_interval(&self) -> Duration {
+        match self.config.flush_interval_ms {
+            0 => Duration::from_secs(1),
+            ms => Duration::from_millis(ms),
+        }
+    }
+
+    pub fn write(&mut self, topic: &str, encoding: Encoding, log_time: u64, data: &[u8]) {
+        let Some(file) = &mut self.file else {
+            return;
+        };
+
+        let channel_id = match file.channels.get(topic) {
+            Some(&channel_id) => channel_id,
+            None => match file.add_channel(topic, encoding) {
+                Ok(channel_id) => channel_id,
+                Err(e) => {
+                    eprintln!("{} Failed to create MCAP channel for {}: {}", "[Logger]".dark_grey(), topic, e);
+                    return;
+                }
+            },
+        };
+
+        let header = MessageHeader {
+            channel_id,
+            sequence: 0,
+            log_time,
+            publish_time: log_time,
+        };
+
+        if let Err(e) = file.writer.write_to_known_channel(&header, data) {
+            eprintln!("{} Error writing to MCAP: {}", "[Logger]".dark_grey(), e);
+        }
+    }
+
+    // Flushes buffered messages so they survive a crash, starts the next file
+    // once the current one reaches its size or age limit, and deletes the
+    // oldest files once the directory is over its retention limit
+    pub fn maintain(&mut self) {
+        let Some(file) = &mut self.file else {
+            return;
+        };
+
+        if self.config.flush_interval_ms > 0
+            && let Err(e) = file.writer.flush()
+        {
+            eprintln!("{} Error flushing MCAP file: {}", "[Logger]".dark_grey(), e);
+        }
+
+        let size = fs::metadata(&file.path).map(|metadata| metadata.len()).unwrap_or(0);
+        let too_big = self.config.max_file_mb > 0.0 && size >= mb_to_bytes(self.config.max_file_mb);
+        let too_old = self.config.max_file_s > 0 && file.opened.elapsed() >= Duration::from_secs(self.config.max_file_s);
+        if too_big || too_old {
+            self.finish();
+            self.open();
+        } else {
+            self.enforce_retention();
+        }
+    }
+
+    // Finishes the current file, writing its summary and index
+    pub fn finish(&mut self) {
+        if let Some(mut file) = self.file.take() {
+            match file.writer.finish() {
+                Ok(summary) => {
+                    let count = summary.stats.map_or(0, |stats| stats.message_count);
+                    println!(
+                        "{} Finalized {} with {} messages (indexed)",
+                        "[Logger]".dark_grey(), file.path.display(), count
+                    );
+                }
+                Err(e) => eprintln!("{} Error finishing {}: {}", "[Logger]".dark_grey(), file.path.display(), e),
+            }
+        }
+    }
+
+    fn open(&mut self) {
+        let directory = Path::new(&self.config.directory);
+        if let Err(e) = fs::create_dir_all(directory) {
+            eprintln!("{} Failed to create {}: {}. Logging disabled.", "[Logger]".dark_grey(), directory.display(), e);
+            return;
+        }
+
+        // Human-readable timestamp, with a suffix when a file was already
+        // started in the same second
+        let timestamp = Local::now().format("%y%m%d_%H%M%S").to_string();
+        let mut path = directory.join(format!("log_{}.mcap", timestamp));
+        let mut suffix = 1;
+        while path.exists() {
+            path = directory.join(format!("log_{}_{}.mcap", timestamp, suffix));
+            suffix += 1;
+        }
+
+        match File::create(&path).map_err(mcap::McapError::from).and_then(Writer::new) {
+            Ok(writer) => {
+                println!("{} Created MCAP log file: {}", "[Logger]".dark_grey(), path.display().to_string().magenta().bold());
+                self.file = Some(McapFile {
+                    writer,
+                    path,
+                    opened: Instant::now(),
+                    log_schema: None,
+                    channels: HashMap::new(),
+                });
+                self.enforce_retention();
+            }
+            Err(e) => {
+                eprintln!("{} Failed to create MCAP file {}: {}. Logging disabled.", "[Logger]".dark_grey(), path.display(), e);
+            }
+        }
+    }
+
+    // Deletes the oldest log files, never the current one, until the
+    // directory's log files fit in `max_total_mb`
+    fn enforce_retention(&self) {
+        if self.config.max_total_mb <= 0.0 {
+            return;
+        }
+        let Ok(entries) = fs::read_dir(&self.config.directory) else {
+            return;
+        };
+
+        let mut files: Vec<(PathBuf, u64)> = entries
+            .filter_map(Result::ok)
+            .filter(|entry| {
+                let name = entry.file_name();
+                let name = name.to_string_lossy();
+                name.starts_with("log_") && name.ends_with(".mcap")
+            })
+            .filter_map(|entry| Some((entry.path(), entry.metadata().ok()?.len())))
+            .collect();
+        // The timestamp in the name orders the files oldest first
+        files.sort();
+
+        let limit = mb_to_bytes(self.config.max_total_mb);
+        let mut total: u64 = files.iter().map(|(_, size)| size).sum();
+        for (path, size) in files {
+            if total <= limit {
+                break;
+            }
+            if self.path() == Some(path.as_path()) {
+                continue;
+            }
+            match fs::remove_file(&path) {
+                Ok(()) => {
+                    println!("{} Deleted {} to stay under {} MB", "[Logger]".dark_grey(), path.display(), self.config.max_total_mb);
+                    total -= size;
+                }
+                Err(e) => eprintln!("{} Failed to delete {}: {}", "[Logger]".dark_grey(), path.display(), e),
+            }
+        }
+    }
+}
+
+impl McapFile {
+    fn add_channel(&mut self, topic: &str, encoding: Encoding) -> mcap::McapResult<u16> {
+        let (schema_id, message_encoding) = match encoding {
+            Encoding::Log => {
+                let schema_id = match self.log_schema {
+                    Some(schema_id) => schema_id,
+                    None => {
+                        let schema_id = self.writer.add_schema("foxglove.Log", "flatbuffer", LOG_SCHEMA)?;
+                        self.log_schema = Some(schema_id);
+                        schema_id
+                    }
+                };
+                (schema_id, "flatbuffer")
+            }
+            Encoding::Json => (0, "json"),
+        };
+
+        let channel_id = self.writer.add_channel(schema_id, topic, message_encoding, &BTreeMap::new())?;
+        self.channels.insert(topic.to_string(), channel_id);
+        Ok(channel_id)
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        // Ensure the MCAP file is properly finalized when the logger is dropped
+        self.finish();
+    }
+}
+
+fn mb_to_bytes(mb: f32) -> u64 {
+    (mb as f64 * 1024.0 * 1024.0) as u64
+}
+
+// What `recover` found in a damaged file
+pub struct Recovered {
+    pub messages: usize,
+    pub channels: usize,
+    pub error: Option<String>, // Why reading stopped before the end of the file
+}
+
+// Rewrites the messages of an MCAP file that was not finished, for example
+// after a crash, into a new file at `output` with its summary and index.
+// Messages are read up to the first damaged record; everything written by
+// the last flush before the crash is there.
+pub fn recover(input: &Path, output: &Path) -> Result<Recovered, String> {
+    let data = fs::read(input)
+        .map_err(|e| format!("Failed to read {}: {}", input.display(), e))?;
+    let messages = mcap::MessageStream::new_with_options(&data, mcap::read::Options::IgnoreEndMagic.into())
+        .map_err(|e| format!("Failed to read {}: {}", input.display(), e))?;
+
+    let file = File::create_new(output)
+        .map_err(|e| format!("Failed to create {}: {}", output.display(), e))?;
+    let mut writer = Writer::new(file)
+        .map_err(|e| format!("Failed to create {}: {}", output.display(), e))?;
+
+    // Channel and schema ids in the input, to those in the output
+    let mut channels: HashMap<u16, u16> = HashMap::new();
+    let mut schemas: HashMap<u16, u16> = HashMap::new();
+    let mut recovered = Recovered { messages: 0, channels: 0, error: None };
+
+    for message in messages {
+        let message = match message {
+            Ok(message) => message,
+            Err(e) => {
+                recovered.error = Some(e.to_string());
+                break;
+            }
+        };
+
+        let channel = &message.channel;
+        let channel_id = match channels.get(&channel.id) {
+            Some(&channel_id) => channel_id,
+            None => {
+                let schema_id = match &channel.schema {
+                    Some(schema) => match schemas.get(&schema.id) {
+                        Some(&schema_id) => schema_id,
+                        None => {
+                            let schema_id = writer.add_schema(&schema.name, &schema.encoding, &schema.data)
+                                .map_err(|e| format!("Failed to write {}: {}", output.display(), e))?;
+                            schemas.insert(schema.id, schema_id);
+                            schema_id
+                        }
+                    },
+                    None => 0,
+                };
+                let channel_id = writer.add_channel(schema_id, &channel.topic, &channel.message_encoding, &channel.metadata)
+                    .map_err(|e| format!("Failed to write {}: {}", output.display(), e))?;
+                channels.insert(channel.id, channel_id);
+                recovered.channels += 1;
+                channel_id
+            }
+        };
+
+        let header = MessageHeader {
+            channel_id,
+            sequence: message.sequence,
+            log_time: message.log_time,
+            publish_time: message.publish_time,
+        };
+        writer.write_to_known_channel(&header, &message.data)
+            .map_err(|e| format!("Failed to write {}: {}", output.display(), e))?;
+        recovered.messages += 1;
+    }
+
+    writer.finish()
+        .map_err(|e| format!("Failed to finish {}: {}", output.display(), e))?;
+    Ok(recovered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A fresh directory for one test's files
+    fn test_directory(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("rover-recording-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    #[test]
+    fn maintain_deletes_the_oldest_files_over_the_limit() {
+        let directory = test_directory("retention");
+        let mut recorder = Recorder::new(RecordingConfig {
+            directory: directory.to_string_lossy().to_string(),
+            max_total_mb: 1.0,
+            ..Default::default()
+        });
+
+        // Files left by earlier runs, older than the current one
+        let older = directory.join("log_000101_000000.mcap");
+        let newer = directory.join("log_000101_000001.mcap");
+        fs::write(&older, vec![0; 700 * 1024]).unwrap();
+        fs::write(&newer, vec![0; 400 * 1024]).unwrap();
+
+        recorder.maintain();
+        assert!(!older.exists());
+        assert!(newer.exists());
+        assert!(recorder.path().unwrap().exists());
+
+        recorder.finish();
+        let _ = fs::remove_dir_all(&directory);
+    }
+
+    #[test]
+    fn recover_reads_back_a_file_with_a_truncated_footer() {
+        let directory = test_directory("recover");
+        let mut recorder = Recorder::new(RecordingConfig {
+            directory: directory.to_string_lossy().to_string(),
+            ..Default::default()
+        });
+        for i in 0..10u64 {
+            recorder.write("test/json", Encoding::Json, i, format!("{{\"i\":{}}}", i).as_bytes());
+        }
+        let input = recorder.path().unwrap().to_path_buf();
+        recorder.finish();
+
+        // Cut the file off partway through its footer, as a crash while
+        // finishing would
+        let data = fs::read(&input).unwrap();
+        fs::write(&input, &data[..data.len() - 20]).unwrap();
+
+        let output = directory.join("recovered.mcap");
+        let recovered = recover(&input, &output).unwrap();
+        assert_eq!(recovered.messages, 10);
+        assert_eq!(recovered.channels, 1);
+        assert!(recovered.error.is_some());
+
+        let data = fs::read(&output).unwrap();
+        let messages: Vec<_> = mcap::MessageStream::new(&data).unwrap()
+            .map(|message| message.unwrap())
+            .collect();
+        assert_eq!(messages.len(), 10);
+        for (i, message) in messages.iter().enumerate() {
+            assert_eq!(message.channel.topic, "test/json");
+            assert_eq!(message.log_time, i as u64);
+            assert_eq!(message.data.as_ref(), format!("{{\"i\":{}}}", i).as_bytes());
+        }
+
+        let _ = fs::remove_dir_all(&directory);
+    }
+}
@@ -1,18 +1,23 @@
-use crate::types::{UserCommand, ManualControl, LogEntry, LogLevel};
+use crate::types::{CommandId, CommandRequest, UserCommand, ManualControl, LogLevel};
 use crate::infra::logger::create_log;
-use tokio::sync::{broadcast, mpsc};
+use crate::infra::log_levels::LogSender;
+use crate::infra::bus::Publisher;
+use tokio::sync::broadcast;
 use tokio::time::{Duration, sleep};
 
+// Local commands are numbered from here, well clear of the IDs operators pick
+const FIRST_COMMAND_ID: CommandId = 1 << 48;
+
 pub struct DirectUserInput {
-    command_tx: mpsc::Sender<UserCommand>,
-    log_tx: mpsc::Sender<LogEntry>,
+    command_tx: Publisher<CommandRequest>,
+    log_tx: LogSender,
     shutdown_rx: broadcast::Receiver<()>,
 }
 
 impl DirectUserInput {
     pub fn new(
-        command_tx: mpsc::Sender<UserCommand>,
-        log_tx: mpsc::Sender<LogEntry>,
+        command_tx: Publisher<CommandRequest>,
+        log_tx: LogSender,
         shutdown_rx: broadcast::Receiver<()>,
     ) -> Self {
         Self {
@@ -30,7 +35,7 @@ impl DirectUserInput {
         )).await;
 
         // Simulate user pressing forward, then turning
-        let commands = vec![
+        let commands = [
             UserCommand::ManualControl(ManualControl::MoveForward(0.5)),
             UserCommand::ManualControl(ManualControl::TurnLeft(0.3)),
             UserCommand::ManualControl(ManualControl::MoveForward(0.7)),
@@ -59,13 +64,8 @@ impl DirectUserInput {
                             format!("User input: {:?}", command)
                         )).await;
 
-                        if let Err(_) = self.command_tx.send(command).await {
-                            let _ = self.log_tx.send(create_log(
-                                "DirectUserInput",
-                                LogLevel::Error,
-                                "Failed to send user command".to_string()
-                            )).await;
-                        }
+                        let request = CommandRequest { id: FIRST_COMMAND_ID + cmd_idx as CommandId, command };
+                        self.command_tx.publish(request).await;
 
                         cmd_idx += 1;
                     }
@@ -1,45 +1,49 @@
-use crate::types::{SensorData, UserCommand, HardwareStatus, LogEntry, LogLevel};
+use crate::types::{SensorData, PipelineStage, CommandRequest, HardwareStatus, LogLevel};
 use crate::infra::logger::create_log;
-use tokio::sync::{broadcast, mpsc};
+use crate::infra::log_levels::LogSender;
+use crate::infra::bus::{Publisher, Subscriber};
+use crate::infra::pipeline::Tracer;
+use tokio::sync::broadcast;
 
 pub struct InputManager {
     // Inputs
-    sensor_rx: mpsc::Receiver<SensorData>,
-    user_cmd_rx: mpsc::Receiver<UserCommand>,
-    hw_status_rx: mpsc::Receiver<HardwareStatus>,
+    sensor_rx: Subscriber<SensorData>,
+    user_cmd_rx: Subscriber<CommandRequest>,
+    hw_status_rx: Subscriber<HardwareStatus>,
 
     // Outputs
-    env_understanding_tx: mpsc::Sender<SensorData>,
-    state_manager_sensor_tx: mpsc::Sender<SensorData>,
-    state_manager_cmd_tx: mpsc::Sender<UserCommand>,
-    task_manager_tx: mpsc::Sender<UserCommand>,
-    log_tx: mpsc::Sender<LogEntry>,
+    sensor_pub: Publisher<SensorData>,
+    command_pub: Publisher<CommandRequest>,
+    hw_status_tx: Publisher<HardwareStatus>,
+    log_tx: LogSender,
 
     shutdown_rx: broadcast::Receiver<()>,
+    tracer: Tracer,
 }
 
 impl InputManager {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
-        sensor_rx: mpsc::Receiver<SensorData>,
-        user_cmd_rx: mpsc::Receiver<UserCommand>,
-        hw_status_rx: mpsc::Receiver<HardwareStatus>,
-        env_understanding_tx: mpsc::Sender<SensorData>,
-        state_manager_sensor_tx: mpsc::Sender<SensorData>,
-        state_manager_cmd_tx: mpsc::Sender<UserCommand>,
-        task_manager_tx: mpsc::Sender<UserCommand>,
-        log_tx: mpsc::Sender<LogEntry>,
+        sensor_rx: Subscriber<SensorData>,
+        user_cmd_rx: Subscriber<CommandRequest>,
+        hw_status_rx: Subscriber<HardwareStatus>,
+        sensor_pub: Publisher<SensorData>,
+        command_pub: Publisher<CommandRequest>,
+        hw_status_tx: Publisher<HardwareStatus>,
+        log_tx: LogSender,
         shutdown_rx: broadcast::Receiver<()>,
+        tracer: Tracer,
     ) -> Self {
         Self {
             sensor_rx,
             user_cmd_rx,
             hw_status_rx,
-            env_understanding_tx,
-            state_manager_sensor_tx,
-            state_manager_cmd_tx,
-            task_manager_tx,
+            sensor_pub,
+            command_pub,
+            hw_status_tx,
             log_tx,
             shutdown_rx,
+            tracer,
         }
     }
 
@@ -79,22 +83,23 @@ impl InputManager {
         )).await;
     }
 
-    async fn handle_sensor_data(&mut self, sensor_data: SensorData) {
-        // Route sensor data to environment understanding and state manager
-        let _ = self.env_understanding_tx.send(sensor_data.clone()).await;
-        let _ = self.state_manager_sensor_tx.send(sensor_data).await;
+    async fn handle_sensor_data(&mut self, mut sensor_data: SensorData) {
+        self.tracer.hop(&mut sensor_data.trace, PipelineStage::InputManager);
+
+        // Read by environment understanding, state manager and energy manager
+        self.sensor_pub.publish(sensor_data).await;
     }
 
-    async fn handle_user_command(&mut self, command: UserCommand) {
+    async fn handle_user_command(&mut self, request: CommandRequest) {
         let _ = self.log_tx.send(create_log(
             "InputManager",
             LogLevel::Info,
-            format!("Routing user command: {:?}", command)
+            format!("Routing user command {}: {:?}", request.id, request.command)
         )).await;
 
-        // Route commands to state manager and task manager
-        let _ = self.state_manager_cmd_tx.send(command.clone()).await;
-        let _ = self.task_manager_tx.send(command).await;
+        // Read by the state manager and task manager. Manual control reaches
+        // behaviour through the state manager once it is accepted.
+        self.command_pub.publish(request).await;
     }
 
     async fn handle_hardware_status(&mut self, status: HardwareStatus) {
@@ -115,5 +120,8 @@ impl InputManager {
             }
             _ => {}
         }
+
+        // Hardware health goes into status updates and on to operators
+        self.hw_status_tx.publish(status).await;
     }
 }
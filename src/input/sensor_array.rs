@@ -1,28 +1,35 @@
-use crate::types::{SensorData, ImuData, GpsData, LogEntry, LogLevel};
+use crate::types::{SensorData, ImuData, GpsData, LogLevel};
 use crate::infra::logger::create_log;
-use tokio::sync::{broadcast, mpsc};
+use crate::infra::log_levels::LogSender;
+use crate::infra::bus::Publisher;
+use crate::infra::pipeline::Tracer;
+use crate::rlog;
+use tokio::sync::broadcast;
 use tokio::time::{Duration, sleep};
 use std::time::SystemTime;
 
+// Module name for rlog! entries
+const LOG_MODULE: &str = "SensorArray";
+
 pub struct SensorArray {
-    hardware_interface_tx: mpsc::Sender<SensorData>,
-    safety_sensor_tx: mpsc::Sender<SensorData>,
-    log_tx: mpsc::Sender<LogEntry>,
+    sensor_pub: Publisher<SensorData>,
+    log_tx: LogSender,
     shutdown_rx: broadcast::Receiver<()>,
+    tracer: Tracer,
 }
 
 impl SensorArray {
     pub fn new(
-        hardware_interface_tx: mpsc::Sender<SensorData>,
-        safety_sensor_tx: mpsc::Sender<SensorData>,
-        log_tx: mpsc::Sender<LogEntry>,
+        sensor_pub: Publisher<SensorData>,
+        log_tx: LogSender,
         shutdown_rx: broadcast::Receiver<()>,
+        tracer: Tracer,
     ) -> Self {
         Self {
-            hardware_interface_tx,
-            safety_sensor_tx,
+            sensor_pub,
             log_tx,
             shutdown_rx,
+            tracer,
         }
     }
 
@@ -48,31 +55,13 @@ impl SensorArray {
                 _ = sleep(Duration::from_millis(500)) => {
                     let sensor_data = self.generate_sensor_data(counter);
 
-                    // Send to hardware interface (which forwards to input manager) and safety controller
-                    if let Err(_) = self.hardware_interface_tx.send(sensor_data.clone()).await {
-                        let _ = self.log_tx.send(create_log(
-                            "SensorArray",
-                            LogLevel::Error,
-                            "Failed to send sensor data to hardware interface".to_string()
-                        )).await;
-                    }
-
-                    if let Err(_) = self.safety_sensor_tx.send(sensor_data).await {
-                        let _ = self.log_tx.send(create_log(
-                            "SensorArray",
-                            LogLevel::Error,
-                            "Failed to send sensor data to safety controller".to_string()
-                        )).await;
-                    }
+                    // Read by the hardware interface (which forwards to input manager) and safety controller
+                    self.sensor_pub.publish(sensor_data).await;
 
                     counter += 1;
 
                     if counter % 10 == 0 {
-                        let _ = self.log_tx.send(create_log(
-                            "SensorArray",
-                            LogLevel::Debug,
-                            format!("Published sensor reading #{}", counter)
-                        )).await;
+                        rlog!(self.log_tx, Debug, "Published sensor reading #{}", counter, reading = counter);
                     }
                 }
             }
@@ -117,6 +106,7 @@ impl SensorArray {
                 accuracy: 2.5,
             },
             battery_level: 0.85 - (counter as f32 * 0.0001).min(0.3),
+            trace: self.tracer.start(counter),
         }
     }
 }
@@ -1,25 +1,26 @@
-use crate::types::{UserCommand, MissionCommand, Waypoint, LogEntry, LogLevel};
+use crate::types::{CommandRequest, LogLevel};
 use crate::infra::logger::create_log;
-use tokio::sync::{broadcast, mpsc};
-use tokio::time::{Duration, sleep};
+use crate::infra::log_levels::LogSender;
+use crate::infra::bus::{Publisher, Subscriber};
+use tokio::sync::broadcast;
 
 pub struct UserInstructions {
-    command_tx: mpsc::Sender<UserCommand>,
-    feedback_rx: mpsc::Receiver<String>,
-    log_tx: mpsc::Sender<LogEntry>,
+    command_tx: Publisher<CommandRequest>,
+    operator_rx: Subscriber<CommandRequest>,
+    log_tx: LogSender,
     shutdown_rx: broadcast::Receiver<()>,
 }
 
 impl UserInstructions {
     pub fn new(
-        command_tx: mpsc::Sender<UserCommand>,
-        feedback_rx: mpsc::Receiver<String>,
-        log_tx: mpsc::Sender<LogEntry>,
+        command_tx: Publisher<CommandRequest>,
+        operator_rx: Subscriber<CommandRequest>,
+        log_tx: LogSender,
         shutdown_rx: broadcast::Receiver<()>,
     ) -> Self {
         Self {
             command_tx,
-            feedback_rx,
+            operator_rx,
             log_tx,
             shutdown_rx,
         }
@@ -32,9 +33,6 @@ impl UserInstructions {
             "Starting user instructions module".to_string()
         )).await;
 
-        // Simulate receiving a mission command after a delay
-        let mut mission_sent = false;
-
         loop {
             tokio::select! {
                 _ = self.shutdown_rx.recv() => {
@@ -45,39 +43,14 @@ impl UserInstructions {
                     )).await;
                     break;
                 }
-                Some(feedback) = self.feedback_rx.recv() => {
-                    let _ = self.log_tx.send(create_log(
-                        "UserInstructions",
-                        LogLevel::Info,
-                        format!("Received feedback: {}", feedback)
-                    )).await;
-                }
-                _ = sleep(Duration::from_secs(5)), if !mission_sent => {
-                    // Send a patrol mission
-                    let mission = UserCommand::MissionCommand(MissionCommand::Patrol {
-                        waypoints: vec![
-                            Waypoint { lat: 37.7749, lon: -122.4194, tolerance: 2.0 },
-                            Waypoint { lat: 37.7750, lon: -122.4195, tolerance: 2.0 },
-                            Waypoint { lat: 37.7751, lon: -122.4196, tolerance: 2.0 },
-                        ],
-                        loops: 2,
-                    });
-
+                Some(request) = self.operator_rx.recv() => {
                     let _ = self.log_tx.send(create_log(
                         "UserInstructions",
                         LogLevel::Info,
-                        "Sending patrol mission".to_string()
+                        format!("Operator command {}: {:?}", request.id, request.command)
                     )).await;
 
-                    if let Err(_) = self.command_tx.send(mission).await {
-                        let _ = self.log_tx.send(create_log(
-                            "UserInstructions",
-                            LogLevel::Error,
-                            "Failed to send mission command".to_string()
-                        )).await;
-                    }
-
-                    mission_sent = true;
+                    self.command_tx.publish(request).await;
                 }
             }
         }
@@ -7,20 +7,45 @@ pub mod planning;
 pub mod control;
 pub mod output;
 
-use tokio::sync::{broadcast, mpsc};
+use infra::bus::{Bus, QueuePolicy, topics};
+use infra::config::RoverConfig;
+use infra::pipeline::Tracer;
+use infra::diagnostics::DiagnosticReporter;
+use infra::metrics::Metrics;
+use infra::log_levels::{LogLevels, LogSender};
+use std::sync::Arc;
+use tokio::sync::{broadcast, watch, RwLock};
 use tokio::task::JoinHandle;
 use crossterm::style::Stylize;
 
 pub struct RoverSystem {
+    config: RoverConfig,
+    bus: Bus,
+    tracer: Tracer,
+    metrics: Metrics,
     shutdown_tx: broadcast::Sender<()>,
     task_handles: Vec<JoinHandle<()>>,
 }
 
+impl Default for RoverSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl RoverSystem {
     pub fn new() -> Self {
+        Self::with_config(RoverConfig::default())
+    }
+
+    pub fn with_config(config: RoverConfig) -> Self {
         let (shutdown_tx, _) = broadcast::channel(16);
 
         Self {
+            tracer: Tracer::new(&config.pipeline),
+            metrics: Metrics::new(),
+            config,
+            bus: Bus::new(),
             shutdown_tx,
             task_handles: Vec::new(),
         }
@@ -33,77 +58,207 @@ impl RoverSystem {
         println!("{}\n", "Initializing all modules...".yellow());
 
         // Create all channels
-        let (log_tx, log_rx) = mpsc::channel(256);
+        // Log entries below their module's level are dropped by the sender, as
+        // are entries that find the channel full
+        let (log_tx, log_rx) = LogSender::channel(256, LogLevels::new(&self.config.log));
 
         // Input layer channels
         // Sensor Array -> Hardware Interface -> Input Manager
-        let (sensor_data_hw_tx, sensor_data_hw_rx) = mpsc::channel(32);
-        let (sensor_data_im_tx, sensor_data_rx) = mpsc::channel(32);
-        let (sensor_data_safety_tx, sensor_data_safety_rx) = mpsc::channel(32);
-        let (user_command_tx, user_command_rx) = mpsc::channel(32);
-        let (hw_status_tx, hw_status_rx) = mpsc::channel(32);
-        let (motor_command_hw_tx, motor_command_hw_rx) = mpsc::channel(32);
+        // Sensor Array -> Safety Controller
+        let sensor_raw_pub = self.bus.publisher(topics::SENSOR_RAW);
+        // Queue policies: stale readings are dropped rather than holding up the
+        // sensors, safety acts on the newest reading only
+        let sensor_data_hw_rx = self.bus.subscribe(topics::SENSOR_RAW, "HardwareInterface", QueuePolicy::Bounded(32));
+        let sensor_data_safety_rx = self.bus.subscribe(topics::SENSOR_RAW, "SafetyController", QueuePolicy::LatestOnly);
+        let sensor_data_im_tx = self.bus.publisher(topics::SENSOR_HARDWARE);
+        let sensor_data_rx = self.bus.subscribe(topics::SENSOR_HARDWARE, "InputManager", QueuePolicy::Bounded(32));
+        // Commands and acknowledgements are never dropped; each is answered
+        let user_command_tx = self.bus.publisher(topics::USER_COMMANDS);
+        let user_command_rx = self.bus.subscribe(topics::USER_COMMANDS, "InputManager", QueuePolicy::Lossless(32));
+        let hw_status_tx = self.bus.publisher(topics::HARDWARE_STATUS);
+        let hw_status_rx = self.bus.subscribe(topics::HARDWARE_STATUS, "InputManager", QueuePolicy::Bounded(32));
 
         // Input manager outputs
-        let (im_env_tx, env_rx) = mpsc::channel(32);
-        let (im_state_sensor_tx, state_sensor_rx) = mpsc::channel(32);
-        let (im_state_cmd_tx, state_cmd_rx) = mpsc::channel(32);
-        let (im_task_tx, task_cmd_rx) = mpsc::channel(32);
+        let im_sensor_pub = self.bus.publisher(topics::SENSOR_DATA);
+        let env_rx = self.bus.subscribe(topics::SENSOR_DATA, "EnvUnderstanding", QueuePolicy::Bounded(32));
+        let state_sensor_rx = self.bus.subscribe(topics::SENSOR_DATA, "StateManager", QueuePolicy::Bounded(32));
+        let energy_sensor_rx = self.bus.subscribe(topics::SENSOR_DATA, "EnergyManager", QueuePolicy::LatestOnly);
+        let im_command_pub = self.bus.publisher(topics::ROUTED_COMMANDS);
+        let state_cmd_rx = self.bus.subscribe(topics::ROUTED_COMMANDS, "StateManager", QueuePolicy::Lossless(32));
+        let task_cmd_rx = self.bus.subscribe(topics::ROUTED_COMMANDS, "TaskMissionManager", QueuePolicy::Lossless(32));
 
         // State manager outputs
-        let (state_tx, _state_rx) = mpsc::channel(32);
-        let (state_safety_tx, state_safety_rx) = mpsc::channel(32);
-        let (state_task_tx, state_task_rx) = mpsc::channel(32);
+        let state_pub = self.bus.publisher(topics::ROBOT_STATE);
+        let state_manual_tx = self.bus.publisher(topics::MANUAL_CONTROL);
+        let manual_rx = self.bus.subscribe(topics::MANUAL_CONTROL, "Behaviour", QueuePolicy::Lossless(32));
+        // Every transition matters, so state is never dropped
+        let state_output_rx = self.bus.subscribe(topics::ROBOT_STATE, "OutputManager", QueuePolicy::Lossless(32));
+        let state_safety_rx = self.bus.subscribe(topics::ROBOT_STATE, "SafetyController", QueuePolicy::Lossless(32));
+        let state_task_rx = self.bus.subscribe(topics::ROBOT_STATE, "TaskMissionManager", QueuePolicy::Lossless(32));
+
+        // Safety controller and diagnostics faults -> State manager and Communication module
+        // Every fault raised or cleared changes the robot state
+        let fault_tx = self.bus.publisher(topics::SAFETY_FAULTS);
+        let fault_rx = self.bus.subscribe(topics::SAFETY_FAULTS, "StateManager", QueuePolicy::Lossless(32));
+        let fault_comm_rx = self.bus.subscribe(topics::SAFETY_FAULTS, "CommunicationModule", QueuePolicy::Lossless(32));
+
+        // Component statuses from every module -> Diagnostics aggregator -> Communication module
+        let diagnostic_status_rx = self.bus.subscribe(topics::DIAGNOSTIC_STATUS, "Diagnostics", QueuePolicy::Bounded(64));
+        let diagnostics_summary_pub = self.bus.publisher(topics::DIAGNOSTIC_SUMMARY);
+        let diagnostics_summary_rx = self.bus.subscribe(topics::DIAGNOSTIC_SUMMARY, "CommunicationModule", QueuePolicy::LatestOnly);
+
+        // Hardware status: Input manager -> Output manager
+        // Only the current health goes into status updates
+        let im_hw_status_tx = self.bus.publisher(topics::HARDWARE_HEALTH);
+        let output_hw_status_rx = self.bus.subscribe(topics::HARDWARE_HEALTH, "OutputManager", QueuePolicy::LatestOnly);
+
+        // Fused position estimate from the state manager, latest value only
+        let (position_tx, position_rx) = watch::channel(None);
+
+        // Battery and range estimate from the energy manager, latest value only
+        let (energy_tx, energy_rx) = watch::channel(None);
+
+        // Geofence, set from config and updated by the task/mission manager
+        let geofence = Arc::new(RwLock::new(self.config.geofence.clone()));
 
         // Environment understanding
-        let (env_state_tx, env_state_rx) = mpsc::channel(32);
+        // Obstacle avoidance only keeps the latest environment
+        let env_state_tx = self.bus.publisher(topics::ENVIRONMENT);
+        let env_state_rx = self.bus.subscribe(topics::ENVIRONMENT, "ObstacleAvoidance", QueuePolicy::LatestOnly);
+
+        // Task/Mission manager <-> Environment understanding scans
+        let scan_req_tx = self.bus.publisher(topics::SCAN_REQUESTS);
+        let scan_req_rx = self.bus.subscribe(topics::SCAN_REQUESTS, "EnvUnderstanding", QueuePolicy::Lossless(32));
+        let scan_result_tx = self.bus.publisher(topics::SCAN_RESULTS);
+        let scan_result_rx = self.bus.subscribe(topics::SCAN_RESULTS, "TaskMissionManager", QueuePolicy::Lossless(32));
 
         // Task/Mission manager
-        let (goal_tx, goal_rx) = mpsc::channel(32);
-
-        // Stance bidirectional channels
-        let (stance_obstacle_req_tx, stance_obstacle_req_rx) = mpsc::channel(32);
-        let (stance_obstacle_resp_tx, stance_obstacle_resp_rx) = mpsc::channel(32);
-        let (stance_goal_req_tx, stance_goal_req_rx) = mpsc::channel(32);
-        let (stance_goal_resp_tx, stance_goal_resp_rx) = mpsc::channel(32);
-        let (stance_behavior_tx, stance_behavior_rx) = mpsc::channel(32);
-
-        // Goal planning and obstacle avoidance bidirectional
-        let (goal_obstacle_req_tx, goal_obstacle_req_rx) = mpsc::channel(32);
-        let (obstacle_goal_resp_tx, obstacle_goal_resp_rx) = mpsc::channel(32);
-
-        // Behavior outputs
-        let (behavior_path_goal_tx, behavior_path_goal_rx) = mpsc::channel(32);
-        let (behavior_path_obstacle_tx, behavior_path_obstacle_rx) = mpsc::channel(32);
-        
-        // Behaviour -> Safety Controller -> Hardware Interface
-        let (behavior_safety_tx, behavior_safety_rx) = mpsc::channel(32);
-        let (behavior_hw_tx, behavior_hw_rx) = mpsc::channel(32);
-
-        // Output manager
-        let (status_feedback_tx, status_feedback_rx) = mpsc::channel(32);
-        let (status_comm_tx, status_comm_rx) = mpsc::channel(32);
+        // Goals are re-sent while a task runs, so a newer one replaces one not yet planned
+        let goal_tx = self.bus.publisher(topics::GOALS);
+        let goal_rx = self.bus.subscribe(topics::GOALS, "GoalPlanning", QueuePolicy::LatestOnly);
+        let mission_event_tx = self.bus.publisher(topics::MISSION_EVENTS);
+        let mission_event_rx = self.bus.subscribe(topics::MISSION_EVENTS, "UserFeedback", QueuePolicy::Lossless(32));
+
+        // Progress of the running mission, latest value only
+        let (mission_tx, mission_rx) = watch::channel(None);
+
+        // Missions the task/mission manager has accepted, for the state manager
+        let task_accepted_tx = self.bus.publisher(topics::MISSIONS_ACCEPTED);
+        let accepted_rx = self.bus.subscribe(topics::MISSIONS_ACCEPTED, "StateManager", QueuePolicy::Lossless(32));
+
+        // Stance requests and replies. Every request is answered, so none are dropped
+        let stance_obstacle_req_tx = self.bus.publisher(topics::STANCE_OBSTACLE_REQUESTS);
+        let stance_obstacle_req_rx = self.bus.subscribe(topics::STANCE_OBSTACLE_REQUESTS, "Stance", QueuePolicy::Lossless(32));
+        let stance_obstacle_resp_tx = self.bus.publisher(topics::STANCE_OBSTACLE_REPLIES);
+        let stance_obstacle_resp_rx = self.bus.subscribe(topics::STANCE_OBSTACLE_REPLIES, "ObstacleAvoidance", QueuePolicy::Lossless(32));
+        let stance_goal_req_tx = self.bus.publisher(topics::STANCE_GOAL_REQUESTS);
+        let stance_goal_req_rx = self.bus.subscribe(topics::STANCE_GOAL_REQUESTS, "Stance", QueuePolicy::Lossless(32));
+        let stance_goal_resp_tx = self.bus.publisher(topics::STANCE_GOAL_REPLIES);
+        let stance_goal_resp_rx = self.bus.subscribe(topics::STANCE_GOAL_REPLIES, "GoalPlanning", QueuePolicy::Lossless(32));
+        let stance_safety_req_tx = self.bus.publisher(topics::STANCE_SAFETY_REQUESTS);
+        let stance_safety_req_rx = self.bus.subscribe(topics::STANCE_SAFETY_REQUESTS, "Stance", QueuePolicy::Lossless(32));
+        // Behaviour adjusts to the current stance only
+        let stance_behavior_tx = self.bus.publisher(topics::STANCE);
+        let stance_behavior_rx = self.bus.subscribe(topics::STANCE, "Behaviour", QueuePolicy::LatestOnly);
+
+        // Goal planning <-> Obstacle avoidance path requests
+        let goal_obstacle_req_tx = self.bus.publisher(topics::PATH_REQUESTS);
+        let goal_obstacle_req_rx = self.bus.subscribe(topics::PATH_REQUESTS, "ObstacleAvoidance", QueuePolicy::Lossless(32));
+        let obstacle_goal_resp_tx = self.bus.publisher(topics::PATH_REPLIES);
+        let obstacle_goal_resp_rx = self.bus.subscribe(topics::PATH_REPLIES, "GoalPlanning", QueuePolicy::Lossless(32));
+
+        // Behaviour inputs: a newer path replaces one not yet followed
+        let behavior_path_goal_tx = self.bus.publisher(topics::PLANNED_PATHS);
+        let behavior_path_goal_rx = self.bus.subscribe(topics::PLANNED_PATHS, "Behaviour", QueuePolicy::LatestOnly);
+        let behavior_path_obstacle_tx = self.bus.publisher(topics::AVOIDANCE_PATHS);
+        let behavior_path_obstacle_rx = self.bus.subscribe(topics::AVOIDANCE_PATHS, "Behaviour", QueuePolicy::LatestOnly);
+
+        // Behaviour -> Command Arbiter -> Safety Controller -> Hardware Interface.
+        // No link waits: a stalled stage must not hold up the watchdogs or stops
+        // behind it. The arbiter keeps the latest command per source, so it gets
+        // a short backlog across sources; after it, only the newest command counts.
+        let behavior_arbiter_tx = self.bus.publisher(topics::BEHAVIOR_COMMANDS);
+        let behavior_arbiter_rx = self.bus.subscribe(topics::BEHAVIOR_COMMANDS, "CommandArbiter", QueuePolicy::Bounded(32));
+        let arbiter_safety_tx = self.bus.publisher(topics::ARBITRATED_COMMANDS);
+        let behavior_safety_rx = self.bus.subscribe(topics::ARBITRATED_COMMANDS, "SafetyController", QueuePolicy::LatestOnly);
+        let behavior_hw_tx = self.bus.publisher(topics::VALIDATED_COMMANDS);
+        let behavior_hw_rx = self.bus.subscribe(topics::VALIDATED_COMMANDS, "HardwareInterface", QueuePolicy::LatestOnly);
+
+        // Output manager: user feedback announces every significant change, the
+        // operator link only sends the newest status
+        let status_feedback_tx = self.bus.publisher(topics::STATUS_CHANGES);
+        let status_feedback_rx = self.bus.subscribe(topics::STATUS_CHANGES, "UserFeedback", QueuePolicy::Lossless(32));
+        let status_comm_tx = self.bus.publisher(topics::STATUS_UPDATES);
+        let status_comm_rx = self.bus.subscribe(topics::STATUS_UPDATES, "CommunicationModule", QueuePolicy::LatestOnly);
 
         // User feedback
-        let (user_feedback_tx, user_feedback_rx) = mpsc::channel(32);
+        let user_feedback_tx = self.bus.publisher(topics::USER_FEEDBACK);
+        let user_feedback_rx = self.bus.subscribe(topics::USER_FEEDBACK, "CommunicationModule", QueuePolicy::Lossless(32));
 
-        // Communication module to user instructions
-        let (comm_user_tx, comm_user_rx) = mpsc::channel(32);
+        // Command acknowledgements -> Communication Module
+        let ack_tx = self.bus.publisher(topics::COMMAND_ACKS);
+        let ack_rx = self.bus.subscribe(topics::COMMAND_ACKS, "CommunicationModule", QueuePolicy::Lossless(32));
 
-        // Calibration storage (not actively used in this basic implementation)
-        let (_calib_req_tx, calib_req_rx) = mpsc::channel(32);
-        let (calib_resp_tx, _calib_resp_rx) = mpsc::channel(32);
+        // Operator commands: Communication Module -> User Instructions
+        let comm_user_tx = self.bus.publisher(topics::OPERATOR_COMMANDS);
+        let comm_user_rx = self.bus.subscribe(topics::OPERATOR_COMMANDS, "UserInstructions", QueuePolicy::Lossless(32));
+
+        // Calibration storage <-> Command Arbiter
+        let calib_req_tx = self.bus.publisher(topics::CALIBRATION_REQUESTS);
+        let calib_req_rx = self.bus.subscribe(topics::CALIBRATION_REQUESTS, "CalibrationStorage", QueuePolicy::Lossless(32));
+        let calib_resp_tx = self.bus.publisher(topics::CALIBRATION_DATA);
+        let calib_resp_rx = self.bus.subscribe(topics::CALIBRATION_DATA, "CommandArbiter", QueuePolicy::Lossless(32));
 
         // Spawn logger first
-        let logger_module = infra::logger::Logger::new(log_rx, self.shutdown_tx.subscribe());
+        let logger_module = infra::logger::Logger::new(log_rx, log_tx.clone(), self.bus.tap(), self.shutdown_tx.subscribe(), self.metrics.clone(), self.config.recording.clone(), self.config.log.sinks.clone());
         self.task_handles.push(tokio::spawn(logger_module.run()));
 
+        let bus_monitor = infra::bus_monitor::BusMonitor::new(
+            self.bus.clone(),
+            log_tx.clone(),
+            self.shutdown_tx.subscribe(),
+            self.config.bus.clone(),
+        );
+        self.task_handles.push(tokio::spawn(bus_monitor.run()));
+
+        let pipeline_monitor = infra::pipeline::PipelineMonitor::new(
+            self.tracer.clone(),
+            &self.bus,
+            log_tx.clone(),
+            self.shutdown_tx.subscribe(),
+            self.config.pipeline.clone(),
+        );
+        self.task_handles.push(tokio::spawn(pipeline_monitor.run()));
+
+        let diagnostics = infra::diagnostics::DiagnosticAggregator::new(
+            diagnostic_status_rx,
+            diagnostics_summary_pub,
+            fault_tx.clone(),
+            log_tx.clone(),
+            self.shutdown_tx.subscribe(),
+            self.config.diagnostics.clone(),
+        );
+        self.task_handles.push(tokio::spawn(diagnostics.run()));
+
+        #[cfg(feature = "metrics")]
+        {
+            let metrics_server = infra::metrics::MetricsServer::new(
+                self.metrics.clone(),
+                self.bus.clone(),
+                mission_rx.clone(),
+                log_tx.clone(),
+                self.shutdown_tx.subscribe(),
+                self.config.metrics.clone(),
+            );
+            self.task_handles.push(tokio::spawn(metrics_server.run()));
+        }
+
         // Spawn input layer modules
         let sensor_array = input::sensor_array::SensorArray::new(
-            sensor_data_hw_tx,
-            sensor_data_safety_tx,
+            sensor_raw_pub,
             log_tx.clone(),
             self.shutdown_tx.subscribe(),
+            self.tracer.clone(),
         );
         self.task_handles.push(tokio::spawn(sensor_array.run()));
 
@@ -125,11 +280,13 @@ impl RoverSystem {
         let hardware_interface = output::hardware_interface::HardwareInterface::new(
             sensor_data_hw_rx,
             behavior_hw_rx,
-            motor_command_hw_rx,
             sensor_data_im_tx,
             hw_status_tx,
+            DiagnosticReporter::new(self.bus.publisher(topics::DIAGNOSTIC_STATUS)),
             log_tx.clone(),
             self.shutdown_tx.subscribe(),
+            self.tracer.clone(),
+            self.metrics.clone(),
         );
         self.task_handles.push(tokio::spawn(hardware_interface.run()));
 
@@ -138,12 +295,12 @@ impl RoverSystem {
             sensor_data_rx,
             user_command_rx,
             hw_status_rx,
-            im_env_tx,
-            im_state_sensor_tx,
-            im_state_cmd_tx,
-            im_task_tx,
+            im_sensor_pub,
+            im_command_pub,
+            im_hw_status_tx,
             log_tx.clone(),
             self.shutdown_tx.subscribe(),
+            self.tracer.clone(),
         );
         self.task_handles.push(tokio::spawn(input_manager.run()));
 
@@ -159,18 +316,25 @@ impl RoverSystem {
         // Spawn core processing modules
         let env_understanding = perception::environment_understanding::EnvironmentUnderstanding::new(
             env_rx,
+            scan_req_rx,
             env_state_tx,
+            scan_result_tx,
             log_tx.clone(),
             self.shutdown_tx.subscribe(),
+            self.tracer.clone(),
         );
         self.task_handles.push(tokio::spawn(env_understanding.run()));
 
         let state_manager = planning::state_manager::StateManager::new(
             state_sensor_rx,
             state_cmd_rx,
-            state_tx,
-            state_safety_tx,
-            state_task_tx,
+            fault_rx,
+            accepted_rx,
+            state_pub,
+            position_tx,
+            state_manual_tx,
+            ack_tx.clone(),
+            DiagnosticReporter::new(self.bus.publisher(topics::DIAGNOSTIC_STATUS)),
             log_tx.clone(),
             self.shutdown_tx.subscribe(),
         );
@@ -179,6 +343,7 @@ impl RoverSystem {
         let stance = perception::stance::Stance::new(
             stance_obstacle_req_rx,
             stance_goal_req_rx,
+            stance_safety_req_rx,
             stance_obstacle_resp_tx,
             stance_goal_resp_tx,
             stance_behavior_tx,
@@ -187,12 +352,31 @@ impl RoverSystem {
         );
         self.task_handles.push(tokio::spawn(stance.run()));
 
+        let energy_manager = planning::energy_manager::EnergyManager::new(
+            energy_sensor_rx,
+            energy_tx,
+            log_tx.clone(),
+            self.shutdown_tx.subscribe(),
+            self.config.energy.clone(),
+        );
+        self.task_handles.push(tokio::spawn(energy_manager.run()));
+
         let task_manager = planning::task_mission_manager::TaskMissionManager::new(
             task_cmd_rx,
             state_task_rx,
+            position_rx.clone(),
+            energy_rx.clone(),
+            scan_result_rx,
             goal_tx,
+            scan_req_tx,
+            mission_event_tx,
+            ack_tx.clone(),
+            mission_tx,
+            task_accepted_tx,
             log_tx.clone(),
             self.shutdown_tx.subscribe(),
+            self.config.mission.clone(),
+            geofence.clone(),
         );
         self.task_handles.push(tokio::spawn(task_manager.run()));
 
@@ -203,8 +387,10 @@ impl RoverSystem {
             goal_obstacle_req_tx,
             obstacle_goal_resp_rx,
             behavior_path_goal_tx,
+            position_rx.clone(),
             log_tx.clone(),
             self.shutdown_tx.subscribe(),
+            geofence.clone(),
         );
         self.task_handles.push(tokio::spawn(goal_planning.run()));
 
@@ -217,6 +403,7 @@ impl RoverSystem {
             behavior_path_obstacle_tx,
             log_tx.clone(),
             self.shutdown_tx.subscribe(),
+            self.tracer.clone(),
         );
         self.task_handles.push(tokio::spawn(obstacle_avoidance.run()));
 
@@ -224,39 +411,67 @@ impl RoverSystem {
             behavior_path_goal_rx,
             behavior_path_obstacle_rx,
             stance_behavior_rx,
-            behavior_safety_tx,
+            manual_rx,
+            behavior_arbiter_tx,
             log_tx.clone(),
             self.shutdown_tx.subscribe(),
+            self.tracer.clone(),
         );
         self.task_handles.push(tokio::spawn(behaviour.run()));
 
+        // Spawn command arbiter (picks one behaviour and ramps it before safety validation)
+        let command_arbiter = control::command_arbiter::CommandArbiter::new(
+            behavior_arbiter_rx,
+            calib_resp_rx,
+            calib_req_tx,
+            arbiter_safety_tx,
+            log_tx.clone(),
+            self.shutdown_tx.subscribe(),
+            self.config.arbiter.clone(),
+            self.tracer.clone(),
+            std::time::Duration::from_millis(self.config.safety.watchdog.command_deadman_ms),
+        );
+        self.task_handles.push(tokio::spawn(command_arbiter.run()));
+
         // Spawn safety controller (validates commands before forwarding to Hardware Interface)
         let safety_controller = control::safety_controller::SafetyController::new(
             behavior_safety_rx,
             sensor_data_safety_rx,
             state_safety_rx,
+            position_rx.clone(),
             behavior_hw_tx,
+            fault_tx,
+            stance_safety_req_tx,
+            ack_tx,
+            DiagnosticReporter::new(self.bus.publisher(topics::DIAGNOSTIC_STATUS)),
             log_tx.clone(),
             self.shutdown_tx.subscribe(),
+            self.config.safety.clone(),
+            geofence,
+            self.tracer.clone(),
+            self.metrics.clone(),
         );
         self.task_handles.push(tokio::spawn(safety_controller.run()));
 
-        // Output Manager is no longer in the direct command path (Behaviour -> Hardware Interface)
-        // but keeping for status updates and backward compatibility
-        let (dummy_motor_cmd_tx, dummy_motor_cmd_rx) = mpsc::channel(32);
-        drop(dummy_motor_cmd_tx); // Close the sender so receiver will never receive
+        // Output Manager builds status updates from the robot state, position,
+        // mission progress, battery and hardware health
         let output_manager = output::output_manager::OutputManager::new(
-            dummy_motor_cmd_rx,
-            motor_command_hw_tx,
+            state_output_rx,
+            position_rx.clone(),
+            energy_rx,
+            mission_rx,
+            output_hw_status_rx,
             status_feedback_tx,
             status_comm_tx,
             log_tx.clone(),
             self.shutdown_tx.subscribe(),
+            self.config.status.clone(),
         );
         self.task_handles.push(tokio::spawn(output_manager.run()));
 
         let user_feedback = output::user_feedback::UserFeedbackModule::new(
             status_feedback_rx,
+            mission_event_rx,
             user_feedback_tx,
             log_tx.clone(),
             self.shutdown_tx.subscribe(),
@@ -266,9 +481,15 @@ impl RoverSystem {
         let communication = output::communication_module::CommunicationModule::new(
             status_comm_rx,
             user_feedback_rx,
+            ack_rx,
+            fault_comm_rx,
+            diagnostics_summary_rx,
+            position_rx,
             comm_user_tx,
+            DiagnosticReporter::new(self.bus.publisher(topics::DIAGNOSTIC_STATUS)),
             log_tx.clone(),
             self.shutdown_tx.subscribe(),
+            self.config.operator_link.clone(),
         );
         self.task_handles.push(tokio::spawn(communication.run()));
 
@@ -280,6 +501,11 @@ impl RoverSystem {
         self.shutdown_tx.clone()
     }
 
+    // Message bus shared by all modules, for extra subscribers, taps and topic stats
+    pub fn bus(&self) -> Bus {
+        self.bus.clone()
+    }
+
     pub async fn wait_for_completion(self) {
         // Wait for all tasks to complete
         for handle in self.task_handles {
@@ -2,6 +2,10 @@ use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind};
 use crossterm::style::Stylize;
 use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
 use rover_embassy::RoverSystem;
+use rover_embassy::infra::config::{DEFAULT_CONFIG_PATH, RoverConfig};
+use rover_embassy::infra::logger::export_json_lines;
+use rover_embassy::infra::recording::recover;
+use std::path::Path;
 use tokio::sync::broadcast;
 use tokio::time::{Duration, sleep};
 
@@ -14,19 +18,18 @@ async fn input_listener(shutdown_tx: broadcast::Sender<()>) {
 
     loop {
         // Poll for events in a non-blocking way
-        if let Ok(true) = crossterm::event::poll(Duration::from_millis(100)) {
-            if let Ok(Event::Key(KeyEvent {
+        if let Ok(true) = crossterm::event::poll(Duration::from_millis(100))
+            && let Ok(Event::Key(KeyEvent {
                 code: KeyCode::Char('q') | KeyCode::Char('Q'),
                 kind: KeyEventKind::Press,
                 ..
             })) = crossterm::event::read()
-            {
-                // Disable raw mode before shutting down
-                let _ = disable_raw_mode();
-                println!("\n{}", "[Main] Shutdown requested, terminating all modules...".red().bold());
-                let _ = shutdown_tx.send(());
-                break;
-            }
+        {
+            // Disable raw mode before shutting down
+            let _ = disable_raw_mode();
+            println!("\n{}", "[Main] Shutdown requested, terminating all modules...".red().bold());
+            let _ = shutdown_tx.send(());
+            break;
         }
     }
 
@@ -34,13 +37,122 @@ async fn input_listener(shutdown_tx: broadcast::Sender<()>) {
     let _ = disable_raw_mode();
 }
 
+// Value given after `flag`, if the flag is present
+fn flag_value(args: &[String], flag: &str) -> Option<String> {
+    let i = args.iter().position(|arg| arg == flag)?;
+    match args.get(i + 1) {
+        Some(value) => Some(value.clone()),
+        None => {
+            eprintln!("[Main] {} requires a value", flag);
+            std::process::exit(1);
+        }
+    }
+}
+
+// Loads the file given with `--config <path>`, or rover.toml if it exists,
+// then applies `--log-level <spec>` on top
+fn load_config() -> RoverConfig {
+    let args: Vec<String> = std::env::args().collect();
+    let path = match flag_value(&args, "--config") {
+        Some(path) => Some(path),
+        None if Path::new(DEFAULT_CONFIG_PATH).exists() => Some(DEFAULT_CONFIG_PATH.to_string()),
+        None => None,
+    };
+
+    let mut config = match path {
+        Some(path) => match RoverConfig::load(&path) {
+            Ok(config) => {
+                println!("{} {}", "[Main] Loaded config from".cyan(), path.cyan().bold());
+                config
+            }
+            Err(e) => {
+                eprintln!("[Main] {}", e);
+                std::process::exit(1);
+            }
+        },
+        None => RoverConfig::default(),
+    };
+
+    if let Some(spec) = flag_value(&args, "--log-level")
+        && let Err(e) = config.log.apply_spec(&spec)
+    {
+        eprintln!("[Main] --log-level: {}", e);
+        std::process::exit(1);
+    }
+    config
+}
+
+// `export-logs <file.mcap>` writes the log entries recorded in a file to
+// stdout as JSON lines
+fn export_logs(args: &[String]) -> ! {
+    let Some(path) = args.first() else {
+        eprintln!("[Main] export-logs requires an MCAP file");
+        std::process::exit(1);
+    };
+
+    let mut stdout = std::io::stdout().lock();
+    match export_json_lines(Path::new(path), &mut stdout) {
+        Ok(count) => {
+            eprintln!("[Main] Exported {} log entries from {}", count, path);
+            std::process::exit(0);
+        }
+        Err(e) => {
+            eprintln!("[Main] {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+// `recover <file.mcap> [output.mcap]` rewrites a file that was not finished,
+// for example after a crash, with its summary and index. The output defaults
+// to <file>-recovered.mcap next to the input.
+fn recover_file(args: &[String]) -> ! {
+    let Some(input) = args.first().map(Path::new) else {
+        eprintln!("[Main] recover requires an MCAP file");
+        std::process::exit(1);
+    };
+    let output = match args.get(1) {
+        Some(output) => Path::new(output).to_path_buf(),
+        None => {
+            let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+            input.with_file_name(format!("{}-recovered.mcap", stem))
+        }
+    };
+
+    match recover(input, &output) {
+        Ok(recovered) => {
+            if let Some(error) = &recovered.error {
+                eprintln!("[Main] {} ends early: {}", input.display(), error);
+            }
+            eprintln!(
+                "[Main] Recovered {} messages on {} channels into {}",
+                recovered.messages, recovered.channels, output.display()
+            );
+            std::process::exit(0);
+        }
+        Err(e) => {
+            eprintln!("[Main] {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
 #[tokio::main]
 async fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).is_some_and(|command| command == "export-logs") {
+        export_logs(&args[2..]);
+    }
+    if args.get(1).is_some_and(|command| command == "recover") {
+        recover_file(&args[2..]);
+    }
+
     // Create and initialize the rover system
-    let mut rover = RoverSystem::new();
+    let mut rover = RoverSystem::with_config(load_config());
 
     // Get shutdown transmitter before initializing
     let shutdown_tx = rover.shutdown_tx();
+    let bus = rover.bus();
 
     // Initialize and start all modules
     rover.initialize_and_run().await;
@@ -58,5 +170,12 @@ async fn main() {
     // Wait for all tasks to complete
     rover.wait_for_completion().await;
 
+    for stats in bus.stats() {
+        println!(
+            "{} {}: {} published, {} dropped",
+            "[Bus]".dark_grey(), stats.topic, stats.published, stats.dropped
+        );
+    }
+
     println!("\n{} {}", "✓".green().bold(), "[Main] All modules stopped. Goodbye!".green());
 }
@@ -1,29 +1,86 @@
-use crate::types::{StatusUpdate, UserFeedback, LogEntry, LogLevel};
+use crate::types::{StatusUpdate, FaultReport, DiagnosticSummary, DiagnosticStatus, DiagnosticLevel, PositionEstimate, CommandRequest, CommandAck, AckStatus, UserFeedback, RoverMessage, LogLevel};
 use crate::infra::logger::create_log;
-use tokio::sync::{broadcast, mpsc};
+use crate::infra::log_levels::LogSender;
+use crate::infra::bus::{Publisher, Subscriber};
+use crate::infra::diagnostics::DiagnosticReporter;
+use crate::output::operator_link::{Encoding, serve_connection};
+use crate::output::telemetry::TelemetryConfig;
+use serde::{Deserialize, Serialize};
+use tokio::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::sync::{broadcast, watch};
+use tokio::time::{Duration, interval};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OperatorLinkConfig {
+    pub json_address: String,   // TCP address for JSON lines, empty to disable
+    pub binary_address: String, // TCP address for length-prefixed MessagePack, empty to disable
+    pub outbound_buffer: usize, // Messages queued per operator before a slow one starts missing them
+    pub telemetry: TelemetryConfig,
+}
+
+impl Default for OperatorLinkConfig {
+    fn default() -> Self {
+        Self {
+            json_address: "127.0.0.1:9870".to_string(),
+            binary_address: "127.0.0.1:9871".to_string(),
+            outbound_buffer: 256,
+            telemetry: TelemetryConfig::default(),
+        }
+    }
+}
 
 pub struct CommunicationModule {
-    status_rx: mpsc::Receiver<StatusUpdate>,
-    feedback_rx: mpsc::Receiver<UserFeedback>,
-    user_instructions_tx: mpsc::Sender<String>,
-    log_tx: mpsc::Sender<LogEntry>,
+    status_rx: Subscriber<StatusUpdate>,
+    feedback_rx: Subscriber<UserFeedback>,
+    ack_rx: Subscriber<CommandAck>,
+    fault_rx: Subscriber<FaultReport>,
+    diagnostics_rx: Subscriber<DiagnosticSummary>,
+    position_rx: watch::Receiver<Option<PositionEstimate>>,
+    user_instructions_tx: Publisher<CommandRequest>,
+    diagnostics: DiagnosticReporter,
+    log_tx: LogSender,
     shutdown_rx: broadcast::Receiver<()>,
+    config: OperatorLinkConfig,
+    outbound_tx: broadcast::Sender<RoverMessage>,
+    operators: Arc<AtomicUsize>, // Connections currently open
+    unavailable: Vec<String>,    // Configured addresses that could not be bound
 }
 
 impl CommunicationModule {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
-        status_rx: mpsc::Receiver<StatusUpdate>,
-        feedback_rx: mpsc::Receiver<UserFeedback>,
-        user_instructions_tx: mpsc::Sender<String>,
-        log_tx: mpsc::Sender<LogEntry>,
+        status_rx: Subscriber<StatusUpdate>,
+        feedback_rx: Subscriber<UserFeedback>,
+        ack_rx: Subscriber<CommandAck>,
+        fault_rx: Subscriber<FaultReport>,
+        diagnostics_rx: Subscriber<DiagnosticSummary>,
+        position_rx: watch::Receiver<Option<PositionEstimate>>,
+        user_instructions_tx: Publisher<CommandRequest>,
+        diagnostics: DiagnosticReporter,
+        log_tx: LogSender,
         shutdown_rx: broadcast::Receiver<()>,
+        config: OperatorLinkConfig,
     ) -> Self {
+        let (outbound_tx, _) = broadcast::channel(config.outbound_buffer.max(1));
+
         Self {
             status_rx,
             feedback_rx,
+            ack_rx,
+            fault_rx,
+            diagnostics_rx,
+            position_rx,
             user_instructions_tx,
+            diagnostics,
             log_tx,
             shutdown_rx,
+            config,
+            outbound_tx,
+            operators: Arc::new(AtomicUsize::new(0)),
+            unavailable: Vec::new(),
         }
     }
 
@@ -34,6 +91,10 @@ impl CommunicationModule {
             "Starting communication module".to_string()
         )).await;
 
+        let json_listener = self.bind(self.config.json_address.clone(), Encoding::Json).await;
+        let binary_listener = self.bind(self.config.binary_address.clone(), Encoding::MessagePack).await;
+        let mut diagnostics_tick = interval(Duration::from_secs(1));
+
         loop {
             tokio::select! {
                 _ = self.shutdown_rx.recv() => {
@@ -45,10 +106,35 @@ impl CommunicationModule {
                     break;
                 }
                 Some(status) = self.status_rx.recv() => {
-                    self.handle_status(status).await;
+                    self.broadcast(RoverMessage::Status(status));
                 }
                 Some(feedback) = self.feedback_rx.recv() => {
-                    self.handle_feedback(feedback).await;
+                    self.broadcast(RoverMessage::Feedback(feedback));
+                }
+                Some(ack) = self.ack_rx.recv() => {
+                    self.log_ack(&ack).await;
+                    self.broadcast(RoverMessage::Ack(ack));
+                }
+                Some(report) = self.fault_rx.recv() => {
+                    self.broadcast(RoverMessage::Fault(report));
+                }
+                Some(summary) = self.diagnostics_rx.recv() => {
+                    self.broadcast(RoverMessage::Diagnostics(summary));
+                }
+                _ = diagnostics_tick.tick() => {
+                    self.report_diagnostics().await;
+                }
+                Ok(()) = self.position_rx.changed() => {
+                    let position = self.position_rx.borrow_and_update().clone();
+                    if let Some(position) = position {
+                        self.broadcast(RoverMessage::Pose(position));
+                    }
+                }
+                Some(connection) = accept(&json_listener) => {
+                    self.spawn_connection(connection, Encoding::Json);
+                }
+                Some(connection) = accept(&binary_listener) => {
+                    self.spawn_connection(connection, Encoding::MessagePack);
                 }
             }
         }
@@ -60,23 +146,83 @@ impl CommunicationModule {
         )).await;
     }
 
-    async fn handle_status(&mut self, status: StatusUpdate) {
-        // In a real system, this would send telemetry to remote systems
+    async fn bind(&mut self, address: String, encoding: Encoding) -> Option<TcpListener> {
+        if address.is_empty() {
+            return None;
+        }
+
+        let (level, message, listener) = match TcpListener::bind(&address).await {
+            Ok(listener) => (LogLevel::Info, format!("Operator link listening on {} ({:?})", address, encoding), Some(listener)),
+            Err(e) => {
+                self.unavailable.push(address.clone());
+                (LogLevel::Error, format!("Cannot listen on {}: {}", address, e), None)
+            }
+        };
+
         let _ = self.log_tx.send(create_log(
             "CommunicationModule",
-            LogLevel::Debug,
-            format!("Broadcasting status update: {:?}", status.state)
+            level,
+            message
         )).await;
+        listener
     }
 
-    async fn handle_feedback(&mut self, feedback: UserFeedback) {
-        // Forward feedback back to user instructions
-        let _ = self.user_instructions_tx.send(feedback.message.clone()).await;
+    async fn report_diagnostics(&mut self) {
+        let operators = self.operators.load(Ordering::Relaxed);
+        let status = if self.unavailable.is_empty() {
+            DiagnosticStatus::new("comms/operator_link", DiagnosticLevel::Ok, format!("{} operator(s) connected", operators))
+        } else {
+            DiagnosticStatus::new("comms/operator_link", DiagnosticLevel::Warn, format!("Cannot listen on {}", self.unavailable.join(", ")))
+        };
+        let status = status
+            .with("operators", operators)
+            .with("json_address", &self.config.json_address)
+            .with("binary_address", &self.config.binary_address);
+        self.diagnostics.report(status).await;
+    }
 
+    async fn log_ack(&self, ack: &CommandAck) {
+        let level = match ack.status {
+            AckStatus::Accepted | AckStatus::Completed => LogLevel::Debug,
+            AckStatus::Rejected(_) | AckStatus::Failed(_) => LogLevel::Info,
+        };
         let _ = self.log_tx.send(create_log(
             "CommunicationModule",
-            LogLevel::Debug,
-            format!("Relayed feedback: {}", feedback.message)
+            level,
+            format!("Command {} {:?} by {}", ack.command_id, ack.status, ack.module)
         )).await;
     }
+
+    // Sends to every connected operator. Nothing is kept when none are connected.
+    fn broadcast(&self, message: RoverMessage) {
+        let _ = self.outbound_tx.send(message);
+    }
+
+    fn spawn_connection(&self, (stream, peer): (TcpStream, std::net::SocketAddr), encoding: Encoding) {
+        let connection = serve_connection(
+            stream,
+            peer,
+            encoding,
+            self.user_instructions_tx.clone(),
+            self.outbound_tx.subscribe(),
+            self.config.telemetry.clone(),
+            self.log_tx.clone(),
+            self.shutdown_rx.resubscribe(),
+        );
+
+        let operators = self.operators.clone();
+        operators.fetch_add(1, Ordering::Relaxed);
+        tokio::spawn(async move {
+            connection.await;
+            operators.fetch_sub(1, Ordering::Relaxed);
+        });
+    }
+}
+
+// Next connection on `listener`, never resolving for a disabled listener
+async fn accept(listener: &Option<TcpListener>) -> Option<(TcpStream, std::net::SocketAddr)> {
+    match listener {
+        Some(listener) => listener.accept().await.ok(),
+        None => std::future::pending().await,
+    }
 }
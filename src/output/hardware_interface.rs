@@ -1,41 +1,61 @@
-use crate::types::{HardwareStatus, HealthStatus, MotorCommand, SensorData, BehaviorCommand, Behavior, LogEntry, LogLevel};
+use crate::types::{HardwareStatus, HealthStatus, DiagnosticStatus, DiagnosticLevel, MotorCommand, SensorData, BehaviorCommand, Behavior, PipelineStage, LogLevel};
 use crate::infra::logger::create_log;
-use tokio::sync::{broadcast, mpsc};
-use tokio::time::{Duration, sleep};
+use crate::infra::log_levels::LogSender;
+use crate::infra::bus::{Publisher, Subscriber};
+use crate::infra::pipeline::Tracer;
+use crate::infra::diagnostics::DiagnosticReporter;
+use crate::infra::metrics::Metrics;
+use crate::rlog;
+use tokio::sync::broadcast;
+use tokio::time::{Duration, interval};
 use std::time::SystemTime;
 
+// Module name for rlog! entries
+const LOG_MODULE: &str = "HardwareInterface";
+
+// Motor temperatures (°C) reported as a warning and as an error
+const MOTOR_TEMP_WARN: f32 = 70.0;
+const MOTOR_TEMP_ERROR: f32 = 85.0;
+
 pub struct HardwareInterface {
     // Inputs
-    sensor_rx: mpsc::Receiver<SensorData>,
-    behavior_rx: mpsc::Receiver<BehaviorCommand>,
-    motor_rx: mpsc::Receiver<MotorCommand>,
+    sensor_rx: Subscriber<SensorData>,
+    behavior_rx: Subscriber<BehaviorCommand>,
     
     // Outputs
-    sensor_tx: mpsc::Sender<SensorData>,
-    status_tx: mpsc::Sender<HardwareStatus>,
+    sensor_pub: Publisher<SensorData>,
+    status_tx: Publisher<HardwareStatus>,
+    diagnostics: DiagnosticReporter,
     
-    log_tx: mpsc::Sender<LogEntry>,
+    log_tx: LogSender,
     shutdown_rx: broadcast::Receiver<()>,
+    tracer: Tracer,
+    metrics: Metrics,
 }
 
 impl HardwareInterface {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
-        sensor_rx: mpsc::Receiver<SensorData>,
-        behavior_rx: mpsc::Receiver<BehaviorCommand>,
-        motor_rx: mpsc::Receiver<MotorCommand>,
-        sensor_tx: mpsc::Sender<SensorData>,
-        status_tx: mpsc::Sender<HardwareStatus>,
-        log_tx: mpsc::Sender<LogEntry>,
+        sensor_rx: Subscriber<SensorData>,
+        behavior_rx: Subscriber<BehaviorCommand>,
+        sensor_pub: Publisher<SensorData>,
+        status_tx: Publisher<HardwareStatus>,
+        diagnostics: DiagnosticReporter,
+        log_tx: LogSender,
         shutdown_rx: broadcast::Receiver<()>,
+        tracer: Tracer,
+        metrics: Metrics,
     ) -> Self {
         Self {
             sensor_rx,
             behavior_rx,
-            motor_rx,
-            sensor_tx,
+            sensor_pub,
             status_tx,
+            diagnostics,
             log_tx,
             shutdown_rx,
+            tracer,
+            metrics,
         }
     }
 
@@ -47,6 +67,7 @@ impl HardwareInterface {
         )).await;
 
         let mut counter = 0;
+        let mut status_timer = interval(Duration::from_secs(2));
 
         loop {
             tokio::select! {
@@ -60,37 +81,18 @@ impl HardwareInterface {
                 }
                 Some(sensor_data) = self.sensor_rx.recv() => {
                     // Forward sensor data to Input Manager
-                    if let Err(_) = self.sensor_tx.send(sensor_data).await {
-                        let _ = self.log_tx.send(create_log(
-                            "HardwareInterface",
-                            LogLevel::Error,
-                            "Failed to forward sensor data to input manager".to_string()
-                        )).await;
-                    }
+                    self.sensor_pub.publish(sensor_data).await;
                 }
                 Some(behavior_cmd) = self.behavior_rx.recv() => {
                     // Convert behavior command to motor command and execute
                     self.handle_behavior_command(behavior_cmd).await;
                 }
-                Some(motor_cmd) = self.motor_rx.recv() => {
-                    // Handle direct motor commands (for backward compatibility)
-                    let _ = self.log_tx.send(create_log(
-                        "HardwareInterface",
-                        LogLevel::Debug,
-                        format!("Motor command: L={:.2}, R={:.2}",
-                            motor_cmd.left_speed, motor_cmd.right_speed)
-                    )).await;
-                }
-                _ = sleep(Duration::from_secs(2)) => {
+                _ = status_timer.tick() => {
                     let status = self.generate_hardware_status(counter);
+                    self.report_diagnostics(&status).await;
+                    self.metrics.set_hardware(&status);
 
-                    if let Err(_) = self.status_tx.send(status).await {
-                        let _ = self.log_tx.send(create_log(
-                            "HardwareInterface",
-                            LogLevel::Error,
-                            "Failed to send hardware status".to_string()
-                        )).await;
-                    }
+                    self.status_tx.publish(status).await;
 
                     counter += 1;
                 }
@@ -104,28 +106,24 @@ impl HardwareInterface {
         )).await;
     }
 
-    async fn handle_behavior_command(&mut self, cmd: BehaviorCommand) {
+    async fn handle_behavior_command(&mut self, mut cmd: BehaviorCommand) {
+        let motor_cmd = motor_command_for(&cmd.behavior);
+        if let Some(trace) = cmd.trace.as_mut() {
+            self.tracer.hop(trace, PipelineStage::Hardware);
+        }
+
         match cmd.behavior {
-            Behavior::MoveTowards { target, speed } => {
-                let motor_cmd = self.calculate_motor_command(target, speed);
-                let _ = self.log_tx.send(create_log(
-                    "HardwareInterface",
-                    LogLevel::Debug,
-                    format!("Executing MoveTowards: L={:.2}, R={:.2}",
-                        motor_cmd.left_speed, motor_cmd.right_speed)
-                )).await;
-            }
-            Behavior::AvoidObstacle { direction } => {
-                let motor_cmd = MotorCommand {
-                    left_speed: direction[1] * 0.5,
-                    right_speed: -direction[1] * 0.5,
-                };
-                let _ = self.log_tx.send(create_log(
-                    "HardwareInterface",
-                    LogLevel::Debug,
-                    format!("Executing AvoidObstacle: L={:.2}, R={:.2}",
-                        motor_cmd.left_speed, motor_cmd.right_speed)
-                )).await;
+            Behavior::MoveTowards { .. } | Behavior::AvoidObstacle { .. } | Behavior::Drive { .. } => {
+                if let Some(motor_cmd) = motor_cmd {
+                    rlog!(
+                        self.log_tx,
+                        Debug,
+                        "Executing {}: L={:.2}, R={:.2}",
+                        behavior_name(&cmd.behavior), motor_cmd.left_speed, motor_cmd.right_speed,
+                        left = motor_cmd.left_speed,
+                        right = motor_cmd.right_speed,
+                    );
+                }
             }
             Behavior::EmergencyStop => {
                 let _ = self.log_tx.send(create_log(
@@ -136,11 +134,7 @@ impl HardwareInterface {
             }
             Behavior::AdjustStance(_) => {
                 // Stance adjustments are handled by the stance module
-                let _ = self.log_tx.send(create_log(
-                    "HardwareInterface",
-                    LogLevel::Debug,
-                    "Stance adjustment received".to_string()
-                )).await;
+                rlog!(self.log_tx, "HardwareInterface", LogLevel::Debug, "Stance adjustment received");
             }
             Behavior::Idle => {
                 // No action needed
@@ -148,15 +142,28 @@ impl HardwareInterface {
         }
     }
 
-    fn calculate_motor_command(&self, target: [f32; 3], speed: f32) -> MotorCommand {
-        // Simplified differential drive calculation
-        let angle_to_target = target[1].atan2(target[0]);
-        let turn_factor = angle_to_target.sin();
+    async fn report_diagnostics(&mut self, status: &HardwareStatus) {
+        let (level, message) = match &status.health {
+            HealthStatus::Healthy => (DiagnosticLevel::Ok, "Healthy".to_string()),
+            HealthStatus::Warning(message) => (DiagnosticLevel::Warn, message.clone()),
+            HealthStatus::Critical(message) => (DiagnosticLevel::Error, message.clone()),
+        };
+        self.diagnostics.report(DiagnosticStatus::new("motors/battery", level, message)
+            .with("voltage", format!("{:.2}", status.battery_voltage))).await;
 
-        MotorCommand {
-            left_speed: speed * (1.0 - turn_factor * 0.5),
-            right_speed: speed * (1.0 + turn_factor * 0.5),
-        }
+        let hottest = status.motor_temps.iter().copied().fold(f32::MIN, f32::max);
+        let (level, message) = if hottest >= MOTOR_TEMP_ERROR {
+            (DiagnosticLevel::Error, format!("Motor overheating at {:.1}°C", hottest))
+        } else if hottest >= MOTOR_TEMP_WARN {
+            (DiagnosticLevel::Warn, format!("Motor hot at {:.1}°C", hottest))
+        } else {
+            (DiagnosticLevel::Ok, "Motors within temperature limits".to_string())
+        };
+        let driver = status.motor_temps.iter().enumerate()
+            .fold(DiagnosticStatus::new("motors/driver", level, message), |driver, (i, temp)| {
+                driver.with(&format!("motor_{}_temp", i), format!("{:.1}", temp))
+            });
+        self.diagnostics.report(driver).await;
     }
 
     fn generate_hardware_status(&self, counter: u64) -> HardwareStatus {
@@ -174,3 +181,44 @@ impl HardwareInterface {
         }
     }
 }
+
+// Differential drive mapping from a behavior to wheel speeds. Shared with the
+// Safety Controller so it can estimate the motion a command will produce.
+pub fn motor_command_for(behavior: &Behavior) -> Option<MotorCommand> {
+    match behavior {
+        Behavior::MoveTowards { target, speed } => {
+            // Simplified differential drive calculation
+            let angle_to_target = target[1].atan2(target[0]);
+            let turn_factor = angle_to_target.sin();
+
+            Some(MotorCommand {
+                left_speed: speed * (1.0 - turn_factor * 0.5),
+                right_speed: speed * (1.0 + turn_factor * 0.5),
+            })
+        }
+        Behavior::AvoidObstacle { direction } => {
+            Some(MotorCommand {
+                left_speed: direction[1] * 0.5,
+                right_speed: -direction[1] * 0.5,
+            })
+        }
+        Behavior::Drive { linear, angular } => {
+            Some(MotorCommand {
+                left_speed: (linear - angular).clamp(-1.0, 1.0),
+                right_speed: (linear + angular).clamp(-1.0, 1.0),
+            })
+        }
+        Behavior::Idle | Behavior::AdjustStance(_) | Behavior::EmergencyStop => None,
+    }
+}
+
+fn behavior_name(behavior: &Behavior) -> &'static str {
+    match behavior {
+        Behavior::Idle => "Idle",
+        Behavior::MoveTowards { .. } => "MoveTowards",
+        Behavior::AvoidObstacle { .. } => "AvoidObstacle",
+        Behavior::Drive { .. } => "Drive",
+        Behavior::AdjustStance(_) => "AdjustStance",
+        Behavior::EmergencyStop => "EmergencyStop",
+    }
+}
@@ -2,3 +2,5 @@ pub mod output_manager;
 pub mod hardware_interface;
 pub mod communication_module;
 pub mod user_feedback;
+pub mod operator_link;
+pub mod telemetry;
@@ -0,0 +1,451 @@
+use crate::types::{OperatorMessage, RoverMessage, CommandRequest, CommandId, CommandAck, AckStatus, TelemetryCommand, UserFeedback, FeedbackType, LogLevel};
+use crate::infra::logger::create_log;
+use crate::infra::log_levels::LogSender;
+use crate::infra::bus::Publisher;
+use crate::output::telemetry::{TelemetryConfig, TelemetryScheduler};
+use std::net::SocketAddr;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::sync::{broadcast, mpsc};
+use tokio::time::{Duration, Instant, interval};
+
+// Largest frame accepted from an operator, a JSON line or a binary frame
+const MAX_FRAME_BYTES: u32 = 1024 * 1024;
+// How often rate-limited telemetry is checked for messages that have become due
+const TELEMETRY_TICK_MS: u64 = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Encoding {
+    Json,        // One JSON message per line
+    MessagePack, // Each message prefixed with its length as a big-endian u32
+}
+
+impl Encoding {
+    pub fn encode(self, message: &RoverMessage) -> Result<Vec<u8>, String> {
+        match self {
+            Encoding::Json => {
+                let mut bytes = serde_json::to_vec(message).map_err(|e| e.to_string())?;
+                bytes.push(b'\n');
+                Ok(bytes)
+            }
+            Encoding::MessagePack => {
+                let payload = rmp_serde::to_vec_named(message).map_err(|e| e.to_string())?;
+                let mut bytes = (payload.len() as u32).to_be_bytes().to_vec();
+                bytes.extend(payload);
+                Ok(bytes)
+            }
+        }
+    }
+
+    pub fn decode(self, frame: &[u8]) -> Result<OperatorMessage, String> {
+        match self {
+            Encoding::Json => serde_json::from_slice(frame).map_err(|e| e.to_string()),
+            Encoding::MessagePack => rmp_serde::from_slice(frame).map_err(|e| e.to_string()),
+        }
+    }
+}
+
+// Serves one operator connection until it closes or the system shuts down.
+// Commands are published on `command_tx`, and messages on `outbound_rx` go to the
+// operator as its telemetry scheduler allows.
+#[allow(clippy::too_many_arguments)]
+pub async fn serve_connection(
+    stream: TcpStream,
+    peer: SocketAddr,
+    encoding: Encoding,
+    command_tx: Publisher<CommandRequest>,
+    outbound_rx: broadcast::Receiver<RoverMessage>,
+    telemetry: TelemetryConfig,
+    log_tx: LogSender,
+    mut shutdown_rx: broadcast::Receiver<()>,
+) {
+    let _ = log_tx.send(create_log(
+        "CommunicationModule",
+        LogLevel::Info,
+        format!("Operator connected from {} ({:?})", peer, encoding)
+    )).await;
+
+    let (reader, writer) = stream.into_split();
+    let (reply_tx, reply_rx) = mpsc::channel(32);
+    let (telemetry_tx, telemetry_rx) = mpsc::channel(32);
+    let mut scheduler = TelemetryScheduler::new(telemetry);
+
+    tokio::select! {
+        _ = shutdown_rx.recv() => {}
+        _ = read_messages(reader, encoding, command_tx, telemetry_tx, reply_tx, &log_tx) => {}
+        _ = write_messages(writer, encoding, outbound_rx, reply_rx, telemetry_rx, &mut scheduler, &log_tx) => {}
+    }
+
+    let stats = scheduler.stats();
+    let _ = log_tx.send(create_log(
+        "CommunicationModule",
+        LogLevel::Info,
+        format!("Operator {} disconnected - sent {} messages, coalesced {}, dropped {}",
+            peer, stats.sent, stats.coalesced, stats.dropped)
+    )).await;
+}
+
+async fn read_messages(
+    reader: OwnedReadHalf,
+    encoding: Encoding,
+    command_tx: Publisher<CommandRequest>,
+    telemetry_tx: mpsc::Sender<(CommandId, TelemetryCommand)>,
+    reply_tx: mpsc::Sender<RoverMessage>,
+    log_tx: &LogSender,
+) {
+    let mut reader = BufReader::new(reader);
+
+    loop {
+        let frame = match read_frame(&mut reader, encoding).await {
+            Ok(Some(frame)) => frame,
+            Ok(None) => return,
+            Err(e) => {
+                let _ = log_tx.send(create_log(
+                    "CommunicationModule",
+                    LogLevel::Warn,
+                    format!("Closing operator connection: {}", e)
+                )).await;
+                return;
+            }
+        };
+        if encoding == Encoding::Json && frame.iter().all(u8::is_ascii_whitespace) {
+            continue;
+        }
+
+        match encoding.decode(&frame) {
+            Ok(OperatorMessage::Command(request)) => {
+                command_tx.publish(request).await;
+            }
+            Ok(OperatorMessage::Telemetry { id, command }) => {
+                if telemetry_tx.send((id, command)).await.is_err() {
+                    return;
+                }
+            }
+            // Log levels are shared, so a change applies to every operator
+            Ok(OperatorMessage::Logging { id, command }) => {
+                let change = log_tx.levels().apply(command);
+                let _ = log_tx.send(create_log(
+                    "CommunicationModule",
+                    LogLevel::Info,
+                    format!("Operator logging: {}", change)
+                )).await;
+                let _ = reply_tx.send(RoverMessage::Ack(CommandAck::new("CommunicationModule", id, AckStatus::Completed))).await;
+            }
+            Err(e) => {
+                let _ = log_tx.send(create_log(
+                    "CommunicationModule",
+                    LogLevel::Warn,
+                    format!("Invalid operator message: {}", e)
+                )).await;
+                let reply = RoverMessage::Feedback(UserFeedback {
+                    message: format!("Invalid message: {}", e),
+                    feedback_type: FeedbackType::Error,
+                });
+                let _ = reply_tx.send(reply).await;
+            }
+        }
+    }
+}
+
+// Reads one message, or None once the operator has closed the connection
+async fn read_frame(reader: &mut BufReader<OwnedReadHalf>, encoding: Encoding) -> Result<Option<Vec<u8>>, String> {
+    match encoding {
+        Encoding::Json => {
+            let mut line = Vec::new();
+            let read = (&mut *reader).take(MAX_FRAME_BYTES as u64 + 1)
+                .read_until(b'\n', &mut line).await
+                .map_err(|e| e.to_string())?;
+            if line.len() > MAX_FRAME_BYTES as usize && !line.ends_with(b"\n") {
+                return Err(format!("line exceeds {} bytes", MAX_FRAME_BYTES));
+            }
+            Ok((read > 0).then_some(line))
+        }
+        Encoding::MessagePack => {
+            let mut length = [0u8; 4];
+            match reader.read_exact(&mut length).await {
+                Ok(_) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+                Err(e) => return Err(e.to_string()),
+            }
+
+            let length = u32::from_be_bytes(length);
+            if length > MAX_FRAME_BYTES {
+                return Err(format!("frame of {} bytes exceeds {} bytes", length, MAX_FRAME_BYTES));
+            }
+            let mut frame = vec![0u8; length as usize];
+            reader.read_exact(&mut frame).await.map_err(|e| e.to_string())?;
+            Ok(Some(frame))
+        }
+    }
+}
+
+async fn write_messages(
+    mut writer: OwnedWriteHalf,
+    encoding: Encoding,
+    mut outbound_rx: broadcast::Receiver<RoverMessage>,
+    mut reply_rx: mpsc::Receiver<RoverMessage>,
+    mut telemetry_rx: mpsc::Receiver<(CommandId, TelemetryCommand)>,
+    scheduler: &mut TelemetryScheduler,
+    log_tx: &LogSender,
+) {
+    let mut tick = interval(Duration::from_millis(TELEMETRY_TICK_MS));
+
+    loop {
+        tokio::select! {
+            // Replies concern this operator alone and skip the scheduler
+            Some(reply) = reply_rx.recv() => {
+                if write_message(&mut writer, encoding, &reply, log_tx).await.is_none() {
+                    return;
+                }
+            }
+            result = outbound_rx.recv() => match result {
+                Ok(message) => scheduler.push(message),
+                // A slow operator misses messages rather than holding up the rover
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    let _ = log_tx.send(create_log(
+                        "CommunicationModule",
+                        LogLevel::Warn,
+                        format!("Operator too slow, skipped {} messages", skipped)
+                    )).await;
+                }
+                Err(broadcast::error::RecvError::Closed) => return,
+            },
+            Some((id, command)) = telemetry_rx.recv() => {
+                let status = match scheduler.apply(command) {
+                    Ok(change) => {
+                        let _ = log_tx.send(create_log(
+                            "CommunicationModule",
+                            LogLevel::Info,
+                            format!("Operator telemetry: {}", change)
+                        )).await;
+                        AckStatus::Completed
+                    }
+                    Err(reason) => AckStatus::Rejected(reason),
+                };
+                scheduler.push(RoverMessage::Ack(CommandAck::new("CommunicationModule", id, status)));
+            }
+            _ = tick.tick() => {}
+        }
+
+        while let Some(message) = scheduler.pop(Instant::now()) {
+            match write_message(&mut writer, encoding, &message, log_tx).await {
+                Some(bytes) => scheduler.spend(bytes),
+                None => return,
+            }
+        }
+    }
+}
+
+// Writes one message, returning its size, or None once the connection is
+// gone. Messages that cannot be encoded are logged and skipped.
+async fn write_message(
+    writer: &mut OwnedWriteHalf,
+    encoding: Encoding,
+    message: &RoverMessage,
+    log_tx: &LogSender,
+) -> Option<usize> {
+    let bytes = match encoding.encode(message) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            let _ = log_tx.send(create_log(
+                "CommunicationModule",
+                LogLevel::Error,
+                format!("Cannot encode operator message: {}", e)
+            )).await;
+            return Some(0);
+        }
+    };
+    writer.write_all(&bytes).await.ok()?;
+    Some(bytes.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infra::bus::{Bus, QueuePolicy, Subscriber, topics};
+    use crate::infra::log_levels::{LogConfig, LogLevels};
+    use crate::types::{LogEntry, SystemCommand, UserCommand};
+    use tokio::net::TcpListener;
+    use tokio::time::timeout;
+
+    const ENCODINGS: [Encoding; 2] = [Encoding::Json, Encoding::MessagePack];
+
+    // An operator connection served on a loopback port, with the ends the
+    // rover side would hold
+    struct Link {
+        client: TcpStream,
+        command_rx: Subscriber<CommandRequest>,
+        outbound_tx: broadcast::Sender<RoverMessage>,
+        _shutdown_tx: broadcast::Sender<()>,
+        _log_rx: mpsc::Receiver<LogEntry>,
+    }
+
+    async fn connect(encoding: Encoding) -> Link {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let bus = Bus::new();
+        let command_tx = bus.publisher(topics::OPERATOR_COMMANDS);
+        let command_rx = bus.subscribe(topics::OPERATOR_COMMANDS, "Test", QueuePolicy::Lossless(32));
+        let (outbound_tx, outbound_rx) = broadcast::channel(32);
+        let (shutdown_tx, shutdown_rx) = broadcast::channel(1);
+        let (log_tx, log_rx) = LogSender::channel(256, LogLevels::new(&LogConfig::default()));
+
+        tokio::spawn(async move {
+            let (stream, peer) = listener.accept().await.unwrap();
+            serve_connection(stream, peer, encoding, command_tx, outbound_rx, TelemetryConfig::default(), log_tx, shutdown_rx).await;
+        });
+
+        Link {
+            client: TcpStream::connect(address).await.unwrap(),
+            command_rx,
+            outbound_tx,
+            _shutdown_tx: shutdown_tx,
+            _log_rx: log_rx,
+        }
+    }
+
+    // Frames a message as an operator would send it
+    fn frame(encoding: Encoding, message: &OperatorMessage) -> Vec<u8> {
+        match encoding {
+            Encoding::Json => {
+                let mut bytes = serde_json::to_vec(message).unwrap();
+                bytes.push(b'\n');
+                bytes
+            }
+            Encoding::MessagePack => {
+                let payload = rmp_serde::to_vec_named(message).unwrap();
+                let mut bytes = (payload.len() as u32).to_be_bytes().to_vec();
+                bytes.extend(payload);
+                bytes
+            }
+        }
+    }
+
+    async fn receive(client: &mut TcpStream, encoding: Encoding) -> RoverMessage {
+        let read = async {
+            match encoding {
+                Encoding::Json => {
+                    let mut line = Vec::new();
+                    let mut byte = [0u8; 1];
+                    while byte[0] != b'\n' {
+                        client.read_exact(&mut byte).await.unwrap();
+                        line.push(byte[0]);
+                    }
+                    serde_json::from_slice(&line).unwrap()
+                }
+                Encoding::MessagePack => {
+                    let mut length = [0u8; 4];
+                    client.read_exact(&mut length).await.unwrap();
+                    let mut payload = vec![0u8; u32::from_be_bytes(length) as usize];
+                    client.read_exact(&mut payload).await.unwrap();
+                    rmp_serde::from_slice(&payload).unwrap()
+                }
+            }
+        };
+        timeout(Duration::from_secs(2), read).await.expect("no message from the rover")
+    }
+
+    fn pause(id: CommandId) -> OperatorMessage {
+        OperatorMessage::Command(CommandRequest { id, command: UserCommand::SystemCommand(SystemCommand::Pause) })
+    }
+
+    fn feedback(message: &str) -> RoverMessage {
+        RoverMessage::Feedback(UserFeedback { message: message.to_string(), feedback_type: FeedbackType::Status })
+    }
+
+    #[tokio::test]
+    async fn commands_reach_the_rover() {
+        for encoding in ENCODINGS {
+            let mut link = connect(encoding).await;
+            link.client.write_all(&frame(encoding, &pause(7))).await.unwrap();
+
+            let request = timeout(Duration::from_secs(2), link.command_rx.recv()).await.unwrap().unwrap();
+            assert_eq!(request.id, 7, "{:?}", encoding);
+            assert!(matches!(request.command, UserCommand::SystemCommand(SystemCommand::Pause)), "{:?}", encoding);
+        }
+    }
+
+    #[tokio::test]
+    async fn broadcasts_reach_the_operator() {
+        for encoding in ENCODINGS {
+            let mut link = connect(encoding).await;
+            link.outbound_tx.send(feedback("Hello operator")).unwrap();
+            match receive(&mut link.client, encoding).await {
+                RoverMessage::Feedback(feedback) => assert_eq!(feedback.message, "Hello operator", "{:?}", encoding),
+                message => panic!("{:?}: expected feedback, got {:?}", encoding, message),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn malformed_frames_are_answered_with_an_error() {
+        for encoding in ENCODINGS {
+            let mut link = connect(encoding).await;
+            let garbage = match encoding {
+                Encoding::Json => b"{not json\n".to_vec(),
+                Encoding::MessagePack => vec![0, 0, 0, 2, 0xc1, 0xc1],
+            };
+            link.client.write_all(&garbage).await.unwrap();
+
+            match receive(&mut link.client, encoding).await {
+                RoverMessage::Feedback(UserFeedback { feedback_type: FeedbackType::Error, message }) => {
+                    assert!(message.starts_with("Invalid message"), "{:?}: {}", encoding, message);
+                }
+                message => panic!("{:?}: expected an error, got {:?}", encoding, message),
+            }
+
+            // The connection stays usable
+            link.client.write_all(&frame(encoding, &pause(2))).await.unwrap();
+            let request = timeout(Duration::from_secs(2), link.command_rx.recv()).await.unwrap().unwrap();
+            assert_eq!(request.id, 2, "{:?}", encoding);
+        }
+    }
+
+    #[tokio::test]
+    async fn overlong_json_lines_close_the_connection() {
+        let mut link = connect(Encoding::Json).await;
+        // Writing may fail once the rover has closed its end
+        let _ = link.client.write_all(&vec![b' '; MAX_FRAME_BYTES as usize + 2]).await;
+
+        let mut buffer = [0u8; 64];
+        let read = timeout(Duration::from_secs(2), link.client.read(&mut buffer)).await.expect("connection left open");
+        assert!(matches!(read, Ok(0) | Err(_)), "{:?}", read);
+        assert!(link.command_rx.recv().await.is_none());
+    }
+
+    #[test]
+    fn encoding_round_trips() {
+        for encoding in ENCODINGS {
+            let bytes = encoding.encode(&feedback("Round trip")).unwrap();
+            let decoded: RoverMessage = match encoding {
+                Encoding::Json => {
+                    assert_eq!(bytes.last(), Some(&b'\n'));
+                    serde_json::from_slice(&bytes).unwrap()
+                }
+                Encoding::MessagePack => {
+                    assert_eq!(u32::from_be_bytes(bytes[..4].try_into().unwrap()) as usize, bytes.len() - 4);
+                    rmp_serde::from_slice(&bytes[4..]).unwrap()
+                }
+            };
+            assert!(matches!(decoded, RoverMessage::Feedback(feedback) if feedback.message == "Round trip"), "{:?}", encoding);
+
+            let framed = frame(encoding, &pause(9));
+            let payload = match encoding {
+                Encoding::Json => &framed[..],
+                Encoding::MessagePack => &framed[4..],
+            };
+            match encoding.decode(payload).unwrap() {
+                OperatorMessage::Command(request) => assert_eq!(request.id, 9, "{:?}", encoding),
+                message => panic!("{:?}: expected a command, got {:?}", encoding, message),
+            }
+        }
+    }
+
+    #[test]
+    fn malformed_frames_do_not_decode() {
+        assert!(Encoding::Json.decode(b"{\"Command\": 3}").is_err());
+        assert!(Encoding::MessagePack.decode(&[0xc1]).is_err());
+        assert!(Encoding::MessagePack.decode(&[]).is_err());
+    }
+}
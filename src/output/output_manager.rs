@@ -1,33 +1,86 @@
-use crate::types::{MotorCommand, StatusUpdate, RobotState, RobotPose, LogEntry, LogLevel};
+use crate::types::{StatusUpdate, RobotState, PositionEstimate, EnergyEstimate, MissionProgress, TaskStatus, HardwareStatus, HealthStatus, LogLevel};
 use crate::infra::logger::create_log;
-use tokio::sync::{broadcast, mpsc};
+use crate::infra::log_levels::LogSender;
+use crate::infra::bus::{Publisher, Subscriber};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast, watch};
+use tokio::time::{Duration, interval};
 use std::time::SystemTime;
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StatusConfig {
+    pub interval_ms: u64,  // Time between periodic status updates
+    pub battery_step: f32, // Battery fraction that counts as a significant change
+}
+
+impl Default for StatusConfig {
+    fn default() -> Self {
+        Self {
+            interval_ms: 1000,
+            battery_step: 0.05,
+        }
+    }
+}
+
+// Parts of a status update whose change is worth publishing at once
+#[derive(Debug, Clone, PartialEq)]
+struct Significant {
+    state: RobotState,
+    mission: Option<(u64, usize, u32, TaskStatus)>, // Mission, task index, loop and task status
+    health: Option<HealthStatus>,
+    battery_band: Option<u32>, // Battery level in steps of `battery_step`
+}
+
 pub struct OutputManager {
-    motor_rx: mpsc::Receiver<MotorCommand>,
-    hardware_tx: mpsc::Sender<MotorCommand>,
-    feedback_tx: mpsc::Sender<StatusUpdate>,
-    comm_tx: mpsc::Sender<StatusUpdate>,
-    log_tx: mpsc::Sender<LogEntry>,
+    // Inputs
+    state_rx: Subscriber<RobotState>,
+    position_rx: watch::Receiver<Option<PositionEstimate>>,
+    energy_rx: watch::Receiver<Option<EnergyEstimate>>,
+    mission_rx: watch::Receiver<Option<MissionProgress>>,
+    hw_status_rx: Subscriber<HardwareStatus>,
+
+    // Outputs
+    feedback_tx: Publisher<StatusUpdate>,
+    comm_tx: Publisher<StatusUpdate>,
+
+    log_tx: LogSender,
     shutdown_rx: broadcast::Receiver<()>,
+    config: StatusConfig,
+
+    state: RobotState,
+    health: Option<HealthStatus>,
+    last_significant: Option<Significant>,
 }
 
 impl OutputManager {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
-        motor_rx: mpsc::Receiver<MotorCommand>,
-        hardware_tx: mpsc::Sender<MotorCommand>,
-        feedback_tx: mpsc::Sender<StatusUpdate>,
-        comm_tx: mpsc::Sender<StatusUpdate>,
-        log_tx: mpsc::Sender<LogEntry>,
+        state_rx: Subscriber<RobotState>,
+        position_rx: watch::Receiver<Option<PositionEstimate>>,
+        energy_rx: watch::Receiver<Option<EnergyEstimate>>,
+        mission_rx: watch::Receiver<Option<MissionProgress>>,
+        hw_status_rx: Subscriber<HardwareStatus>,
+        feedback_tx: Publisher<StatusUpdate>,
+        comm_tx: Publisher<StatusUpdate>,
+        log_tx: LogSender,
         shutdown_rx: broadcast::Receiver<()>,
+        config: StatusConfig,
     ) -> Self {
         Self {
-            motor_rx,
-            hardware_tx,
+            state_rx,
+            position_rx,
+            energy_rx,
+            mission_rx,
+            hw_status_rx,
             feedback_tx,
             comm_tx,
             log_tx,
             shutdown_rx,
+            config,
+            state: RobotState::Idle,
+            health: None,
+            last_significant: None,
         }
     }
 
@@ -38,7 +91,7 @@ impl OutputManager {
             "Starting output manager".to_string()
         )).await;
 
-        let mut command_count = 0;
+        let mut periodic = interval(Duration::from_millis(self.config.interval_ms.max(1)));
 
         loop {
             tokio::select! {
@@ -50,22 +103,23 @@ impl OutputManager {
                     )).await;
                     break;
                 }
-                Some(motor_cmd) = self.motor_rx.recv() => {
-                    // Forward to hardware interface
-                    if let Err(_) = self.hardware_tx.send(motor_cmd.clone()).await {
-                        let _ = self.log_tx.send(create_log(
-                            "OutputManager",
-                            LogLevel::Error,
-                            "Failed to send motor command to hardware".to_string()
-                        )).await;
-                    }
-
-                    command_count += 1;
-
-                    // Periodically send status updates
-                    if command_count % 5 == 0 {
-                        self.send_status_update().await;
-                    }
+                Some(state) = self.state_rx.recv() => {
+                    self.state = state;
+                    self.publish_if_changed().await;
+                }
+                Some(status) = self.hw_status_rx.recv() => {
+                    self.health = Some(status.health);
+                    self.publish_if_changed().await;
+                }
+                Ok(()) = self.mission_rx.changed() => {
+                    self.publish_if_changed().await;
+                }
+                Ok(()) = self.energy_rx.changed() => {
+                    self.publish_if_changed().await;
+                }
+                _ = periodic.tick() => {
+                    let status = self.status();
+                    self.comm_tx.publish(status).await;
                 }
             }
         }
@@ -77,21 +131,41 @@ impl OutputManager {
         )).await;
     }
 
-    async fn send_status_update(&mut self) {
-        let status = StatusUpdate {
+    // Snapshot of the latest state, pose, mission progress, battery and health
+    fn status(&self) -> StatusUpdate {
+        StatusUpdate {
             timestamp: SystemTime::now(),
-            state: RobotState::ExecutingMission,
-            pose: RobotPose {
-                position: [0.0, 0.0, 0.0],
-                orientation: [1.0, 0.0, 0.0, 0.0],
-                velocity: [0.5, 0.0, 0.0],
-                angular_velocity: [0.0, 0.0, 0.0],
-            },
-            current_mission: Some("Patrol Mission".to_string()),
-            battery_level: 0.75,
-        };
-
-        let _ = self.feedback_tx.send(status.clone()).await;
-        let _ = self.comm_tx.send(status).await;
+            state: self.state.clone(),
+            position: self.position_rx.borrow().clone(),
+            mission: self.mission_rx.borrow().clone(),
+            battery_level: self.energy_rx.borrow().as_ref().map(|estimate| estimate.battery_level),
+            health: self.health.clone(),
+        }
+    }
+
+    // Publishes at once when the state, mission progress, health or battery
+    // band changed; everything else waits for the next periodic update
+    async fn publish_if_changed(&mut self) {
+        let status = self.status();
+        let significant = self.significant(&status);
+        if self.last_significant.as_ref() == Some(&significant) {
+            return;
+        }
+        self.last_significant = Some(significant);
+
+        self.feedback_tx.publish(status.clone()).await;
+        self.comm_tx.publish(status).await;
+    }
+
+    fn significant(&self, status: &StatusUpdate) -> Significant {
+        let step = self.config.battery_step.max(0.01);
+        Significant {
+            state: status.state.clone(),
+            mission: status.mission.as_ref().map(|progress| {
+                (progress.mission_id, progress.task_index, progress.loop_number, progress.status.clone())
+            }),
+            health: status.health.clone(),
+            battery_band: status.battery_level.map(|level| (level / step).floor() as u32),
+        }
     }
 }
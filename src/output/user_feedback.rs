@@ -1,23 +1,28 @@
-use crate::types::{StatusUpdate, UserFeedback, FeedbackType, LogEntry, LogLevel};
+use crate::types::{StatusUpdate, HealthStatus, MissionEvent, MissionProgress, TaskStatus, UserFeedback, FeedbackType, LogLevel};
 use crate::infra::logger::create_log;
-use tokio::sync::{broadcast, mpsc};
+use crate::infra::log_levels::LogSender;
+use crate::infra::bus::{Publisher, Subscriber};
+use tokio::sync::broadcast;
 
 pub struct UserFeedbackModule {
-    status_rx: mpsc::Receiver<StatusUpdate>,
-    comm_tx: mpsc::Sender<UserFeedback>,
-    log_tx: mpsc::Sender<LogEntry>,
+    status_rx: Subscriber<StatusUpdate>,
+    event_rx: Subscriber<MissionEvent>,
+    comm_tx: Publisher<UserFeedback>,
+    log_tx: LogSender,
     shutdown_rx: broadcast::Receiver<()>,
 }
 
 impl UserFeedbackModule {
     pub fn new(
-        status_rx: mpsc::Receiver<StatusUpdate>,
-        comm_tx: mpsc::Sender<UserFeedback>,
-        log_tx: mpsc::Sender<LogEntry>,
+        status_rx: Subscriber<StatusUpdate>,
+        event_rx: Subscriber<MissionEvent>,
+        comm_tx: Publisher<UserFeedback>,
+        log_tx: LogSender,
         shutdown_rx: broadcast::Receiver<()>,
     ) -> Self {
         Self {
             status_rx,
+            event_rx,
             comm_tx,
             log_tx,
             shutdown_rx,
@@ -45,6 +50,15 @@ impl UserFeedbackModule {
                     self.display_status(&status).await;
                     self.forward_to_comm(&status).await;
                 }
+                Some(event) = self.event_rx.recv() => match event {
+                    MissionEvent::Progress(progress) => self.report_progress(&progress).await,
+                    MissionEvent::ResumeOffer { mission_name, task_index, task_count, queued, .. } => {
+                        self.offer_resume(&mission_name, task_index, task_count, queued).await;
+                    }
+                    MissionEvent::FaultCleared { mission_id, mission_name, task_index, task_count } => {
+                        self.offer_fault_resume(mission_id, &mission_name, task_index, task_count).await;
+                    }
+                },
             }
         }
 
@@ -56,32 +70,118 @@ impl UserFeedbackModule {
     }
 
     async fn display_status(&mut self, status: &StatusUpdate) {
-        let mission_str = status.current_mission.as_ref()
-            .map(|m| m.as_str())
-            .unwrap_or("None");
+        let mission_str = match &status.mission {
+            Some(progress) => format!("{} (task {}/{})", progress.mission_name, progress.task_index + 1, progress.task_count),
+            None => "None".to_string(),
+        };
+        let health_str = match &status.health {
+            Some(HealthStatus::Healthy) => "Healthy".to_string(),
+            Some(HealthStatus::Warning(msg)) => format!("Warning ({})", msg),
+            Some(HealthStatus::Critical(msg)) => format!("Critical ({})", msg),
+            None => "Unknown".to_string(),
+        };
 
         let _ = self.log_tx.send(create_log(
             "UserFeedback",
             LogLevel::Info,
             format!(
-                "Status: {:?} | Mission: {} | Battery: {:.0}%",
+                "Status: {:?} | Mission: {} | Battery: {} | Health: {}",
                 status.state,
                 mission_str,
-                status.battery_level * 100.0
+                battery_str(status),
+                health_str
             )
         )).await;
     }
 
+    async fn report_progress(&mut self, progress: &MissionProgress) {
+        let (status, feedback_type) = match &progress.status {
+            TaskStatus::Pending => ("pending".to_string(), FeedbackType::Status),
+            TaskStatus::InProgress => match progress.distance_to_target {
+                Some(distance) => (format!("in progress, {:.1}m to go", distance), FeedbackType::Status),
+                None => ("in progress".to_string(), FeedbackType::Status),
+            },
+            TaskStatus::Completed => ("completed".to_string(), FeedbackType::Success),
+            TaskStatus::Failed(reason) => (format!("failed: {}", reason), FeedbackType::Error),
+        };
+
+        let pass = match progress.loop_count {
+            1 => String::new(),
+            0 => format!(", loop {}", progress.loop_number),
+            count => format!(", loop {}/{}", progress.loop_number, count),
+        };
+
+        let message = format!(
+            "Mission {} - task {}/{}{} ({}) {}",
+            progress.mission_name,
+            progress.task_index + 1,
+            progress.task_count,
+            pass,
+            progress.task_description,
+            status
+        );
+
+        let level = match feedback_type {
+            FeedbackType::Error => LogLevel::Warn,
+            _ => LogLevel::Info,
+        };
+        let _ = self.log_tx.send(create_log(
+            "UserFeedback",
+            level,
+            message.clone()
+        )).await;
+
+        self.comm_tx.publish(UserFeedback { message, feedback_type }).await;
+    }
+
+    async fn offer_resume(&mut self, mission_name: &str, task_index: usize, task_count: usize, queued: usize) {
+        let mut message = format!("Resume mission {} at task {}/{}?", mission_name, task_index + 1, task_count);
+        if queued > 0 {
+            message.push_str(&format!(" {} more saved mission(s) would be queued.", queued));
+        }
+        message.push_str(" Send ResumeSaved to resume or DiscardSaved to discard.");
+
+        let _ = self.log_tx.send(create_log(
+            "UserFeedback",
+            LogLevel::Info,
+            message.clone()
+        )).await;
+
+        self.comm_tx.publish(UserFeedback { message, feedback_type: FeedbackType::Warning }).await;
+    }
+
+    async fn offer_fault_resume(&mut self, mission_id: u64, mission_name: &str, task_index: usize, task_count: usize) {
+        let message = format!(
+            "Faults cleared. Mission {} is suspended at task {}/{}. Send Resume to continue or Cancel({}) to abandon it.",
+            mission_name, task_index + 1, task_count, mission_id
+        );
+
+        let _ = self.log_tx.send(create_log(
+            "UserFeedback",
+            LogLevel::Info,
+            message.clone()
+        )).await;
+
+        self.comm_tx.publish(UserFeedback { message, feedback_type: FeedbackType::Warning }).await;
+    }
+
     async fn forward_to_comm(&mut self, status: &StatusUpdate) {
         let feedback = UserFeedback {
             message: format!(
-                "State: {:?}, Battery: {:.0}%",
+                "State: {:?}, Battery: {}",
                 status.state,
-                status.battery_level * 100.0
+                battery_str(status)
             ),
             feedback_type: FeedbackType::Status,
         };
 
-        let _ = self.comm_tx.send(feedback).await;
+        self.comm_tx.publish(feedback).await;
+    }
+}
+
+fn battery_str(status: &StatusUpdate) -> String {
+    match status.battery_level {
+        Some(level) => format!("{:.0}%", level * 100.0),
+        None => "unknown".to_string(),
     }
 }
@@ -1,26 +1,61 @@
-use crate::types::{SensorData, EnvironmentState, Obstacle, ObstacleType, TerrainType, LogEntry, LogLevel};
+use crate::types::{SensorData, EnvironmentState, PipelineStage, Obstacle, ObstacleType, ScanResult, TerrainType, LogLevel};
 use crate::infra::logger::create_log;
-use tokio::sync::{broadcast, mpsc};
+use crate::infra::log_levels::LogSender;
+use crate::infra::bus::{Publisher, Subscriber};
+use crate::infra::pipeline::Tracer;
+use crate::control::protective_field::SENSOR_DIRECTIONS;
+use crate::rlog;
+use crate::control::stability::roll_pitch;
+use crate::planning::geo::yaw;
+use std::f32::consts::TAU;
+use std::time::SystemTime;
+use tokio::sync::broadcast;
+use serde::Serialize;
+
+// Module name for rlog! entries
+const LOG_MODULE: &str = "EnvUnderstanding";
+
+// Heading sectors a scan must see before it is complete
+const SCAN_SECTORS: usize = 12;
+// Readings closer than this (meters) are reported as obstacles
+const OBSTACLE_RANGE: f32 = 1.5;
+
+#[derive(Debug, Clone, Serialize)]
+pub enum ScanRequest {
+    Start,
+    Cancel,
+}
 
 pub struct EnvironmentUnderstanding {
-    sensor_rx: mpsc::Receiver<SensorData>,
-    env_state_tx: mpsc::Sender<EnvironmentState>,
-    log_tx: mpsc::Sender<LogEntry>,
+    sensor_rx: Subscriber<SensorData>,
+    scan_rx: Subscriber<ScanRequest>,
+    env_state_pub: Publisher<EnvironmentState>,
+    scan_tx: Publisher<ScanResult>,
+    log_tx: LogSender,
     shutdown_rx: broadcast::Receiver<()>,
+    tracer: Tracer,
+    scan: Option<Vec<Option<f32>>>, // Closest distance per sector while a scan runs
 }
 
 impl EnvironmentUnderstanding {
     pub fn new(
-        sensor_rx: mpsc::Receiver<SensorData>,
-        env_state_tx: mpsc::Sender<EnvironmentState>,
-        log_tx: mpsc::Sender<LogEntry>,
+        sensor_rx: Subscriber<SensorData>,
+        scan_rx: Subscriber<ScanRequest>,
+        env_state_pub: Publisher<EnvironmentState>,
+        scan_tx: Publisher<ScanResult>,
+        log_tx: LogSender,
         shutdown_rx: broadcast::Receiver<()>,
+        tracer: Tracer,
     ) -> Self {
         Self {
             sensor_rx,
-            env_state_tx,
+            scan_rx,
+            env_state_pub,
+            scan_tx,
             log_tx,
             shutdown_rx,
+            tracer,
+            scan: None,
         }
     }
 
@@ -42,17 +77,36 @@ impl EnvironmentUnderstanding {
                     break;
                 }
                 Some(sensor_data) = self.sensor_rx.recv() => {
-                    let env_state = self.process_sensor_data(&sensor_data);
+                    let mut env_state = self.process_sensor_data(&sensor_data);
 
                     if !env_state.obstacles.is_empty() {
-                        let _ = self.log_tx.send(create_log(
-                            "EnvUnderstanding",
-                            LogLevel::Info,
-                            format!("Detected {} obstacles", env_state.obstacles.len())
-                        )).await;
+                        let count = env_state.obstacles.len();
+                        rlog!(self.log_tx, Info, "Detected {} obstacles", count, obstacles = count);
                     }
 
-                    let _ = self.env_state_tx.send(env_state).await;
+                    self.tracer.hop(&mut env_state.trace, PipelineStage::EnvUnderstanding);
+                    self.env_state_pub.publish(env_state).await;
+
+                    if self.scan.is_some() {
+                        self.accumulate_scan(&sensor_data).await;
+                    }
+                }
+                Some(request) = self.scan_rx.recv() => {
+                    let message = match request {
+                        ScanRequest::Start => {
+                            self.scan = Some(vec![None; SCAN_SECTORS]);
+                            "Starting 360° scan"
+                        }
+                        ScanRequest::Cancel => {
+                            self.scan = None;
+                            "Scan cancelled"
+                        }
+                    };
+                    let _ = self.log_tx.send(create_log(
+                        "EnvUnderstanding",
+                        LogLevel::Info,
+                        message.to_string()
+                    )).await;
                 }
             }
         }
@@ -64,14 +118,65 @@ impl EnvironmentUnderstanding {
         )).await;
     }
 
+    // Files each distance reading under the heading it was taken at. The scan
+    // completes once every sector has been seen.
+    async fn accumulate_scan(&mut self, sensor_data: &SensorData) {
+        let Some(sectors) = self.scan.as_mut() else {
+            return;
+        };
+
+        let heading = yaw(sensor_data.imu.orientation);
+        for (i, &distance) in sensor_data.distance_sensors.iter().enumerate() {
+            let Some(angle) = sensor_angle(i) else {
+                continue;
+            };
+            let angle = (heading + angle).rem_euclid(TAU);
+            let sector = ((angle / TAU * SCAN_SECTORS as f32) as usize).min(SCAN_SECTORS - 1);
+            let closest = sectors[sector].map_or(distance, |seen| seen.min(distance));
+            sectors[sector] = Some(closest);
+        }
+
+        let seen = sectors.iter().filter(|sector| sector.is_some()).count();
+        if seen < SCAN_SECTORS {
+            return;
+        }
+
+        let sector_ranges: Vec<f32> = sectors.iter().flatten().copied().collect();
+        self.scan = None;
+
+        // Report an obstacle at the middle of every sector with a close reading
+        let obstacles: Vec<Obstacle> = sector_ranges.iter().enumerate()
+            .filter(|&(_, &distance)| distance < OBSTACLE_RANGE)
+            .map(|(sector, &distance)| {
+                let angle = (sector as f32 + 0.5) / SCAN_SECTORS as f32 * TAU;
+                Obstacle {
+                    position: [distance * angle.cos(), distance * angle.sin(), 0.0],
+                    size: [0.3, 0.3, 0.5],
+                    obstacle_type: ObstacleType::Static,
+                }
+            })
+            .collect();
+
+        let _ = self.log_tx.send(create_log(
+            "EnvUnderstanding",
+            LogLevel::Info,
+            format!("Scan complete - {} sectors, {} obstacles", sector_ranges.len(), obstacles.len())
+        )).await;
+
+        self.scan_tx.publish(ScanResult {
+            timestamp: SystemTime::now(),
+            sector_ranges,
+            obstacles,
+        }).await;
+    }
+
     fn process_sensor_data(&self, sensor_data: &SensorData) -> EnvironmentState {
         let mut obstacles = Vec::new();
 
         // Convert distance sensor readings to obstacles
         for (i, &distance) in sensor_data.distance_sensors.iter().enumerate() {
-            if distance < 1.5 {
+            if distance < OBSTACLE_RANGE && let Some(angle) = sensor_angle(i) {
                 // Close obstacle detected
-                let angle = (i as f32) * std::f32::consts::PI / 2.0;
                 let x = distance * angle.cos();
                 let y = distance * angle.sin();
 
@@ -90,9 +195,11 @@ impl EnvironmentUnderstanding {
             sensor_data.imu.acceleration[2].powi(2)
         ).sqrt();
 
+        let (roll, pitch) = roll_pitch(sensor_data.imu.orientation);
+
         let terrain_type = if (accel_magnitude - 9.81).abs() > 2.0 {
             TerrainType::Rough
-        } else if sensor_data.imu.orientation[1].abs() > 0.2 {
+        } else if roll.abs() > 0.2 || pitch.abs() > 0.2 {
             TerrainType::Steep
         } else {
             TerrainType::Flat
@@ -102,6 +209,13 @@ impl EnvironmentUnderstanding {
             obstacles,
             terrain_type,
             confidence: 0.8,
+            trace: sensor_data.trace.clone(),
         }
     }
 }
+
+// Direction of a distance sensor relative to the robot's heading, in radians,
+// None for a sensor the layout does not know
+fn sensor_angle(index: usize) -> Option<f32> {
+    SENSOR_DIRECTIONS.get(index).map(|direction| direction.angle())
+}
@@ -1,17 +1,20 @@
-use crate::types::{CalibrationData, LogEntry, LogLevel};
+use crate::types::{CalibrationData, LogLevel};
 use crate::infra::logger::create_log;
-use tokio::sync::{broadcast, mpsc, RwLock};
+use crate::infra::log_levels::LogSender;
+use crate::infra::bus::{Publisher, Subscriber};
+use tokio::sync::{broadcast, RwLock};
 use std::sync::Arc;
+use serde::Serialize;
 
 pub struct ModelCalibrationStorage {
     calibration_data: Arc<RwLock<CalibrationData>>,
-    request_rx: mpsc::Receiver<CalibrationRequest>,
-    response_tx: mpsc::Sender<CalibrationData>,
-    log_tx: mpsc::Sender<LogEntry>,
+    request_rx: Subscriber<CalibrationRequest>,
+    response_tx: Publisher<CalibrationData>,
+    log_tx: LogSender,
     shutdown_rx: broadcast::Receiver<()>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum CalibrationRequest {
     Get,
     Update(CalibrationData),
@@ -19,9 +22,9 @@ pub enum CalibrationRequest {
 
 impl ModelCalibrationStorage {
     pub fn new(
-        request_rx: mpsc::Receiver<CalibrationRequest>,
-        response_tx: mpsc::Sender<CalibrationData>,
-        log_tx: mpsc::Sender<LogEntry>,
+        request_rx: Subscriber<CalibrationRequest>,
+        response_tx: Publisher<CalibrationData>,
+        log_tx: LogSender,
         shutdown_rx: broadcast::Receiver<()>,
     ) -> Self {
         let default_calibration = CalibrationData {
@@ -80,7 +83,7 @@ impl ModelCalibrationStorage {
         match request {
             CalibrationRequest::Get => {
                 let data = self.calibration_data.read().await.clone();
-                let _ = self.response_tx.send(data).await;
+                self.response_tx.publish(data).await;
             }
             CalibrationRequest::Update(new_data) => {
                 let mut data = self.calibration_data.write().await;
@@ -1,31 +1,37 @@
-use crate::types::{EnvironmentState, Path, RobotPose, StanceConfig, LogEntry, LogLevel};
+use crate::types::{EnvironmentState, Path, PipelineStage, RobotPose, StanceConfig, LogLevel};
 use crate::infra::logger::create_log;
+use crate::infra::log_levels::LogSender;
+use crate::infra::bus::{Publisher, Subscriber};
+use crate::infra::pipeline::Tracer;
 use crate::perception::stance::StanceRequest;
 use crate::planning::goal_planning::PathRequest;
-use tokio::sync::{broadcast, mpsc};
+use tokio::sync::broadcast;
 
 pub struct ObstacleAvoidance {
-    env_state_rx: mpsc::Receiver<EnvironmentState>,
-    stance_query_tx: mpsc::Sender<StanceRequest>,
-    stance_rx: mpsc::Receiver<StanceConfig>,
-    goal_path_rx: mpsc::Receiver<PathRequest>,
-    goal_path_tx: mpsc::Sender<Path>,
-    behavior_tx: mpsc::Sender<Path>,
-    log_tx: mpsc::Sender<LogEntry>,
+    env_state_rx: Subscriber<EnvironmentState>,
+    stance_query_tx: Publisher<StanceRequest>,
+    stance_rx: Subscriber<StanceConfig>,
+    goal_path_rx: Subscriber<PathRequest>,
+    goal_path_tx: Publisher<Path>,
+    behavior_tx: Publisher<Path>,
+    log_tx: LogSender,
     shutdown_rx: broadcast::Receiver<()>,
+    tracer: Tracer,
     current_env: Option<EnvironmentState>,
 }
 
 impl ObstacleAvoidance {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
-        env_state_rx: mpsc::Receiver<EnvironmentState>,
-        stance_query_tx: mpsc::Sender<StanceRequest>,
-        stance_rx: mpsc::Receiver<StanceConfig>,
-        goal_path_rx: mpsc::Receiver<PathRequest>,
-        goal_path_tx: mpsc::Sender<Path>,
-        behavior_tx: mpsc::Sender<Path>,
-        log_tx: mpsc::Sender<LogEntry>,
+        env_state_rx: Subscriber<EnvironmentState>,
+        stance_query_tx: Publisher<StanceRequest>,
+        stance_rx: Subscriber<StanceConfig>,
+        goal_path_rx: Subscriber<PathRequest>,
+        goal_path_tx: Publisher<Path>,
+        behavior_tx: Publisher<Path>,
+        log_tx: LogSender,
         shutdown_rx: broadcast::Receiver<()>,
+        tracer: Tracer,
     ) -> Self {
         Self {
             env_state_rx,
@@ -36,6 +42,7 @@ impl ObstacleAvoidance {
             behavior_tx,
             log_tx,
             shutdown_rx,
+            tracer,
             current_env: None,
         }
     }
@@ -84,7 +91,7 @@ impl ObstacleAvoidance {
 
     async fn validate_path(&mut self, request: PathRequest) {
         match request {
-            PathRequest::Plan { start, goal } => {
+            PathRequest::Plan { start, goal, via } => {
                 let _ = self.log_tx.send(create_log(
                     "ObstacleAvoidance",
                     LogLevel::Info,
@@ -92,37 +99,53 @@ impl ObstacleAvoidance {
                 )).await;
 
                 // Query stance for navigation constraints
-                let _ = self.stance_query_tx.send(StanceRequest::Query).await;
+                self.stance_query_tx.publish(StanceRequest::Query).await;
+
+                // Create a safe path (simplified - just interpolate each leg)
+                let mut corners = vec![start.position];
+                corners.extend(via.iter().map(|pose| pose.position));
+                corners.push(goal.position);
 
-                // Create a safe path (simplified - just interpolate)
                 let mut waypoints = Vec::new();
-                for i in 0..5 {
-                    let t = i as f32 / 4.0;
-                    let pos = [
-                        start.position[0] + t * (goal.position[0] - start.position[0]),
-                        start.position[1] + t * (goal.position[1] - start.position[1]),
-                        start.position[2] + t * (goal.position[2] - start.position[2]),
-                    ];
+                for (leg, pair) in corners.windows(2).enumerate() {
+                    let (from, to) = (pair[0], pair[1]);
+                    // Legs after the first skip their start, which ended the previous leg
+                    for i in (if leg == 0 { 0 } else { 1 })..5 {
+                        let t = i as f32 / 4.0;
+                        let pos = [
+                            from[0] + t * (to[0] - from[0]),
+                            from[1] + t * (to[1] - from[1]),
+                            from[2] + t * (to[2] - from[2]),
+                        ];
 
-                    waypoints.push(RobotPose {
-                        position: pos,
-                        orientation: start.orientation,
-                        velocity: [0.5, 0.0, 0.0],
-                        angular_velocity: [0.0, 0.0, 0.0],
-                    });
+                        waypoints.push(RobotPose {
+                            position: pos,
+                            orientation: start.orientation,
+                            velocity: [0.5, 0.0, 0.0],
+                            angular_velocity: [0.0, 0.0, 0.0],
+                        });
+                    }
                 }
 
+                // The path was checked against the latest environment
+                let trace = self.current_env.as_ref().map(|env| {
+                    let mut trace = env.trace.clone();
+                    self.tracer.hop(&mut trace, PipelineStage::ObstacleAvoidance);
+                    trace
+                });
+
                 let path = Path {
                     waypoints: waypoints.clone(),
                     total_distance: 5.0,
                     estimated_time: 10.0,
+                    trace,
                 };
 
                 // Send validated path back to goal planning
-                let _ = self.goal_path_tx.send(path.clone()).await;
+                self.goal_path_tx.publish(path.clone()).await;
 
                 // Also send directly to behavior for immediate avoidance
-                let _ = self.behavior_tx.send(path).await;
+                self.behavior_tx.publish(path).await;
             }
         }
     }
@@ -1,37 +1,44 @@
-use crate::types::{StanceConfig, StanceType, LogEntry, LogLevel};
+use crate::types::{StanceConfig, StanceType, LogLevel};
 use crate::infra::logger::create_log;
-use tokio::sync::{broadcast, mpsc};
+use crate::infra::log_levels::LogSender;
+use crate::infra::bus::{Publisher, Subscriber};
+use tokio::sync::broadcast;
+use serde::Serialize;
 
 pub struct Stance {
-    obstacle_rx: mpsc::Receiver<StanceRequest>,
-    goal_rx: mpsc::Receiver<StanceRequest>,
-    obstacle_tx: mpsc::Sender<StanceConfig>,
-    goal_tx: mpsc::Sender<StanceConfig>,
-    behavior_tx: mpsc::Sender<StanceConfig>,
-    log_tx: mpsc::Sender<LogEntry>,
+    obstacle_rx: Subscriber<StanceRequest>,
+    goal_rx: Subscriber<StanceRequest>,
+    safety_rx: Subscriber<StanceRequest>,
+    obstacle_tx: Publisher<StanceConfig>,
+    goal_tx: Publisher<StanceConfig>,
+    behavior_tx: Publisher<StanceConfig>,
+    log_tx: LogSender,
     shutdown_rx: broadcast::Receiver<()>,
     current_stance: StanceConfig,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum StanceRequest {
     Query,
     Adjust(StanceConfig),
 }
 
 impl Stance {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
-        obstacle_rx: mpsc::Receiver<StanceRequest>,
-        goal_rx: mpsc::Receiver<StanceRequest>,
-        obstacle_tx: mpsc::Sender<StanceConfig>,
-        goal_tx: mpsc::Sender<StanceConfig>,
-        behavior_tx: mpsc::Sender<StanceConfig>,
-        log_tx: mpsc::Sender<LogEntry>,
+        obstacle_rx: Subscriber<StanceRequest>,
+        goal_rx: Subscriber<StanceRequest>,
+        safety_rx: Subscriber<StanceRequest>,
+        obstacle_tx: Publisher<StanceConfig>,
+        goal_tx: Publisher<StanceConfig>,
+        behavior_tx: Publisher<StanceConfig>,
+        log_tx: LogSender,
         shutdown_rx: broadcast::Receiver<()>,
     ) -> Self {
         Self {
             obstacle_rx,
             goal_rx,
+            safety_rx,
             obstacle_tx,
             goal_tx,
             behavior_tx,
@@ -69,6 +76,12 @@ impl Stance {
                     let tx = self.goal_tx.clone();
                     self.handle_request(request, &tx).await;
                 }
+                Some(request) = self.safety_rx.recv() => {
+                    // Tilt compensation from the Safety Controller, which expects no reply
+                    if let StanceRequest::Adjust(new_stance) = request {
+                        self.apply_stance(new_stance).await;
+                    }
+                }
             }
         }
 
@@ -79,21 +92,25 @@ impl Stance {
         )).await;
     }
 
-    async fn handle_request(&mut self, request: StanceRequest, response_tx: &mpsc::Sender<StanceConfig>) {
+    async fn handle_request(&mut self, request: StanceRequest, response_tx: &Publisher<StanceConfig>) {
         match request {
             StanceRequest::Query => {
-                let _ = response_tx.send(self.current_stance.clone()).await;
+                response_tx.publish(self.current_stance.clone()).await;
             }
             StanceRequest::Adjust(new_stance) => {
-                let _ = self.log_tx.send(create_log(
-                    "Stance",
-                    LogLevel::Info,
-                    format!("Adjusting stance: {:?}", new_stance.stance_type)
-                )).await;
-
-                self.current_stance = new_stance.clone();
-                let _ = self.behavior_tx.send(new_stance).await;
+                self.apply_stance(new_stance).await;
             }
         }
     }
+
+    async fn apply_stance(&mut self, new_stance: StanceConfig) {
+        let _ = self.log_tx.send(create_log(
+            "Stance",
+            LogLevel::Info,
+            format!("Adjusting stance: {:?}", new_stance.stance_type)
+        )).await;
+
+        self.current_stance = new_stance.clone();
+        self.behavior_tx.publish(new_stance).await;
+    }
 }
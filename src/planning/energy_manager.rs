@@ -0,0 +1,167 @@
+use crate::types::{EnergyEstimate, SensorData, LogLevel};
+use crate::infra::logger::create_log;
+use crate::infra::log_levels::LogSender;
+use crate::infra::bus::Subscriber;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast, watch};
+use tokio::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EnergyConfig {
+    pub reserve: f32,                    // Battery fraction that must be left on arriving home
+    pub cruise_speed: f32,               // m/s, turns distances into driving time
+    pub margin: f32,                     // Multiplies every energy estimate
+    pub default_discharge_per_hour: f32, // Battery fraction per hour until a rate has been observed
+    pub rate_interval_s: u64,            // Seconds between discharge rate samples
+    pub rate_smoothing: f32,             // Weight of each new sample in the smoothed rate, 0.0 to 1.0
+}
+
+impl Default for EnergyConfig {
+    fn default() -> Self {
+        Self {
+            reserve: 0.15,
+            cruise_speed: 0.5,
+            margin: 1.2,
+            default_discharge_per_hour: 0.5,
+            rate_interval_s: 10,
+            rate_smoothing: 0.3,
+        }
+    }
+}
+
+impl EnergyEstimate {
+    // Battery fraction left before the reserve
+    pub fn usable(&self) -> f32 {
+        (self.battery_level - self.reserve).max(0.0)
+    }
+
+    // Battery fraction needed to drive `distance` meters and spend `seconds`
+    // waiting or scanning
+    pub fn charge_needed(&self, distance: f64, seconds: f64) -> f32 {
+        self.per_meter * distance as f32 + self.per_second * seconds as f32
+    }
+}
+
+// Lowest rate used for estimates, so ranges stay finite while the level is flat
+const MIN_DISCHARGE_RATE: f32 = 1e-6;
+
+pub struct EnergyManager {
+    sensor_rx: Subscriber<SensorData>,
+    energy_tx: watch::Sender<Option<EnergyEstimate>>,
+    log_tx: LogSender,
+    shutdown_rx: broadcast::Receiver<()>,
+    config: EnergyConfig,
+    discharge_rate: Option<f32>, // Smoothed battery fraction per second, once observed
+    last_sample: Option<(Instant, f32)>,
+}
+
+impl EnergyManager {
+    pub fn new(
+        sensor_rx: Subscriber<SensorData>,
+        energy_tx: watch::Sender<Option<EnergyEstimate>>,
+        log_tx: LogSender,
+        shutdown_rx: broadcast::Receiver<()>,
+        config: EnergyConfig,
+    ) -> Self {
+        Self {
+            sensor_rx,
+            energy_tx,
+            log_tx,
+            shutdown_rx,
+            config,
+            discharge_rate: None,
+            last_sample: None,
+        }
+    }
+
+    pub async fn run(mut self) {
+        let _ = self.log_tx.send(create_log(
+            "EnergyManager",
+            LogLevel::Info,
+            "Starting energy manager".to_string()
+        )).await;
+
+        loop {
+            tokio::select! {
+                _ = self.shutdown_rx.recv() => {
+                    let _ = self.log_tx.send(create_log(
+                        "EnergyManager",
+                        LogLevel::Info,
+                        "Shutdown signal received".to_string()
+                    )).await;
+                    break;
+                }
+                Some(sensor_data) = self.sensor_rx.recv() => {
+                    self.update_rate(sensor_data.battery_level).await;
+                    self.publish(&sensor_data);
+                }
+            }
+        }
+
+        let _ = self.log_tx.send(create_log(
+            "EnergyManager",
+            LogLevel::Info,
+            "Stopped".to_string()
+        )).await;
+    }
+
+    // Samples the battery level every `rate_interval_s` and smooths the
+    // discharge rate between samples. Rising levels mean charging and are
+    // not counted.
+    async fn update_rate(&mut self, level: f32) {
+        let now = Instant::now();
+        let Some((sampled_at, sampled_level)) = self.last_sample else {
+            self.last_sample = Some((now, level));
+            return;
+        };
+
+        let elapsed = now.duration_since(sampled_at);
+        if elapsed < Duration::from_secs(self.config.rate_interval_s) {
+            return;
+        }
+        self.last_sample = Some((now, level));
+
+        if level > sampled_level {
+            return;
+        }
+        let sample = (sampled_level - level) / elapsed.as_secs_f32();
+
+        let first = self.discharge_rate.is_none();
+        let rate = match self.discharge_rate {
+            Some(rate) => rate + self.config.rate_smoothing * (sample - rate),
+            None => sample,
+        };
+        self.discharge_rate = Some(rate);
+
+        if first {
+            let _ = self.log_tx.send(create_log(
+                "EnergyManager",
+                LogLevel::Info,
+                format!("Observed discharge rate {:.1}%/h", rate * 3600.0 * 100.0)
+            )).await;
+        }
+    }
+
+    fn publish(&self, sensor_data: &SensorData) {
+        let observed = self.discharge_rate.is_some();
+        let rate = self.discharge_rate
+            .unwrap_or(self.config.default_discharge_per_hour / 3600.0)
+            .max(MIN_DISCHARGE_RATE);
+
+        let per_second = rate * self.config.margin;
+        let per_meter = per_second / self.config.cruise_speed.max(0.01);
+
+        let mut estimate = EnergyEstimate {
+            timestamp: sensor_data.timestamp,
+            battery_level: sensor_data.battery_level,
+            reserve: self.config.reserve,
+            per_second,
+            per_meter,
+            observed,
+            range: 0.0,
+        };
+        estimate.range = estimate.usable() / per_meter;
+        let _ = self.energy_tx.send(Some(estimate));
+    }
+}
@@ -0,0 +1,35 @@
+use crate::types::GeoPoint;
+
+const EARTH_RADIUS: f64 = 6_371_000.0; // meters
+
+// Great-circle distance in meters
+pub fn distance(a: GeoPoint, b: GeoPoint) -> f64 {
+    let (lat1, lat2) = (a.lat.to_radians(), b.lat.to_radians());
+    let dlat = lat2 - lat1;
+    let dlon = (b.lon - a.lon).to_radians();
+
+    let h = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS * h.sqrt().asin()
+}
+
+// East/north offset in meters of `point` from `origin`. An equirectangular
+// projection is accurate to well under a meter over a test field.
+pub fn to_local(origin: GeoPoint, point: GeoPoint) -> [f64; 2] {
+    let east = (point.lon - origin.lon).to_radians() * EARTH_RADIUS * origin.lat.to_radians().cos();
+    let north = (point.lat - origin.lat).to_radians() * EARTH_RADIUS;
+    [east, north]
+}
+
+// Inverse of `to_local`
+pub fn from_local(origin: GeoPoint, local: [f64; 2]) -> GeoPoint {
+    GeoPoint {
+        lat: origin.lat + (local[1] / EARTH_RADIUS).to_degrees(),
+        lon: origin.lon + (local[0] / (EARTH_RADIUS * origin.lat.to_radians().cos())).to_degrees(),
+    }
+}
+
+// Yaw in radians from a quaternion [w, x, y, z], counter-clockwise from the x axis
+pub fn yaw(q: [f32; 4]) -> f32 {
+    let [w, x, y, z] = q;
+    (2.0 * (w * z + x * y)).atan2(1.0 - 2.0 * (y * y + z * z))
+}
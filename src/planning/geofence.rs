@@ -0,0 +1,306 @@
+use crate::types::{Geofence, GeoPoint, GeoPolygon};
+use crate::planning::geo::{from_local, to_local};
+use std::collections::BinaryHeap;
+use std::cmp::Ordering;
+use std::fmt;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+// Geofence shared between the modules that enforce it
+pub type SharedGeofence = Arc<RwLock<Geofence>>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GeofenceViolation {
+    OutsideKeepIn,
+    InsideKeepOut(String),
+}
+
+impl fmt::Display for GeofenceViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GeofenceViolation::OutsideKeepIn => write!(f, "outside keep-in area"),
+            GeofenceViolation::InsideKeepOut(name) => write!(f, "inside keep-out area '{}'", name),
+        }
+    }
+}
+
+type Local = [f64; 2];
+
+// Geofence projected into a local east/north frame in meters
+struct LocalFence {
+    keep_in: Vec<Vec<Local>>,
+    keep_out: Vec<(String, Vec<Local>)>,
+}
+
+impl LocalFence {
+    fn check(&self, point: Local) -> Result<(), GeofenceViolation> {
+        if !self.keep_in.is_empty() && !self.keep_in.iter().any(|polygon| contains(polygon, point)) {
+            return Err(GeofenceViolation::OutsideKeepIn);
+        }
+
+        if let Some((name, _)) = self.keep_out.iter().find(|(_, polygon)| contains(polygon, point)) {
+            return Err(GeofenceViolation::InsideKeepOut(name.clone()));
+        }
+
+        Ok(())
+    }
+
+    // A segment is allowed if both ends are and it crosses no fence edge
+    fn segment_allowed(&self, a: Local, b: Local) -> bool {
+        if self.check(a).is_err() || self.check(b).is_err() {
+            return false;
+        }
+
+        self.keep_in.iter()
+            .chain(self.keep_out.iter().map(|(_, polygon)| polygon))
+            .all(|polygon| edges(polygon).all(|(p, q)| !segments_cross(a, b, p, q)))
+    }
+}
+
+impl Geofence {
+    pub fn is_empty(&self) -> bool {
+        self.keep_in.is_empty() && self.keep_out.is_empty()
+    }
+
+    // Removes all areas, keeping the margin
+    pub fn clear(&mut self) {
+        self.keep_in.clear();
+        self.keep_out.clear();
+    }
+
+    fn localise(&self, origin: GeoPoint) -> LocalFence {
+        let project = |polygon: &GeoPolygon| -> Vec<Local> {
+            polygon.vertices.iter().map(|&vertex| to_local(origin, vertex)).collect()
+        };
+
+        LocalFence {
+            keep_in: self.keep_in.iter().map(project).collect(),
+            keep_out: self.keep_out.iter().map(|polygon| (polygon.name.clone(), project(polygon))).collect(),
+        }
+    }
+
+    pub fn check(&self, point: GeoPoint) -> Result<(), GeofenceViolation> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        self.localise(point).check([0.0, 0.0])
+    }
+
+    // Plans a route from `start` to `goal` that never leaves the keep-in areas or
+    // enters a keep-out area, treating fence edges as obstacles. Returns the
+    // points to drive through after `start`, ending with `goal`, or None if the
+    // goal cannot be reached. A start outside the fence also gives None: no route
+    // is planned back in, the operator drives the rover back manually.
+    pub fn plan_route(&self, start: GeoPoint, goal: GeoPoint) -> Option<Vec<GeoPoint>> {
+        if self.is_empty() {
+            return Some(vec![goal]);
+        }
+
+        let fence = self.localise(start);
+        let start_local = [0.0, 0.0];
+        let goal_local = to_local(start, goal);
+
+        if fence.check(start_local).is_err() || fence.check(goal_local).is_err() {
+            return None;
+        }
+        if fence.segment_allowed(start_local, goal_local) {
+            return Some(vec![goal]);
+        }
+
+        // Visibility graph through fence corners pushed `margin` away from the fence.
+        // Start and goal stay at indices 0 and 1; only corners outside the fence are dropped
+        let mut corners = Vec::new();
+        for polygon in &fence.keep_in {
+            corners.extend(offset_vertices(polygon, -self.margin));
+        }
+        for (_, polygon) in &fence.keep_out {
+            corners.extend(offset_vertices(polygon, self.margin));
+        }
+        let mut nodes = vec![start_local, goal_local];
+        nodes.extend(corners.into_iter().filter(|&node| fence.check(node).is_ok()));
+
+        // Dijkstra from node 0 (start) to node 1 (goal)
+        let mut best = vec![f64::INFINITY; nodes.len()];
+        let mut previous = vec![usize::MAX; nodes.len()];
+        let mut queue = BinaryHeap::new();
+        best[0] = 0.0;
+        queue.push(QueueEntry { cost: 0.0, node: 0 });
+
+        while let Some(QueueEntry { cost, node }) = queue.pop() {
+            if node == 1 {
+                break;
+            }
+            if cost > best[node] {
+                continue;
+            }
+
+            for next in 0..nodes.len() {
+                if next == node || !fence.segment_allowed(nodes[node], nodes[next]) {
+                    continue;
+                }
+                let next_cost = cost + length(nodes[node], nodes[next]);
+                if next_cost < best[next] {
+                    best[next] = next_cost;
+                    previous[next] = node;
+                    queue.push(QueueEntry { cost: next_cost, node: next });
+                }
+            }
+        }
+
+        if previous[1] == usize::MAX {
+            return None;
+        }
+
+        let mut route = Vec::new();
+        let mut node = 1;
+        while node != 0 {
+            route.push(if node == 1 { goal } else { from_local(start, nodes[node]) });
+            node = previous[node];
+        }
+        route.reverse();
+        Some(route)
+    }
+}
+
+#[derive(PartialEq)]
+struct QueueEntry {
+    cost: f64,
+    node: usize,
+}
+
+impl Eq for QueueEntry {}
+
+impl Ord for QueueEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed so the BinaryHeap pops the cheapest entry first
+        other.cost.total_cmp(&self.cost).then(self.node.cmp(&other.node))
+    }
+}
+
+impl PartialOrd for QueueEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+fn length(a: Local, b: Local) -> f64 {
+    (b[0] - a[0]).hypot(b[1] - a[1])
+}
+
+fn edges(polygon: &[Local]) -> impl Iterator<Item = (Local, Local)> + '_ {
+    polygon.iter().enumerate().map(|(i, &p)| (p, polygon[(i + 1) % polygon.len()]))
+}
+
+// Ray casting point-in-polygon test
+fn contains(polygon: &[Local], point: Local) -> bool {
+    let mut inside = false;
+    for (p, q) in edges(polygon) {
+        if (p[1] > point[1]) != (q[1] > point[1]) {
+            let x = p[0] + (point[1] - p[1]) / (q[1] - p[1]) * (q[0] - p[0]);
+            if point[0] < x {
+                inside = !inside;
+            }
+        }
+    }
+    inside
+}
+
+fn cross(o: Local, a: Local, b: Local) -> f64 {
+    (a[0] - o[0]) * (b[1] - o[1]) - (a[1] - o[1]) * (b[0] - o[0])
+}
+
+// True if segments a-b and p-q properly intersect
+fn segments_cross(a: Local, b: Local, p: Local, q: Local) -> bool {
+    let d1 = cross(p, q, a);
+    let d2 = cross(p, q, b);
+    let d3 = cross(a, b, p);
+    let d4 = cross(a, b, q);
+    d1 * d2 < 0.0 && d3 * d4 < 0.0
+}
+
+// Polygon vertices moved `distance` meters outwards along the corner bisector
+// (inwards for a negative distance)
+fn offset_vertices(polygon: &[Local], distance: f64) -> Vec<Local> {
+    let n = polygon.len();
+    if n < 3 {
+        return Vec::new();
+    }
+
+    // Shoelace formula, positive for counter-clockwise polygons
+    let area: f64 = edges(polygon).map(|(p, q)| p[0] * q[1] - q[0] * p[1]).sum();
+    let orientation = if area >= 0.0 { 1.0 } else { -1.0 };
+
+    let outward_normal = |p: Local, q: Local| -> Local {
+        let len = length(p, q).max(f64::EPSILON);
+        [orientation * (q[1] - p[1]) / len, -orientation * (q[0] - p[0]) / len]
+    };
+
+    (0..n).map(|i| {
+        let prev = polygon[(i + n - 1) % n];
+        let vertex = polygon[i];
+        let next = polygon[(i + 1) % n];
+
+        let n1 = outward_normal(prev, vertex);
+        let n2 = outward_normal(vertex, next);
+        let bisector = [n1[0] + n2[0], n1[1] + n2[1]];
+        let len = bisector[0].hypot(bisector[1]).max(f64::EPSILON);
+        let bisector = [bisector[0] / len, bisector[1] / len];
+
+        // Scale so both adjacent edges end up `distance` away, capped for sharp corners
+        let scale = distance / (bisector[0] * n1[0] + bisector[1] * n1[1]).max(0.3);
+        [vertex[0] + bisector[0] * scale, vertex[1] + bisector[1] * scale]
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORIGIN: GeoPoint = GeoPoint { lat: 48.0, lon: 11.0 };
+
+    fn polygon(name: &str, corners: &[[f64; 2]]) -> GeoPolygon {
+        GeoPolygon {
+            name: name.to_string(),
+            vertices: corners.iter().map(|&corner| from_local(ORIGIN, corner)).collect(),
+        }
+    }
+
+    fn fence() -> Geofence {
+        Geofence {
+            keep_in: vec![polygon("field", &[[-50.0, -50.0], [50.0, -50.0], [50.0, 50.0], [-50.0, 50.0]])],
+            keep_out: vec![polygon("pond", &[[-5.0, -20.0], [5.0, -20.0], [5.0, 20.0], [-5.0, 20.0]])],
+            margin: 1.0,
+        }
+    }
+
+    #[test]
+    fn routes_around_keep_out_to_goal() {
+        let start = from_local(ORIGIN, [-20.0, 0.0]);
+        let goal = from_local(ORIGIN, [20.0, 0.0]);
+
+        let route = fence().plan_route(start, goal).expect("route around the pond");
+
+        assert!(route.len() > 1);
+        assert_eq!(*route.last().unwrap(), goal);
+        for point in &route[..route.len() - 1] {
+            assert!(fence().check(*point).is_ok());
+        }
+    }
+
+    #[test]
+    fn start_outside_fence_has_no_route() {
+        let start = from_local(ORIGIN, [0.0, 0.0]); // Inside the pond
+        let goal = from_local(ORIGIN, [20.0, 0.0]);
+        assert_eq!(fence().plan_route(start, goal), None);
+
+        let start = from_local(ORIGIN, [80.0, 0.0]); // Outside the field
+        assert_eq!(fence().plan_route(start, goal), None);
+    }
+
+    #[test]
+    fn goal_outside_fence_has_no_route() {
+        let start = from_local(ORIGIN, [-20.0, 0.0]);
+        let goal = from_local(ORIGIN, [0.0, 0.0]);
+        assert_eq!(fence().plan_route(start, goal), None);
+    }
+}
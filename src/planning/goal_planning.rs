@@ -1,34 +1,44 @@
-use crate::types::{Goal, Path, RobotPose, StanceConfig, LogEntry, LogLevel};
+use crate::types::{Goal, GoalType, GeoPoint, Path, PositionEstimate, RobotPose, StanceConfig, LogLevel};
 use crate::infra::logger::create_log;
+use crate::infra::log_levels::LogSender;
 use crate::perception::stance::StanceRequest;
-use tokio::sync::{broadcast, mpsc};
+use crate::planning::geofence::SharedGeofence;
+use crate::infra::bus::{Publisher, Subscriber};
+use tokio::sync::{broadcast, watch};
+use serde::Serialize;
 
 pub struct GoalPlanning {
-    goal_rx: mpsc::Receiver<Goal>,
-    stance_query_tx: mpsc::Sender<StanceRequest>,
-    stance_rx: mpsc::Receiver<StanceConfig>,
-    obstacle_tx: mpsc::Sender<PathRequest>,
-    obstacle_rx: mpsc::Receiver<Path>,
-    behavior_tx: mpsc::Sender<Path>,
-    log_tx: mpsc::Sender<LogEntry>,
+    goal_rx: Subscriber<Goal>,
+    stance_query_tx: Publisher<StanceRequest>,
+    stance_rx: Subscriber<StanceConfig>,
+    obstacle_tx: Publisher<PathRequest>,
+    obstacle_rx: Subscriber<Path>,
+    behavior_tx: Publisher<Path>,
+    position_rx: watch::Receiver<Option<PositionEstimate>>,
+    log_tx: LogSender,
     shutdown_rx: broadcast::Receiver<()>,
+    geofence: SharedGeofence,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum PathRequest {
-    Plan { start: RobotPose, goal: RobotPose },
+    // `via` holds intermediate poses, in order, that keep the path inside the geofence
+    Plan { start: RobotPose, goal: RobotPose, via: Vec<RobotPose> },
 }
 
 impl GoalPlanning {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
-        goal_rx: mpsc::Receiver<Goal>,
-        stance_query_tx: mpsc::Sender<StanceRequest>,
-        stance_rx: mpsc::Receiver<StanceConfig>,
-        obstacle_tx: mpsc::Sender<PathRequest>,
-        obstacle_rx: mpsc::Receiver<Path>,
-        behavior_tx: mpsc::Sender<Path>,
-        log_tx: mpsc::Sender<LogEntry>,
+        goal_rx: Subscriber<Goal>,
+        stance_query_tx: Publisher<StanceRequest>,
+        stance_rx: Subscriber<StanceConfig>,
+        obstacle_tx: Publisher<PathRequest>,
+        obstacle_rx: Subscriber<Path>,
+        behavior_tx: Publisher<Path>,
+        position_rx: watch::Receiver<Option<PositionEstimate>>,
+        log_tx: LogSender,
         shutdown_rx: broadcast::Receiver<()>,
+        geofence: SharedGeofence,
     ) -> Self {
         Self {
             goal_rx,
@@ -37,8 +47,10 @@ impl GoalPlanning {
             obstacle_tx,
             obstacle_rx,
             behavior_tx,
+            position_rx,
             log_tx,
             shutdown_rx,
+            geofence,
         }
     }
 
@@ -69,7 +81,7 @@ impl GoalPlanning {
                         LogLevel::Info,
                         format!("Received safe path with {} waypoints", path.waypoints.len())
                     )).await;
-                    let _ = self.behavior_tx.send(path).await;
+                    self.behavior_tx.publish(path).await;
                 }
                 Some(stance_config) = self.stance_rx.recv() => {
                     // Received stance configuration response
@@ -97,21 +109,84 @@ impl GoalPlanning {
             format!("Planning path to goal: {:?}", goal.goal_type)
         )).await;
 
+        // Holding and scanning happen in place, so there is nothing to route
+        match goal.goal_type {
+            GoalType::Hold => {
+                self.behavior_tx.publish(Path {
+                    waypoints: Vec::new(),
+                    total_distance: 0.0,
+                    estimated_time: 0.0,
+                    trace: None,
+                }).await;
+                return;
+            }
+            GoalType::Scan => {
+                self.behavior_tx.publish(Path {
+                    waypoints: vec![goal.target_pose],
+                    total_distance: 0.0,
+                    estimated_time: 0.0,
+                    trace: None,
+                }).await;
+                return;
+            }
+            _ => {}
+        }
+
         // Query current stance
-        let _ = self.stance_query_tx.send(StanceRequest::Query).await;
+        self.stance_query_tx.publish(StanceRequest::Query).await;
 
-        // Create a simple path to goal
+        // Start from the fused position once there is one
+        let estimate = self.position_rx.borrow().clone();
         let start = RobotPose {
-            position: [0.0, 0.0, 0.0],
+            position: match &estimate {
+                Some(estimate) => [estimate.position.lat as f32, estimate.position.lon as f32, 0.0],
+                None => [0.0, 0.0, 0.0],
+            },
             orientation: [1.0, 0.0, 0.0, 0.0],
             velocity: [0.0, 0.0, 0.0],
             angular_velocity: [0.0, 0.0, 0.0],
         };
 
+        // Route around the geofence; fence edges are treated as obstacles
+        let mut via = Vec::new();
+        if let Some(estimate) = estimate {
+            let target = GeoPoint {
+                lat: goal.target_pose.position[0] as f64,
+                lon: goal.target_pose.position[1] as f64,
+            };
+            let route = self.geofence.read().await.plan_route(estimate.position, target);
+
+            match route {
+                Some(route) => {
+                    via = route[..route.len() - 1].iter().map(|point| RobotPose {
+                        position: [point.lat as f32, point.lon as f32, 0.0],
+                        ..start.clone()
+                    }).collect();
+
+                    if !via.is_empty() {
+                        let _ = self.log_tx.send(create_log(
+                            "GoalPlanning",
+                            LogLevel::Info,
+                            format!("Routing around geofence through {} intermediate point(s)", via.len())
+                        )).await;
+                    }
+                }
+                None => {
+                    let _ = self.log_tx.send(create_log(
+                        "GoalPlanning",
+                        LogLevel::Error,
+                        "No route to goal inside the geofence".to_string()
+                    )).await;
+                    return;
+                }
+            }
+        }
+
         // Request path validation from obstacle avoidance
-        let _ = self.obstacle_tx.send(PathRequest::Plan {
+        self.obstacle_tx.publish(PathRequest::Plan {
             start,
             goal: goal.target_pose,
+            via,
         }).await;
     }
 }
@@ -0,0 +1,34 @@
+use crate::types::HomePosition;
+use crate::planning::mission_store::write_atomically;
+use std::fs;
+use std::path::PathBuf;
+
+// Home position kept in a JSON file so it survives restarts
+pub struct HomeStore {
+    path: PathBuf,
+}
+
+impl HomeStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    // Returns None if no home has been saved yet
+    pub fn load(&self) -> Result<Option<HomePosition>, String> {
+        if !self.path.exists() {
+            return Ok(None);
+        }
+        let contents = fs::read_to_string(&self.path)
+            .map_err(|e| format!("cannot read {}: {}", self.path.display(), e))?;
+        serde_json::from_str(&contents)
+            .map(Some)
+            .map_err(|e| format!("cannot parse {}: {}", self.path.display(), e))
+    }
+
+    pub fn save(&self, home: &HomePosition) -> Result<(), String> {
+        let contents = serde_json::to_vec_pretty(home)
+            .map_err(|e| format!("cannot serialise home position: {}", e))?;
+        write_atomically(&self.path, &contents)
+            .map_err(|e| format!("cannot write {}: {}", self.path.display(), e))
+    }
+}
@@ -0,0 +1,509 @@
+use crate::types::{Mission, Task, TaskStatus, TaskType, Waypoint};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+// Tolerance for waypoints whose file does not give one, as for GoToWaypoint
+const DEFAULT_TOLERANCE: f32 = 2.0;
+
+// MAVLink mission commands understood in .plan files
+const MAV_CMD_NAV_WAYPOINT: u32 = 16;
+const MAV_CMD_NAV_LOITER_TIME: u32 = 19;
+const MAV_CMD_NAV_DELAY: u32 = 93;
+const MAV_CMD_DO_JUMP: u32 = 177;
+const MAV_CMD_DO_CHANGE_SPEED: u32 = 178;
+const MAV_FRAME_MISSION: u32 = 2;
+const MAV_FRAME_GLOBAL_RELATIVE_ALT: u32 = 3;
+const MAV_TYPE_GROUND_ROVER: u32 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MissionFormat {
+    Json,    // Mission as serialised by serde
+    GeoJson, // FeatureCollection of Point and LineString features
+    QgcPlan, // QGroundControl .plan file
+}
+
+impl MissionFormat {
+    // Picks the format from the file extension
+    pub fn from_path(path: &Path) -> Result<Self, String> {
+        let extension = path.extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_ascii_lowercase());
+
+        match extension.as_deref() {
+            Some("json") => Ok(MissionFormat::Json),
+            Some("geojson") => Ok(MissionFormat::GeoJson),
+            Some("plan") => Ok(MissionFormat::QgcPlan),
+            _ => Err(format!("{} is not a .json, .geojson or .plan file", path.display())),
+        }
+    }
+}
+
+// Parses a mission file. Missions read from GeoJSON or .plan files are named
+// `name` unless the file names them, and get id 0 and priority `priority`.
+pub fn parse_mission(contents: &str, format: MissionFormat, name: &str, priority: u8) -> Result<Mission, String> {
+    let mission = match format {
+        MissionFormat::Json => serde_json::from_str(contents).map_err(|e| e.to_string())?,
+        MissionFormat::GeoJson => {
+            let collection: FeatureCollection = serde_json::from_str(contents).map_err(|e| e.to_string())?;
+            let tasks = geojson_tasks(&collection)?;
+            Mission {
+                id: 0,
+                name: collection.name.unwrap_or_else(|| name.to_string()),
+                tasks,
+                priority,
+                loops: collection.loops.unwrap_or(1),
+            }
+        }
+        MissionFormat::QgcPlan => {
+            let plan: QgcPlan = serde_json::from_str(contents).map_err(|e| e.to_string())?;
+            let (tasks, loops) = plan_tasks(&plan)?;
+            Mission {
+                id: 0,
+                name: name.to_string(),
+                tasks,
+                priority,
+                loops,
+            }
+        }
+    };
+
+    validate(&mission)?;
+    Ok(mission)
+}
+
+pub fn format_mission(mission: &Mission, format: MissionFormat) -> Result<String, String> {
+    let contents = match format {
+        MissionFormat::Json => serde_json::to_string_pretty(mission),
+        MissionFormat::GeoJson => serde_json::to_string_pretty(&to_geojson(mission)?),
+        MissionFormat::QgcPlan => serde_json::to_string_pretty(&to_plan(mission)?),
+    };
+    contents.map_err(|e| e.to_string())
+}
+
+// Checks a mission can be run: at least one task, waypoints on the globe with
+// a positive tolerance
+pub fn validate(mission: &Mission) -> Result<(), String> {
+    if mission.tasks.is_empty() {
+        return Err("mission has no tasks".to_string());
+    }
+
+    for (i, task) in mission.tasks.iter().enumerate() {
+        if let TaskType::Navigate(waypoint) = &task.task_type {
+            if !(-90.0..=90.0).contains(&waypoint.lat) || !(-180.0..=180.0).contains(&waypoint.lon) {
+                return Err(format!("task {} has invalid coordinates ({}, {})", i + 1, waypoint.lat, waypoint.lon));
+            }
+            if !waypoint.tolerance.is_finite() || waypoint.tolerance <= 0.0 {
+                return Err(format!("task {} has invalid tolerance {}", i + 1, waypoint.tolerance));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn navigate_task(lat: f64, lon: f64, tolerance: f32, description: Option<String>, index: usize) -> Task {
+    Task {
+        id: 0,
+        description: description.unwrap_or_else(|| format!("Navigate to point {}", index)),
+        task_type: TaskType::Navigate(Waypoint { lat, lon, tolerance }),
+        status: TaskStatus::Pending,
+    }
+}
+
+fn wait_task(seconds: f64) -> Result<Task, String> {
+    if !seconds.is_finite() || seconds < 0.0 {
+        return Err(format!("invalid wait time {}", seconds));
+    }
+    let ms = (seconds * 1000.0).round() as u64;
+    Ok(Task {
+        id: 0,
+        description: format!("Wait {} ms", ms),
+        task_type: TaskType::Wait(ms),
+        status: TaskStatus::Pending,
+    })
+}
+
+fn scan_task() -> Task {
+    Task {
+        id: 0,
+        description: "Scan surroundings".to_string(),
+        task_type: TaskType::Scan,
+        status: TaskStatus::Pending,
+    }
+}
+
+// Numbers tasks from 1 in the order they run
+fn number_tasks(mut tasks: Vec<Task>) -> Vec<Task> {
+    for (i, task) in tasks.iter_mut().enumerate() {
+        task.id = i as u64 + 1;
+    }
+    tasks
+}
+
+// ============================================================================
+// GeoJSON
+// ============================================================================
+
+#[derive(Debug, Serialize, Deserialize)]
+struct FeatureCollection {
+    #[serde(rename = "type")]
+    kind: GeoJsonType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    // Times the features run, 0 repeats until cancelled, once if missing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    loops: Option<u32>,
+    features: Vec<Feature>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+enum GeoJsonType {
+    FeatureCollection,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Feature {
+    #[serde(rename = "type")]
+    kind: FeatureType,
+    geometry: Geometry,
+    #[serde(default)]
+    properties: Option<FeatureProperties>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+enum FeatureType {
+    Feature,
+}
+
+// Coordinates are [lon, lat] with an optional altitude, which is ignored
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type")]
+enum Geometry {
+    Point { coordinates: Vec<f64> },
+    LineString { coordinates: Vec<Vec<f64>> },
+}
+
+// Per-feature task options. `wait` and `scan` apply on arriving at a Point,
+// or at the last vertex of a LineString; the scan runs before the wait.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct FeatureProperties {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tolerance: Option<f32>, // meters
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    wait: Option<f64>,      // seconds
+    #[serde(default, skip_serializing_if = "is_false")]
+    scan: bool,
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+fn lat_lon(position: &[f64], feature: usize) -> Result<(f64, f64), String> {
+    match position {
+        [lon, lat, ..] => Ok((*lat, *lon)),
+        _ => Err(format!("feature {} has a position with fewer than 2 coordinates", feature)),
+    }
+}
+
+fn geojson_tasks(collection: &FeatureCollection) -> Result<Vec<Task>, String> {
+    let mut tasks = Vec::new();
+    let mut points = 0;
+
+    for (i, feature) in collection.features.iter().enumerate() {
+        let feature_number = i + 1;
+        let properties = feature.properties.as_ref();
+        let tolerance = properties.and_then(|p| p.tolerance).unwrap_or(DEFAULT_TOLERANCE);
+        let name = properties.and_then(|p| p.name.clone());
+
+        match &feature.geometry {
+            Geometry::Point { coordinates } => {
+                let (lat, lon) = lat_lon(coordinates, feature_number)?;
+                points += 1;
+                tasks.push(navigate_task(lat, lon, tolerance, name, points));
+            }
+            Geometry::LineString { coordinates } => {
+                if coordinates.is_empty() {
+                    return Err(format!("feature {} is an empty LineString", feature_number));
+                }
+                for (vertex, position) in coordinates.iter().enumerate() {
+                    let (lat, lon) = lat_lon(position, feature_number)?;
+                    points += 1;
+                    let description = name.as_ref().map(|name| format!("{} point {}", name, vertex + 1));
+                    tasks.push(navigate_task(lat, lon, tolerance, description, points));
+                }
+            }
+        }
+
+        if let Some(properties) = properties {
+            if properties.scan {
+                tasks.push(scan_task());
+            }
+            if let Some(seconds) = properties.wait {
+                tasks.push(wait_task(seconds).map_err(|e| format!("feature {}: {}", feature_number, e))?);
+            }
+        }
+    }
+
+    Ok(number_tasks(tasks))
+}
+
+// One Point feature per waypoint, carrying the scan and wait that follow it
+fn to_geojson(mission: &Mission) -> Result<FeatureCollection, String> {
+    let mut features: Vec<Feature> = Vec::new();
+
+    for task in &mission.tasks {
+        let last = features.last_mut().and_then(|feature| feature.properties.as_mut());
+
+        match (&task.task_type, last) {
+            (TaskType::Navigate(waypoint), _) => {
+                features.push(Feature {
+                    kind: FeatureType::Feature,
+                    geometry: Geometry::Point { coordinates: vec![waypoint.lon, waypoint.lat] },
+                    properties: Some(FeatureProperties {
+                        name: Some(task.description.clone()),
+                        tolerance: Some(waypoint.tolerance),
+                        ..Default::default()
+                    }),
+                });
+            }
+            (TaskType::Scan, Some(properties)) if !properties.scan && properties.wait.is_none() => {
+                properties.scan = true;
+            }
+            (TaskType::Wait(ms), Some(properties)) if properties.wait.is_none() => {
+                properties.wait = Some(*ms as f64 / 1000.0);
+            }
+            _ => {
+                return Err(format!(
+                    "task {} ({}) cannot be expressed in GeoJSON: scans and waits must follow a waypoint, at most one of each, scan first",
+                    task.id, task.description
+                ));
+            }
+        }
+    }
+
+    Ok(FeatureCollection {
+        kind: GeoJsonType::FeatureCollection,
+        name: Some(mission.name.clone()),
+        loops: Some(mission.loops),
+        features,
+    })
+}
+
+// ============================================================================
+// QGroundControl .plan
+// ============================================================================
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct QgcPlan {
+    file_type: String,
+    version: u32,
+    #[serde(default)]
+    ground_station: String,
+    mission: QgcMission,
+    #[serde(default = "empty_fence")]
+    geo_fence: serde_json::Value,
+    #[serde(default = "empty_rally_points")]
+    rally_points: serde_json::Value,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct QgcMission {
+    version: u32,
+    #[serde(default)]
+    firmware_type: u32,
+    #[serde(default)]
+    vehicle_type: u32,
+    #[serde(default)]
+    cruise_speed: f64,
+    #[serde(default)]
+    hover_speed: f64,
+    #[serde(default)]
+    planned_home_position: Vec<f64>,
+    items: Vec<QgcItem>,
+}
+
+// Survey and other complex items are not supported
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type")]
+enum QgcItem {
+    SimpleItem(QgcSimpleItem),
+}
+
+// `params` follow the MAVLink command: for a waypoint [hold s, acceptance
+// radius m, pass radius, yaw, lat, lon, alt]
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct QgcSimpleItem {
+    command: u32,
+    #[serde(default)]
+    frame: u32,
+    params: Vec<Option<f64>>,
+    #[serde(default = "default_true")]
+    auto_continue: bool,
+    #[serde(rename = "doJumpId", default)]
+    do_jump_id: u32,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn empty_fence() -> serde_json::Value {
+    serde_json::json!({ "circles": [], "polygons": [], "version": 2 })
+}
+
+fn empty_rally_points() -> serde_json::Value {
+    serde_json::json!({ "points": [], "version": 2 })
+}
+
+// A missing or null parameter reads as 0, as in QGroundControl
+fn param(item: &QgcSimpleItem, index: usize) -> f64 {
+    item.params.get(index).copied().flatten().unwrap_or(0.0)
+}
+
+// Tasks and loop count of a plan. A DO_JUMP back to the first item is only
+// understood as the last item, where it repeats the whole mission.
+fn plan_tasks(plan: &QgcPlan) -> Result<(Vec<Task>, u32), String> {
+    if plan.file_type != "Plan" {
+        return Err(format!("fileType is '{}', expected 'Plan'", plan.file_type));
+    }
+
+    let items = &plan.mission.items;
+    let mut tasks = Vec::new();
+    let mut points = 0;
+    let mut loops = 1;
+
+    for (i, QgcItem::SimpleItem(item)) in items.iter().enumerate() {
+        match item.command {
+            MAV_CMD_NAV_WAYPOINT | MAV_CMD_NAV_LOITER_TIME => {
+                let tolerance = match param(item, 1) as f32 {
+                    radius if item.command == MAV_CMD_NAV_WAYPOINT && radius > 0.0 => radius,
+                    _ => DEFAULT_TOLERANCE,
+                };
+                points += 1;
+                tasks.push(navigate_task(param(item, 4), param(item, 5), tolerance, None, points));
+
+                let hold = param(item, 0);
+                if hold > 0.0 {
+                    tasks.push(wait_task(hold).map_err(|e| format!("item {}: {}", i + 1, e))?);
+                }
+            }
+            MAV_CMD_NAV_DELAY => {
+                tasks.push(wait_task(param(item, 0)).map_err(|e| format!("item {}: {}", i + 1, e))?);
+            }
+            // Speed is up to the rover's own limits
+            MAV_CMD_DO_CHANGE_SPEED => {}
+            MAV_CMD_DO_JUMP => {
+                let QgcItem::SimpleItem(first) = &items[0];
+                if i + 1 != items.len() || param(item, 0) != first.do_jump_id as f64 {
+                    return Err(format!("item {} jumps to item {}, only a final jump to the first item is supported", i + 1, param(item, 0)));
+                }
+                // The repeat count does not include the first run, -1 repeats forever
+                loops = match param(item, 1) {
+                    repeat if repeat < 0.0 => 0,
+                    repeat => repeat.round() as u32 + 1,
+                };
+            }
+            command => return Err(format!("item {} uses unsupported MAVLink command {}", i + 1, command)),
+        }
+    }
+
+    Ok((number_tasks(tasks), loops))
+}
+
+fn plan_item(command: u32, frame: u32, params: [f64; 7], sequence: u32) -> QgcItem {
+    QgcItem::SimpleItem(QgcSimpleItem {
+        command,
+        frame,
+        params: params.into_iter().map(Some).collect(),
+        auto_continue: true,
+        do_jump_id: sequence,
+    })
+}
+
+// Waypoints become NAV_WAYPOINT items holding for a wait that follows them,
+// other waits become NAV_DELAY items, and a mission that runs more than once
+// ends with a DO_JUMP back to the start. Scans have no MAVLink equivalent.
+fn to_plan(mission: &Mission) -> Result<QgcPlan, String> {
+    let mut items: Vec<QgcItem> = Vec::new();
+    let mut home = Vec::new();
+    let mut after_waypoint = false;
+
+    for task in &mission.tasks {
+        let sequence = items.len() as u32 + 1;
+
+        match &task.task_type {
+            TaskType::Navigate(waypoint) => {
+                if home.is_empty() {
+                    home = vec![waypoint.lat, waypoint.lon, 0.0];
+                }
+                let params = [0.0, waypoint.tolerance as f64, 0.0, f64::NAN, waypoint.lat, waypoint.lon, 0.0];
+                items.push(plan_item(MAV_CMD_NAV_WAYPOINT, MAV_FRAME_GLOBAL_RELATIVE_ALT, params, sequence));
+                after_waypoint = true;
+                continue;
+            }
+            TaskType::Wait(ms) => {
+                let seconds = *ms as f64 / 1000.0;
+                match items.last_mut() {
+                    Some(QgcItem::SimpleItem(item)) if after_waypoint => item.params[0] = Some(seconds),
+                    _ => {
+                        let params = [seconds, -1.0, -1.0, -1.0, 0.0, 0.0, 0.0];
+                        items.push(plan_item(MAV_CMD_NAV_DELAY, MAV_FRAME_MISSION, params, sequence));
+                    }
+                }
+            }
+            TaskType::Scan => {
+                return Err(format!("task {} ({}) is a scan, which .plan files cannot express", task.id, task.description));
+            }
+        }
+        after_waypoint = false;
+    }
+
+    if mission.loops != 1 && !items.is_empty() {
+        let repeat = match mission.loops {
+            0 => -1.0,
+            loops => (loops - 1) as f64,
+        };
+        let params = [1.0, repeat, 0.0, 0.0, 0.0, 0.0, 0.0];
+        items.push(plan_item(MAV_CMD_DO_JUMP, MAV_FRAME_MISSION, params, items.len() as u32 + 1));
+    }
+
+    // QGroundControl writes an unset yaw as null
+    for QgcItem::SimpleItem(item) in &mut items {
+        for value in &mut item.params {
+            if value.is_some_and(f64::is_nan) {
+                *value = None;
+            }
+        }
+    }
+
+    Ok(QgcPlan {
+        file_type: "Plan".to_string(),
+        version: 1,
+        ground_station: "QGroundControl".to_string(),
+        mission: QgcMission {
+            version: 2,
+            firmware_type: 0,
+            vehicle_type: MAV_TYPE_GROUND_ROVER,
+            cruise_speed: 0.0,
+            hover_speed: 0.0,
+            planned_home_position: home,
+            items,
+        },
+        geo_fence: empty_fence(),
+        rally_points: empty_rally_points(),
+    })
+}
+
+// Name for a mission read from `path` whose file does not name it
+pub fn name_from_path(path: &Path) -> String {
+    path.file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("Imported mission")
+        .to_string()
+}
@@ -0,0 +1,107 @@
+use crate::types::{Mission, TaskStatus};
+use crate::planning::mission_format::{MissionFormat, format_mission, name_from_path, parse_mission};
+use crate::planning::task_mission_manager::DEFAULT_PRIORITY;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+// A mission with where it left off: the running mission, or one waiting in
+// the queue after being preempted
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedMission {
+    pub mission: Mission,
+    pub task: usize,      // Index of the task to run next
+    pub loop_number: u32, // 1-based pass through the task list
+}
+
+// Everything the mission manager needs to pick up after a restart
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MissionSnapshot {
+    pub running: Option<SavedMission>,
+    pub queue: Vec<SavedMission>,
+}
+
+impl MissionSnapshot {
+    pub fn is_empty(&self) -> bool {
+        self.running.is_none() && self.queue.is_empty()
+    }
+
+    // The running mission first, then the queue in order
+    pub fn into_missions(self) -> Vec<SavedMission> {
+        self.running.into_iter().chain(self.queue).collect()
+    }
+}
+
+// Mission state kept in a JSON file. Every save replaces the file atomically
+// so a crash mid-write leaves the previous snapshot intact.
+pub struct MissionStore {
+    path: PathBuf,
+}
+
+impl MissionStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Returns an empty snapshot if nothing has been saved yet
+    pub fn load(&self) -> Result<MissionSnapshot, String> {
+        if !self.path.exists() {
+            return Ok(MissionSnapshot::default());
+        }
+        let contents = fs::read_to_string(&self.path)
+            .map_err(|e| format!("cannot read {}: {}", self.path.display(), e))?;
+        serde_json::from_str(&contents)
+            .map_err(|e| format!("cannot parse {}: {}", self.path.display(), e))
+    }
+
+    pub fn save(&self, snapshot: &MissionSnapshot) -> Result<(), String> {
+        let contents = serde_json::to_vec_pretty(snapshot)
+            .map_err(|e| format!("cannot serialise mission state: {}", e))?;
+        write_atomically(&self.path, &contents)
+            .map_err(|e| format!("cannot write {}: {}", self.path.display(), e))
+    }
+}
+
+// Writes to a temporary file next to `path`, syncs it and renames it over
+// `path`, so readers see either the old or the new contents
+pub fn write_atomically(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut temp_name = path.as_os_str().to_owned();
+    temp_name.push(".tmp");
+    let temp_path = PathBuf::from(temp_name);
+
+    let mut file = File::create(&temp_path)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    drop(file);
+
+    fs::rename(&temp_path, path)
+}
+
+// Reads a mission from a JSON, GeoJSON or QGroundControl .plan file, picked
+// by extension. Task statuses are reset so the mission runs from the start.
+pub fn import_mission(path: &Path) -> Result<Mission, String> {
+    let format = MissionFormat::from_path(path)?;
+    let contents = fs::read_to_string(path)
+        .map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+    let mut mission = parse_mission(&contents, format, &name_from_path(path), DEFAULT_PRIORITY)
+        .map_err(|e| format!("invalid mission in {}: {}", path.display(), e))?;
+
+    for task in &mut mission.tasks {
+        task.status = TaskStatus::Pending;
+    }
+    Ok(mission)
+}
+
+// Writes a mission in the format picked by the file extension
+pub fn export_mission(mission: &Mission, path: &Path) -> Result<(), String> {
+    let format = MissionFormat::from_path(path)?;
+    let contents = format_mission(mission, format)
+        .map_err(|e| format!("cannot export to {}: {}", path.display(), e))?;
+    write_atomically(path, contents.as_bytes())
+        .map_err(|e| format!("cannot write {}: {}", path.display(), e))
+}
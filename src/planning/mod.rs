@@ -1,3 +1,9 @@
 pub mod state_manager;
 pub mod task_mission_manager;
 pub mod goal_planning;
+pub mod geo;
+pub mod geofence;
+pub mod mission_store;
+pub mod mission_format;
+pub mod home_position;
+pub mod energy_manager;
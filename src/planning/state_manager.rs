@@ -1,35 +1,59 @@
-use crate::types::{RobotState, RobotPose, SensorData, UserCommand, SystemCommand, LogEntry, LogLevel};
+use crate::types::{RobotState, RobotPose, SensorData, CommandRequest, CommandId, CommandAck, AckStatus, UserCommand, SystemCommand, ManualControl, FaultReport, SafetyFault, GeoPoint, PositionEstimate, DiagnosticStatus, DiagnosticLevel, LogLevel};
 use crate::infra::logger::create_log;
-use tokio::sync::{broadcast, mpsc};
+use crate::infra::log_levels::LogSender;
+use crate::infra::bus::{Publisher, Subscriber};
+use crate::infra::diagnostics::DiagnosticReporter;
+use crate::planning::geo::{from_local, to_local, yaw};
+use tokio::sync::{broadcast, watch};
+
+// GPS fixes less accurate than this (meters) are not fused into the position estimate
+const MAX_GPS_ACCURACY: f32 = 10.0;
+// Growth of the position uncertainty between fixes, in meters per second
+const POSITION_DRIFT_RATE: f32 = 0.5;
 
 pub struct StateManager {
-    sensor_rx: mpsc::Receiver<SensorData>,
-    command_rx: mpsc::Receiver<UserCommand>,
-    state_tx: mpsc::Sender<RobotState>,
-    safety_state_tx: mpsc::Sender<RobotState>,
-    task_manager_state_tx: mpsc::Sender<RobotState>,
-    log_tx: mpsc::Sender<LogEntry>,
+    sensor_rx: Subscriber<SensorData>,
+    command_rx: Subscriber<CommandRequest>,
+    fault_rx: Subscriber<FaultReport>,
+    accepted_rx: Subscriber<u64>,
+    state_pub: Publisher<RobotState>,
+    position_tx: watch::Sender<Option<PositionEstimate>>,
+    manual_tx: Publisher<(CommandId, ManualControl)>,
+    ack_tx: Publisher<CommandAck>,
+    diagnostics: DiagnosticReporter,
+    log_tx: LogSender,
     shutdown_rx: broadcast::Receiver<()>,
     current_state: RobotState,
     pose: RobotPose,
+    position: Option<PositionEstimate>,
+    active_faults: Vec<SafetyFault>,
 }
 
 impl StateManager {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
-        sensor_rx: mpsc::Receiver<SensorData>,
-        command_rx: mpsc::Receiver<UserCommand>,
-        state_tx: mpsc::Sender<RobotState>,
-        safety_state_tx: mpsc::Sender<RobotState>,
-        task_manager_state_tx: mpsc::Sender<RobotState>,
-        log_tx: mpsc::Sender<LogEntry>,
+        sensor_rx: Subscriber<SensorData>,
+        command_rx: Subscriber<CommandRequest>,
+        fault_rx: Subscriber<FaultReport>,
+        accepted_rx: Subscriber<u64>,
+        state_pub: Publisher<RobotState>,
+        position_tx: watch::Sender<Option<PositionEstimate>>,
+            manual_tx: Publisher<(CommandId, ManualControl)>,
+        ack_tx: Publisher<CommandAck>,
+        diagnostics: DiagnosticReporter,
+        log_tx: LogSender,
         shutdown_rx: broadcast::Receiver<()>,
     ) -> Self {
         Self {
             sensor_rx,
             command_rx,
-            state_tx,
-            safety_state_tx,
-            task_manager_state_tx,
+            fault_rx,
+            accepted_rx,
+            state_pub,
+            position_tx,
+            manual_tx,
+            ack_tx,
+            diagnostics,
             log_tx,
             shutdown_rx,
             current_state: RobotState::Idle,
@@ -39,6 +63,8 @@ impl StateManager {
                 velocity: [0.0, 0.0, 0.0],
                 angular_velocity: [0.0, 0.0, 0.0],
             },
+            position: None,
+            active_faults: Vec::new(),
         }
     }
 
@@ -61,10 +87,17 @@ impl StateManager {
                 }
                 Some(sensor_data) = self.sensor_rx.recv() => {
                     self.update_pose(&sensor_data);
+                    self.report_gps(&sensor_data).await;
                 }
                 Some(command) = self.command_rx.recv() => {
                     self.handle_command(command).await;
                 }
+                Some(report) = self.fault_rx.recv() => {
+                    self.handle_fault(report).await;
+                }
+                Some(mission_id) = self.accepted_rx.recv() => {
+                    self.handle_mission_accepted(mission_id).await;
+                }
             }
         }
 
@@ -79,16 +112,80 @@ impl StateManager {
         // Update pose from sensor data
         self.pose.orientation = sensor_data.imu.orientation;
         // In a real system, we'd integrate velocity to get position
+        self.fuse_gps(sensor_data);
+    }
+
+    // Blends each usable GPS fix into the position estimate, weighting it
+    // against the estimate's uncertainty grown since the last fix
+    fn fuse_gps(&mut self, sensor_data: &SensorData) {
+        let gps = &sensor_data.gps;
+        if gps.accuracy <= 0.0 || gps.accuracy > MAX_GPS_ACCURACY {
+            return;
+        }
+
+        let fix = GeoPoint { lat: gps.latitude, lon: gps.longitude };
+        let (position, accuracy) = match &self.position {
+            None => (fix, gps.accuracy),
+            Some(previous) => {
+                let elapsed = sensor_data.timestamp.duration_since(previous.timestamp)
+                    .unwrap_or_default()
+                    .as_secs_f32();
+                let prior_variance = (previous.accuracy + POSITION_DRIFT_RATE * elapsed).powi(2);
+                let gain = prior_variance / (prior_variance + gps.accuracy.powi(2));
+
+                let offset = to_local(previous.position, fix);
+                let position = from_local(previous.position, [offset[0] * gain as f64, offset[1] * gain as f64]);
+                (position, (prior_variance * (1.0 - gain)).sqrt())
+            }
+        };
+
+        let estimate = PositionEstimate {
+            timestamp: sensor_data.timestamp,
+            position,
+            accuracy,
+            yaw: yaw(sensor_data.imu.orientation),
+        };
+        self.position = Some(estimate.clone());
+        let _ = self.position_tx.send(Some(estimate));
+    }
+
+    async fn report_gps(&mut self, sensor_data: &SensorData) {
+        let accuracy = sensor_data.gps.accuracy;
+        let (level, message) = if accuracy <= 0.0 {
+            (DiagnosticLevel::Error, "No GPS fix".to_string())
+        } else if accuracy > MAX_GPS_ACCURACY {
+            (DiagnosticLevel::Warn, format!("GPS fix too inaccurate to use ({:.1} m)", accuracy))
+        } else {
+            (DiagnosticLevel::Ok, "GPS fix in use".to_string())
+        };
+        let status = DiagnosticStatus::new("sensors/gps", level, message)
+            .with("accuracy_m", format!("{:.1}", accuracy))
+            .with("position_accuracy_m", self.position.as_ref().map_or("none".to_string(), |p| format!("{:.1}", p.accuracy)));
+        self.diagnostics.report(status).await;
     }
 
-    async fn handle_command(&mut self, command: UserCommand) {
-        let new_state = match command {
+    // Acknowledges the state change a command asks for. System commands are
+    // complete once the state has changed; manual control is only accepted
+    // here, Behaviour and the Safety Controller report the rest. Manual
+    // control only reaches Behaviour once it has been accepted. Mission
+    // commands are answered by the task/mission manager alone.
+    async fn handle_command(&mut self, request: CommandRequest) {
+        let completes = matches!(request.command, UserCommand::SystemCommand(_));
+        let new_state = match &request.command {
+            UserCommand::ManualControl(control)
+                if !self.active_faults.is_empty() && self.active_faults.iter().all(allows_manual_control) =>
+            {
+                // The operator drives the robot back; the state stays in Error
+                // until the faults clear
+                self.ack_tx.publish(CommandAck::new("StateManager", request.id, AckStatus::Accepted)).await;
+                self.manual_tx.publish((request.id, control.clone())).await;
+                return;
+            }
             UserCommand::ManualControl(_) => {
                 RobotState::ManualControl
             }
-            UserCommand::MissionCommand(_) => {
-                RobotState::ExecutingMission
-            }
+            // The state follows the missions the task/mission manager accepts
+            UserCommand::MissionCommand(_) => return,
             UserCommand::SystemCommand(sys_cmd) => {
                 match sys_cmd {
                     SystemCommand::Pause => RobotState::Paused,
@@ -97,8 +194,72 @@ impl StateManager {
                     SystemCommand::Calibrate => RobotState::Idle,
                 }
             }
+            // Geofence updates do not change the robot state
+            UserCommand::GeofenceCommand(_) => return,
         };
 
+        // Active faults keep the robot in Error until they clear; only an
+        // emergency stop may override them
+        if !self.active_faults.is_empty() && !matches!(new_state, RobotState::EmergencyStop) {
+            let _ = self.log_tx.send(create_log(
+                "StateManager",
+                LogLevel::Warn,
+                format!("Ignoring transition to {:?} - {} fault(s) active", new_state, self.active_faults.len())
+            )).await;
+            let reason = format!("{} safety fault(s) active", self.active_faults.len());
+            self.ack_tx.publish(CommandAck::new("StateManager", request.id, AckStatus::Rejected(reason))).await;
+            return;
+        }
+
+        self.transition_to(new_state).await;
+        let status = if completes { AckStatus::Completed } else { AckStatus::Accepted };
+        self.ack_tx.publish(CommandAck::new("StateManager", request.id, status)).await;
+
+        if let UserCommand::ManualControl(control) = request.command {
+            self.manual_tx.publish((request.id, control)).await;
+        }
+    }
+
+    // The task/mission manager accepted or resumed a mission. It refuses
+    // missions while the robot is in Error, so a fault raised in between
+    // keeps the robot there.
+    async fn handle_mission_accepted(&mut self, mission_id: u64) {
+        if !self.active_faults.is_empty() {
+            let _ = self.log_tx.send(create_log(
+                "StateManager",
+                LogLevel::Warn,
+                format!("Not executing mission {} - {} fault(s) active", mission_id, self.active_faults.len())
+            )).await;
+            return;
+        }
+        self.transition_to(RobotState::ExecutingMission).await;
+    }
+
+    async fn handle_fault(&mut self, report: FaultReport) {
+        // Component failures are told apart by component, other faults by kind
+        let same_fault = |fault: &SafetyFault| match (fault, &report.fault) {
+            (SafetyFault::ComponentFailure { component, .. }, SafetyFault::ComponentFailure { component: reported, .. }) => {
+                component == reported
+            }
+            (fault, reported) => std::mem::discriminant(fault) == std::mem::discriminant(reported),
+        };
+
+        if report.active {
+            if !self.active_faults.iter().any(same_fault) {
+                self.active_faults.push(report.fault.clone());
+            }
+            if !matches!(self.current_state, RobotState::EmergencyStop) {
+                self.transition_to(RobotState::Error(format!("Safety fault: {:?}", report.fault))).await;
+            }
+        } else {
+            self.active_faults.retain(|fault| !same_fault(fault));
+            if self.active_faults.is_empty() && matches!(self.current_state, RobotState::Error(_)) {
+                self.transition_to(RobotState::Idle).await;
+            }
+        }
+    }
+
+    async fn transition_to(&mut self, new_state: RobotState) {
         if !matches!(&self.current_state, state if std::mem::discriminant(state) == std::mem::discriminant(&new_state)) {
             let _ = self.log_tx.send(create_log(
                 "StateManager",
@@ -108,10 +269,14 @@ impl StateManager {
 
             self.current_state = new_state.clone();
 
-            // Broadcast state to interested modules
-            let _ = self.state_tx.send(new_state.clone()).await;
-            let _ = self.safety_state_tx.send(new_state.clone()).await;
-            let _ = self.task_manager_state_tx.send(new_state).await;
+            // Read by the safety controller, task/mission manager and output manager
+            self.state_pub.publish(new_state).await;
         }
     }
 }
+
+// Faults the Safety Controller still lets manual commands through for, so the
+// operator can drive the robot out of them
+fn allows_manual_control(fault: &SafetyFault) -> bool {
+    matches!(fault, SafetyFault::GeofenceBreach { .. })
+}
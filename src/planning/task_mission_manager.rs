@@ -1,32 +1,146 @@
-use crate::types::{Mission, Task, TaskType, TaskStatus, UserCommand, MissionCommand, RobotState, Goal, GoalType, RobotPose, LogEntry, LogLevel};
+use crate::types::{EnergyEstimate, Mission, MissionEvent, MissionProgress, ScanResult, Task, TaskType, TaskStatus, CommandRequest, CommandId, CommandAck, AckStatus, UserCommand, MissionCommand, GeofenceCommand, GeoPoint, HomeLocation, HomePosition, HomeSource, PositionEstimate, RobotState, Goal, GoalType, RobotPose, LogLevel};
 use crate::infra::logger::create_log;
-use tokio::sync::{broadcast, mpsc};
+use crate::infra::log_levels::LogSender;
+use crate::infra::bus::{Publisher, Subscriber};
+use crate::perception::environment_understanding::ScanRequest;
+use crate::planning::geo::distance;
+use crate::planning::geofence::SharedGeofence;
+use crate::planning::home_position::HomeStore;
+use crate::planning::mission_store::{MissionSnapshot, MissionStore, SavedMission, export_mission, import_mission};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::SystemTime;
+use tokio::sync::{broadcast, watch};
+use tokio::time::{Duration, Instant, interval};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MissionConfig {
+    pub check_interval_ms: u64,  // How often task completion is checked
+    pub progress_report_ms: u64, // How often progress is reported while a task runs
+    pub task_timeout_s: u64,     // A task not completed within this time fails
+    pub goal_refresh_ms: u64,    // Re-send the running task's goal this often, below the arbiter's goal path timeout
+    pub scan_turn_rate: f32,     // Normalised turn rate while scanning
+    pub store_path: String,      // File the running mission and queue are saved to
+    pub home_path: String,       // File the home position is saved to
+    pub home_accuracy: f32,      // meters, a fix must be at least this accurate to become home
+    pub scan_estimate_s: u64,    // Expected length of a scan, for energy estimates
+}
+
+impl Default for MissionConfig {
+    fn default() -> Self {
+        Self {
+            check_interval_ms: 500,
+            progress_report_ms: 2000,
+            task_timeout_s: 600,
+            goal_refresh_ms: 5000,
+            scan_turn_rate: 0.3,
+            store_path: "missions.json".to_string(),
+            home_path: "home.json".to_string(),
+            home_accuracy: 3.0,
+            scan_estimate_s: 30,
+        }
+    }
+}
+
+// Priority of missions that do not ask for one
+pub const DEFAULT_PRIORITY: u8 = 5;
+// Getting home outranks routine missions
+const RETURN_HOME_PRIORITY: u8 = 8;
+// Running out of battery away from home outranks everything
+const ENERGY_RETURN_PRIORITY: u8 = u8::MAX;
 
 pub struct TaskMissionManager {
-    command_rx: mpsc::Receiver<UserCommand>,
-    state_rx: mpsc::Receiver<RobotState>,
-    goal_tx: mpsc::Sender<Goal>,
-    log_tx: mpsc::Sender<LogEntry>,
+    command_rx: Subscriber<CommandRequest>,
+    state_rx: Subscriber<RobotState>,
+    position_rx: watch::Receiver<Option<PositionEstimate>>,
+    energy_rx: watch::Receiver<Option<EnergyEstimate>>,
+    scan_result_rx: Subscriber<ScanResult>,
+    goal_tx: Publisher<Goal>,
+    scan_tx: Publisher<ScanRequest>,
+    event_tx: Publisher<MissionEvent>,
+    ack_tx: Publisher<CommandAck>,
+    mission_tx: watch::Sender<Option<MissionProgress>>,
+    accepted_tx: Publisher<u64>,
+    log_tx: LogSender,
     shutdown_rx: broadcast::Receiver<()>,
+    config: MissionConfig,
+    geofence: SharedGeofence,
+    store: MissionStore,
+    saved: Vec<SavedMission>,    // Missions saved before a restart, offered to the operator
+    home_store: HomeStore,
+    home: Option<HomePosition>,
+    home_captured: bool,         // Set once this run has captured or been given a home
+    return_home_mission: Option<u64>, // Id of the latest ReturnHome mission
+    energy_hold: Option<u64>,    // Queued mission held back for lack of charge
+    mission_commands: HashMap<u64, CommandId>, // Command that created each running or queued mission
     current_mission: Option<Mission>,
+    queue: Vec<SavedMission>,   // Highest priority first, first come first served within a priority
+    suspended_since: Option<Instant>, // Set while the robot state does not allow missions to run
+    faulted: bool,               // Set while the robot is in Error
+    current_task: usize,         // Index of the running task in current_mission
+    current_loop: u32,           // 1-based pass through the mission's task list
+    task_started: Instant,
+    last_goal_sent: Instant,
+    last_progress_report: Instant,
+    scan_complete: bool,         // Set when EnvironmentUnderstanding reports the running scan
     mission_counter: u64,
 }
 
 impl TaskMissionManager {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
-        command_rx: mpsc::Receiver<UserCommand>,
-        state_rx: mpsc::Receiver<RobotState>,
-        goal_tx: mpsc::Sender<Goal>,
-        log_tx: mpsc::Sender<LogEntry>,
+        command_rx: Subscriber<CommandRequest>,
+        state_rx: Subscriber<RobotState>,
+        position_rx: watch::Receiver<Option<PositionEstimate>>,
+        energy_rx: watch::Receiver<Option<EnergyEstimate>>,
+        scan_result_rx: Subscriber<ScanResult>,
+        goal_tx: Publisher<Goal>,
+        scan_tx: Publisher<ScanRequest>,
+        event_tx: Publisher<MissionEvent>,
+        ack_tx: Publisher<CommandAck>,
+        mission_tx: watch::Sender<Option<MissionProgress>>,
+        accepted_tx: Publisher<u64>,
+        log_tx: LogSender,
         shutdown_rx: broadcast::Receiver<()>,
+        config: MissionConfig,
+        geofence: SharedGeofence,
     ) -> Self {
         Self {
             command_rx,
             state_rx,
+            position_rx,
+            energy_rx,
+            scan_result_rx,
             goal_tx,
+            scan_tx,
+            event_tx,
+            ack_tx,
+            mission_tx,
+            accepted_tx,
             log_tx,
             shutdown_rx,
+            store: MissionStore::new(&config.store_path),
+            saved: Vec::new(),
+            home_store: HomeStore::new(&config.home_path),
+            home: None,
+            home_captured: false,
+            return_home_mission: None,
+            energy_hold: None,
+            mission_commands: HashMap::new(),
+            config,
+            geofence,
             current_mission: None,
+            queue: Vec::new(),
+            suspended_since: None,
+            faulted: false,
+            current_task: 0,
+            current_loop: 1,
+            task_started: Instant::now(),
+            last_goal_sent: Instant::now(),
+            last_progress_report: Instant::now(),
+            scan_complete: false,
             mission_counter: 0,
         }
     }
@@ -38,6 +152,11 @@ impl TaskMissionManager {
             "Starting task/mission manager".to_string()
         )).await;
 
+        self.load_home().await;
+        self.load_saved().await;
+
+        let mut progress_check = interval(Duration::from_millis(self.config.check_interval_ms));
+
         loop {
             tokio::select! {
                 _ = self.shutdown_rx.recv() => {
@@ -51,8 +170,19 @@ impl TaskMissionManager {
                 Some(command) = self.command_rx.recv() => {
                     self.handle_command(command).await;
                 }
-                Some(_state) = self.state_rx.recv() => {
-                    // Update based on state changes
+                Some(state) = self.state_rx.recv() => {
+                    self.handle_state(state).await;
+                }
+                Some(result) = self.scan_result_rx.recv() => {
+                    let _ = self.log_tx.send(create_log(
+                        "TaskMissionManager",
+                        LogLevel::Info,
+                        format!("Scan finished with {} obstacles", result.obstacles.len())
+                    )).await;
+                    self.scan_complete = true;
+                }
+                _ = progress_check.tick() => {
+                    self.check_progress().await;
                 }
             }
         }
@@ -64,28 +194,711 @@ impl TaskMissionManager {
         )).await;
     }
 
-    async fn handle_command(&mut self, command: UserCommand) {
-        match command {
-            UserCommand::MissionCommand(mission_cmd) => {
-                let mission = self.create_mission_from_command(mission_cmd);
+    async fn handle_command(&mut self, request: CommandRequest) {
+        match request.command {
+            UserCommand::GeofenceCommand(geofence_cmd) => {
+                self.handle_geofence_command(geofence_cmd).await;
+                self.ack(request.id, AckStatus::Completed, None).await;
+            }
+            UserCommand::MissionCommand(mission_cmd) => self.handle_mission_command(request.id, mission_cmd).await,
+            UserCommand::ManualControl(_) | UserCommand::SystemCommand(_) => {}
+        }
+    }
+
+    async fn handle_mission_command(&mut self, command_id: CommandId, command: MissionCommand) {
+        let mission_id = match &command {
+            MissionCommand::Cancel(mission_id) | MissionCommand::Export { mission_id, .. } => Some(*mission_id),
+            _ => None,
+        };
+
+        let status = match command {
+            MissionCommand::Cancel(mission_id) => self.cancel_mission(mission_id).await,
+            MissionCommand::ClearQueue => {
+                let cleared = std::mem::take(&mut self.queue);
+                for queued in &cleared {
+                    self.finish_command(queued.mission.id, AckStatus::Failed("queue cleared".to_string())).await;
+                }
+                let _ = self.log_tx.send(create_log(
+                    "TaskMissionManager",
+                    LogLevel::Info,
+                    format!("Cleared {} queued mission(s)", cleared.len())
+                )).await;
+                self.persist().await;
+                AckStatus::Completed
+            }
+            MissionCommand::ResumeSaved => {
+                if self.faulted {
+                    let _ = self.log_tx.send(create_log(
+                        "TaskMissionManager",
+                        LogLevel::Warn,
+                        "Cannot resume saved missions - safety fault active".to_string()
+                    )).await;
+                    AckStatus::Rejected("safety fault active".to_string())
+                } else if self.saved.is_empty() {
+                    let _ = self.log_tx.send(create_log(
+                        "TaskMissionManager",
+                        LogLevel::Warn,
+                        "No saved missions to resume".to_string()
+                    )).await;
+                    AckStatus::Rejected("no saved missions to resume".to_string())
+                } else {
+                    self.restore_saved().await;
+                    if self.current_mission.is_none() {
+                        self.start_next_mission().await;
+                    }
+                    if let Some(mission) = &self.current_mission {
+                        self.accepted_tx.publish(mission.id).await;
+                    }
+                    AckStatus::Completed
+                }
+            }
+            MissionCommand::DiscardSaved => {
+                let discarded = self.saved.len();
+                self.saved.clear();
+                let _ = self.log_tx.send(create_log(
+                    "TaskMissionManager",
+                    LogLevel::Info,
+                    format!("Discarded {} saved mission(s)", discarded)
+                )).await;
+                self.persist().await;
+                AckStatus::Completed
+            }
+            MissionCommand::Import(path) => match import_mission(Path::new(&path)) {
+                Ok(mut mission) => {
+                    self.mission_counter += 1;
+                    mission.id = self.mission_counter;
+                    self.accept_mission(command_id, mission).await;
+                    return;
+                }
+                Err(reason) => {
+                    let _ = self.log_tx.send(create_log(
+                        "TaskMissionManager",
+                        LogLevel::Warn,
+                        format!("Cannot import mission: {}", reason)
+                    )).await;
+                    AckStatus::Rejected(format!("cannot import mission: {}", reason))
+                }
+            },
+            MissionCommand::Export { mission_id, path } => self.export(mission_id, &path).await,
+            MissionCommand::SetHome(location) => self.set_home(location).await,
+            command if self.home.is_none() && returns_home(&command) => {
+                let _ = self.log_tx.send(create_log(
+                    "TaskMissionManager",
+                    LogLevel::Warn,
+                    "Cannot return home - no home position yet".to_string()
+                )).await;
+                AckStatus::Rejected("no home position yet".to_string())
+            }
+            command => {
+                if let Some(mission) = self.create_mission_from_command(command) {
+                    self.accept_mission(command_id, mission).await;
+                }
+                return;
+            }
+        };
+
+        self.ack(command_id, status, mission_id).await;
+    }
+
+    async fn ack(&self, command_id: CommandId, status: AckStatus, mission_id: Option<u64>) {
+        let mut ack = CommandAck::new("TaskMissionManager", command_id, status);
+        ack.mission_id = mission_id;
+        self.ack_tx.publish(ack).await;
+    }
+
+    // Reports the outcome of a mission to the command that created it
+    async fn finish_command(&mut self, mission_id: u64, status: AckStatus) {
+        if let Some(command_id) = self.mission_commands.remove(&mission_id) {
+            self.ack(command_id, status, Some(mission_id)).await;
+        }
+    }
+
+    // Checks a new mission against the geofence, then runs or queues it. This
+    // is the only answer a mission command gets; the State Manager switches to
+    // ExecutingMission once the mission is accepted.
+    async fn accept_mission(&mut self, command_id: CommandId, mission: Mission) {
+        if self.faulted {
+            let _ = self.log_tx.send(create_log(
+                "TaskMissionManager",
+                LogLevel::Warn,
+                format!("Rejected mission {}: safety fault active", mission.name)
+            )).await;
+            self.ack(command_id, AckStatus::Rejected("safety fault active".to_string()), None).await;
+            return;
+        }
+
+        // A new mission settles an open resume offer: the saved missions are
+        // queued rather than dropped
+        if !self.saved.is_empty() {
+            self.restore_saved().await;
+        }
+
+        if let Err(reason) = self.check_geofence(&mission).await {
+            let _ = self.log_tx.send(create_log(
+                "TaskMissionManager",
+                LogLevel::Warn,
+                format!("Rejected mission {}: {}", mission.name, reason)
+            )).await;
+            self.ack(command_id, AckStatus::Rejected(reason), None).await;
+            return;
+        }
+
+        // Never refuse to go home
+        if self.return_home_mission != Some(mission.id)
+            && let Err(reason) = self.check_energy(&mission, 0, 1)
+        {
+            let _ = self.log_tx.send(create_log(
+                "TaskMissionManager",
+                LogLevel::Warn,
+                format!("Rejected mission {}: {}", mission.name, reason)
+            )).await;
+            self.ack(command_id, AckStatus::Rejected(reason), None).await;
+            return;
+        }
+
+        let _ = self.log_tx.send(create_log(
+            "TaskMissionManager",
+            LogLevel::Info,
+            format!("New mission {}: {} with {} tasks, priority {}",
+                mission.id, mission.name, mission.tasks.len(), mission.priority)
+        )).await;
+
+        self.ack(command_id, AckStatus::Accepted, Some(mission.id)).await;
+        self.mission_commands.insert(mission.id, command_id);
+        self.accepted_tx.publish(mission.id).await;
+        self.submit(mission).await;
+    }
+
+    async fn export(&self, mission_id: u64, path: &str) -> AckStatus {
+        let mission = self.current_mission.iter()
+            .chain(self.queue.iter().chain(&self.saved).map(|saved| &saved.mission))
+            .find(|mission| mission.id == mission_id);
+
+        let (level, message, status) = match mission.map(|mission| export_mission(mission, Path::new(path))) {
+            Some(Ok(())) => (LogLevel::Info, format!("Exported mission {} to {}", mission_id, path), AckStatus::Completed),
+            Some(Err(reason)) => (
+                LogLevel::Warn,
+                format!("Cannot export mission {}: {}", mission_id, reason),
+                AckStatus::Failed(reason),
+            ),
+            None => (
+                LogLevel::Warn,
+                format!("Cannot export mission {} - not running, queued or saved", mission_id),
+                AckStatus::Rejected("mission is not running, queued or saved".to_string()),
+            ),
+        };
+
+        let _ = self.log_tx.send(create_log(
+            "TaskMissionManager",
+            level,
+            message
+        )).await;
+        status
+    }
+
+    async fn load_home(&mut self) {
+        let message = match self.home_store.load() {
+            Ok(Some(home)) => {
+                self.home = Some(home);
+                format!("Loaded home ({:.6}, {:.6}), {:?}", home.position.lat, home.position.lon, home.source)
+            }
+            Ok(None) => "No saved home - capturing it from the first good fix".to_string(),
+            Err(reason) => format!("Cannot load home: {}", reason),
+        };
+
+        let _ = self.log_tx.send(create_log(
+            "TaskMissionManager",
+            LogLevel::Info,
+            message
+        )).await;
+    }
+
+    // Takes the first fix of the run within `home_accuracy` as home, unless
+    // the operator has set one
+    async fn capture_home(&mut self) {
+        if self.home_captured || self.home.is_some_and(|home| home.source == HomeSource::Set) {
+            return;
+        }
+        let Some(estimate) = self.position_rx.borrow().clone() else {
+            return;
+        };
+        if estimate.accuracy > self.config.home_accuracy {
+            return;
+        }
+
+        self.update_home(HomePosition { position: estimate.position, source: HomeSource::Captured }).await;
+    }
+
+    async fn set_home(&mut self, location: HomeLocation) -> AckStatus {
+        let estimate = self.position_rx.borrow().clone();
+        let position = match location {
+            HomeLocation::At(point) => point,
+            HomeLocation::Here => match estimate {
+                Some(estimate) if estimate.accuracy <= self.config.home_accuracy => estimate.position,
+                Some(estimate) => {
+                    let reason = format!("position accuracy {:.1}m exceeds {:.1}m",
+                        estimate.accuracy, self.config.home_accuracy);
+                    let _ = self.log_tx.send(create_log(
+                        "TaskMissionManager",
+                        LogLevel::Warn,
+                        format!("Cannot set home here - {}", reason)
+                    )).await;
+                    return AckStatus::Rejected(reason);
+                }
+                None => {
+                    let _ = self.log_tx.send(create_log(
+                        "TaskMissionManager",
+                        LogLevel::Warn,
+                        "Cannot set home here - no position estimate".to_string()
+                    )).await;
+                    return AckStatus::Rejected("no position estimate".to_string());
+                }
+            },
+        };
+
+        let valid = (-90.0..=90.0).contains(&position.lat) && (-180.0..=180.0).contains(&position.lon);
+        let fenced = self.geofence.read().await.check(position);
+        if !valid || fenced.is_err() {
+            let reason = match fenced {
+                Err(violation) => violation.to_string(),
+                Ok(()) => "not valid coordinates".to_string(),
+            };
+            let _ = self.log_tx.send(create_log(
+                "TaskMissionManager",
+                LogLevel::Warn,
+                format!("Cannot set home to ({:.6}, {:.6}): {}", position.lat, position.lon, reason)
+            )).await;
+            return AckStatus::Rejected(reason);
+        }
+
+        self.update_home(HomePosition { position, source: HomeSource::Set }).await;
+        AckStatus::Completed
+    }
+
+    async fn update_home(&mut self, home: HomePosition) {
+        self.home = Some(home);
+        self.home_captured = true;
+
+        let _ = self.log_tx.send(create_log(
+            "TaskMissionManager",
+            LogLevel::Info,
+            format!("Home {} at ({:.6}, {:.6})",
+                if home.source == HomeSource::Set { "set" } else { "captured" },
+                home.position.lat, home.position.lon)
+        )).await;
+
+        if let Err(reason) = self.home_store.save(&home) {
+            let _ = self.log_tx.send(create_log(
+                "TaskMissionManager",
+                LogLevel::Error,
+                format!("Cannot save home: {}", reason)
+            )).await;
+        }
+    }
+
+    // Offers to resume the missions saved when the manager last ran
+    async fn load_saved(&mut self) {
+        let snapshot = match self.store.load() {
+            Ok(snapshot) => snapshot,
+            Err(reason) => {
+                let _ = self.log_tx.send(create_log(
+                    "TaskMissionManager",
+                    LogLevel::Error,
+                    format!("Cannot load saved missions: {}", reason)
+                )).await;
+                return;
+            }
+        };
+        if snapshot.is_empty() {
+            return;
+        }
+
+        self.saved = snapshot.into_missions();
+        // New missions must not reuse a saved mission's id
+        self.mission_counter = self.saved.iter().map(|saved| saved.mission.id).max().unwrap_or(0);
+
+        let first = &self.saved[0];
+        let _ = self.log_tx.send(create_log(
+            "TaskMissionManager",
+            LogLevel::Info,
+            format!("Saved mission {} can be resumed at task {}/{} ({} more saved)",
+                first.mission.name, first.task + 1, first.mission.tasks.len(), self.saved.len() - 1)
+        )).await;
+
+        let offer = MissionEvent::ResumeOffer {
+            mission_id: first.mission.id,
+            mission_name: first.mission.name.clone(),
+            task_index: first.task,
+            task_count: first.mission.tasks.len(),
+            queued: self.saved.len() - 1,
+        };
+        self.event_tx.publish(offer).await;
+    }
+
+    // Queues the saved missions in their saved order, ahead of queued missions
+    // of the same priority
+    async fn restore_saved(&mut self) {
+        let saved = std::mem::take(&mut self.saved);
+        let count = saved.len();
+
+        for entry in saved.into_iter().rev() {
+            let position = self.queue.iter()
+                .position(|queued| queued.mission.priority <= entry.mission.priority)
+                .unwrap_or(self.queue.len());
+            self.queue.insert(position, entry);
+        }
+
+        let _ = self.log_tx.send(create_log(
+            "TaskMissionManager",
+            LogLevel::Info,
+            format!("Restored {} saved mission(s)", count)
+        )).await;
+        self.persist().await;
+    }
+
+    // Saves the running mission and the queue. While a resume offer is open
+    // the file keeps the offered missions instead.
+    async fn persist(&self) {
+        if !self.saved.is_empty() {
+            return;
+        }
+
+        let snapshot = MissionSnapshot {
+            running: self.current_mission.as_ref().map(|mission| SavedMission {
+                mission: mission.clone(),
+                task: self.current_task,
+                loop_number: self.current_loop,
+            }),
+            queue: self.queue.clone(),
+        };
+
+        if let Err(reason) = self.store.save(&snapshot) {
+            let _ = self.log_tx.send(create_log(
+                "TaskMissionManager",
+                LogLevel::Error,
+                format!("Cannot save mission state: {}", reason)
+            )).await;
+        }
+    }
+
+    // Starts a mission if nothing is running or it outranks the running one,
+    // otherwise queues it
+    async fn submit(&mut self, mission: Mission) {
+        if let Some(current) = &self.current_mission {
+            if mission.priority <= current.priority {
+                let position = self.queue.iter()
+                    .position(|queued| queued.mission.priority < mission.priority)
+                    .unwrap_or(self.queue.len());
 
                 let _ = self.log_tx.send(create_log(
                     "TaskMissionManager",
                     LogLevel::Info,
-                    format!("New mission: {} with {} tasks", mission.name, mission.tasks.len())
+                    format!("Queued mission {} at position {} behind {}", mission.name, position + 1, current.name)
                 )).await;
 
-                self.current_mission = Some(mission.clone());
-                self.execute_mission(mission).await;
+                self.queue.insert(position, SavedMission { mission, task: 0, loop_number: 1 });
+                self.persist().await;
+                return;
             }
-            _ => {}
+
+            let _ = self.log_tx.send(create_log(
+                "TaskMissionManager",
+                LogLevel::Info,
+                format!("Mission {} (priority {}) preempts {} (priority {})",
+                    mission.name, mission.priority, current.name, current.priority)
+            )).await;
+            self.preempt_current().await;
         }
+
+        self.current_mission = Some(mission);
+        self.current_task = 0;
+        self.current_loop = 1;
+        self.start_task().await;
     }
 
-    fn create_mission_from_command(&mut self, cmd: MissionCommand) -> Mission {
-        self.mission_counter += 1;
+    // Moves the running mission back into the queue, ahead of missions of the
+    // same priority, so it resumes at its current task
+    async fn preempt_current(&mut self) {
+        if self.current_task_is_scan() {
+            self.scan_tx.publish(ScanRequest::Cancel).await;
+        }
+
+        let Some(mut mission) = self.current_mission.take() else {
+            return;
+        };
+        if let Some(task) = mission.tasks.get_mut(self.current_task) {
+            task.status = TaskStatus::Pending;
+        }
+
+        let position = self.queue.iter()
+            .position(|queued| queued.mission.priority <= mission.priority)
+            .unwrap_or(self.queue.len());
+        self.queue.insert(position, SavedMission {
+            mission,
+            task: self.current_task,
+            loop_number: self.current_loop,
+        });
+    }
+
+    // Starts or resumes the first queued mission, unless the battery cannot
+    // see it through. A held mission starts once there is enough charge.
+    async fn start_next_mission(&mut self) {
+        let Some(next) = self.queue.first() else {
+            return;
+        };
+
+        if self.return_home_mission != Some(next.mission.id)
+            && let Err(reason) = self.check_energy(&next.mission, next.task, next.loop_number)
+        {
+            if self.energy_hold != Some(next.mission.id) {
+                self.energy_hold = Some(next.mission.id);
+                let _ = self.log_tx.send(create_log(
+                    "TaskMissionManager",
+                    LogLevel::Warn,
+                    format!("Holding mission {}: {}", next.mission.name, reason)
+                )).await;
+            }
+            return;
+        }
+        self.energy_hold = None;
+
+        let next = self.queue.remove(0);
+
+        let _ = self.log_tx.send(create_log(
+            "TaskMissionManager",
+            LogLevel::Info,
+            if next.task > 0 || next.loop_number > 1 {
+                format!("Resuming mission {} at task {}", next.mission.name, next.task + 1)
+            } else {
+                format!("Starting queued mission {}", next.mission.name)
+            }
+        )).await;
+
+        self.current_mission = Some(next.mission);
+        self.current_task = next.task;
+        self.current_loop = next.loop_number;
+        self.start_task().await;
+    }
+
+    async fn cancel_mission(&mut self, mission_id: u64) -> AckStatus {
+        let cancelled = || AckStatus::Failed("cancelled".to_string());
+        let (message, status) = if self.current_mission.as_ref().is_some_and(|mission| mission.id == mission_id) {
+            self.end_mission(cancelled()).await;
+            self.start_next_mission().await;
+            (format!("Cancelled running mission {}", mission_id), AckStatus::Completed)
+        } else if let Some(position) = self.queue.iter().position(|queued| queued.mission.id == mission_id) {
+            self.queue.remove(position);
+            self.finish_command(mission_id, cancelled()).await;
+            self.persist().await;
+            (format!("Cancelled queued mission {}", mission_id), AckStatus::Completed)
+        } else {
+            (
+                format!("Cannot cancel mission {} - not running or queued", mission_id),
+                AckStatus::Rejected("mission is not running or queued".to_string()),
+            )
+        };
+
+        let _ = self.log_tx.send(create_log(
+            "TaskMissionManager",
+            LogLevel::Info,
+            message
+        )).await;
+        status
+    }
+
+    // Missions only run while the robot is executing them. Pausing, manual
+    // control, faults and emergency stops suspend the running mission until
+    // the state returns to ExecutingMission. When faults clear the robot goes
+    // Idle and the mission stays suspended until the operator resumes or
+    // cancels it.
+    async fn handle_state(&mut self, state: RobotState) {
+        let fault_cleared = self.faulted && matches!(state, RobotState::Idle);
+        self.faulted = matches!(state, RobotState::Error(_));
+
+        match state {
+            RobotState::Paused | RobotState::ManualControl | RobotState::EmergencyStop | RobotState::Error(_) => {
+                if self.suspended_since.is_some() {
+                    return;
+                }
+                self.suspended_since = Some(Instant::now());
+
+                let Some(mission) = &self.current_mission else {
+                    return;
+                };
+                let _ = self.log_tx.send(create_log(
+                    "TaskMissionManager",
+                    LogLevel::Info,
+                    format!("Suspending mission {} ({:?})", mission.name, state)
+                )).await;
+
+                if self.current_task_is_scan() {
+                    self.scan_tx.publish(ScanRequest::Cancel).await;
+                }
+                let hold = Goal { target_pose: origin_pose(), goal_type: GoalType::Hold };
+                self.goal_tx.publish(hold).await;
+            }
+            RobotState::ExecutingMission => {
+                let Some(since) = self.suspended_since.take() else {
+                    return;
+                };
+                // Time spent suspended does not count towards waits or timeouts
+                self.task_started += since.elapsed();
+
+                if let Some(mission) = &self.current_mission {
+                    let _ = self.log_tx.send(create_log(
+                        "TaskMissionManager",
+                        LogLevel::Info,
+                        format!("Resuming mission {} at task {}", mission.name, self.current_task + 1)
+                    )).await;
+                    self.dispatch_task().await;
+                }
+            }
+            RobotState::Idle => {
+                if !fault_cleared || self.suspended_since.is_none() {
+                    return;
+                }
+                let Some(mission) = &self.current_mission else {
+                    return;
+                };
+                let _ = self.log_tx.send(create_log(
+                    "TaskMissionManager",
+                    LogLevel::Info,
+                    format!("Faults cleared, mission {} stays suspended at task {} until resumed", mission.name, self.current_task + 1)
+                )).await;
+                let event = MissionEvent::FaultCleared {
+                    mission_id: mission.id,
+                    mission_name: mission.name.clone(),
+                    task_index: self.current_task,
+                    task_count: mission.tasks.len(),
+                };
+                self.event_tx.publish(event).await;
+            }
+        }
+    }
+
+    async fn handle_geofence_command(&mut self, command: GeofenceCommand) {
+        let message = match command {
+            GeofenceCommand::Set(geofence) => {
+                let message = format!("Geofence set: {} keep-in, {} keep-out area(s)",
+                    geofence.keep_in.len(), geofence.keep_out.len());
+                *self.geofence.write().await = geofence;
+                message
+            }
+            GeofenceCommand::Clear => {
+                self.geofence.write().await.clear();
+                "Geofence cleared".to_string()
+            }
+        };
 
-        let (name, tasks) = match cmd {
+        let _ = self.log_tx.send(create_log(
+            "TaskMissionManager",
+            LogLevel::Info,
+            message
+        )).await;
+    }
+
+    // Battery fraction needed to finish `mission` from `task` in pass
+    // `loop_number` and then drive home, with the current energy estimate.
+    // None without an estimate.
+    fn charge_needed(&self, mission: &Mission, task: usize, loop_number: u32) -> Option<(f32, EnergyEstimate)> {
+        let estimate = self.energy_rx.borrow().clone()?;
+        let start = self.position_rx.borrow().as_ref().map(|estimate| estimate.position);
+        let scan_s = self.config.scan_estimate_s as f64;
+
+        let remaining = &mission.tasks[task.min(mission.tasks.len())..];
+        let (mut drive_m, mut seconds, mut end) = route_cost(remaining, start, scan_s);
+
+        // Later passes of a fixed loop count. Endless patrols are only checked
+        // for the current pass and rely on the reserve check to come home.
+        if mission.loops > loop_number {
+            let (pass_distance, pass_seconds, pass_end) = route_cost(&mission.tasks, end, scan_s);
+            let passes = (mission.loops - loop_number) as f64;
+            drive_m += pass_distance * passes;
+            seconds += pass_seconds * passes;
+            end = pass_end.or(end);
+        }
+
+        if let (Some(end), Some(home)) = (end, self.home) {
+            drive_m += distance(end, home.position);
+        }
+
+        Some((estimate.charge_needed(drive_m, seconds), estimate))
+    }
+
+    fn check_energy(&self, mission: &Mission, task: usize, loop_number: u32) -> Result<(), String> {
+        match self.charge_needed(mission, task, loop_number) {
+            Some((needed, estimate)) if needed > estimate.usable() => Err(format!(
+                "needs {:.0}% battery to finish and return home, {:.0}% is left above the {:.0}% reserve",
+                needed * 100.0, estimate.usable() * 100.0, estimate.reserve * 100.0
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    // Preempts the running mission with ReturnHome once the charge left above
+    // the reserve only just covers the way home
+    async fn check_reserve(&mut self) {
+        let Some(mission) = &self.current_mission else {
+            return;
+        };
+        if self.return_home_mission == Some(mission.id) {
+            return;
+        }
+        let estimate = self.energy_rx.borrow().clone();
+        let position = self.position_rx.borrow().as_ref().map(|estimate| estimate.position);
+        let (Some(estimate), Some(position), Some(home)) = (estimate, position, self.home) else {
+            return;
+        };
+
+        let needed = estimate.charge_needed(distance(position, home.position), 0.0);
+        if estimate.usable() > needed {
+            return;
+        }
+
+        let _ = self.log_tx.send(create_log(
+            "TaskMissionManager",
+            LogLevel::Warn,
+            format!("Battery at {:.0}% only just covers the way home - interrupting {} to return home",
+                estimate.battery_level * 100.0, mission.name)
+        )).await;
+
+        if let Some(mut mission) = self.create_mission_from_command(MissionCommand::ReturnHome) {
+            mission.priority = ENERGY_RETURN_PRIORITY;
+            self.submit(mission).await;
+        }
+    }
+
+    // Every waypoint of a mission must lie inside the geofence
+    async fn check_geofence(&self, mission: &Mission) -> Result<(), String> {
+        let geofence = self.geofence.read().await;
+
+        for task in &mission.tasks {
+            if let TaskType::Navigate(waypoint) = &task.task_type {
+                let point = GeoPoint { lat: waypoint.lat, lon: waypoint.lon };
+                geofence.check(point).map_err(|violation| {
+                    format!("task {} waypoint ({:.6}, {:.6}) is {}", task.id, waypoint.lat, waypoint.lon, violation)
+                })?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Builds the mission a command asks for, or None for commands that manage
+    // existing missions
+    fn create_mission_from_command(&mut self, cmd: MissionCommand) -> Option<Mission> {
+        let mut priority = DEFAULT_PRIORITY;
+        let returning_home = matches!(cmd, MissionCommand::ReturnHome);
+
+        let (name, tasks, loops) = match cmd {
+            MissionCommand::WithPriority(priority, command) => {
+                let mut mission = self.create_mission_from_command(*command)?;
+                mission.priority = priority;
+                return Some(mission);
+            }
+            MissionCommand::Cancel(_) | MissionCommand::ClearQueue | MissionCommand::ResumeSaved
+            | MissionCommand::DiscardSaved | MissionCommand::Import(_) | MissionCommand::Export { .. }
+            | MissionCommand::SetHome(_) => return None,
             MissionCommand::GoToWaypoint { lat, lon } => {
                 (
                     format!("GoTo({:.4}, {:.4})", lat, lon),
@@ -94,7 +907,8 @@ impl TaskMissionManager {
                         description: "Navigate to waypoint".to_string(),
                         task_type: TaskType::Navigate(crate::types::Waypoint { lat, lon, tolerance: 2.0 }),
                         status: TaskStatus::Pending,
-                    }]
+                    }],
+                    1
                 )
             }
             MissionCommand::Patrol { waypoints, loops } => {
@@ -107,7 +921,27 @@ impl TaskMissionManager {
                     }
                 }).collect();
 
-                (format!("Patrol {} waypoints x{} loops", waypoints.len(), loops), tasks)
+                let name = match loops {
+                    0 => format!("Patrol {} waypoints until cancelled", waypoints.len()),
+                    loops => format!("Patrol {} waypoints x{} loops", waypoints.len(), loops),
+                };
+                (name, tasks, loops)
+            }
+            MissionCommand::Sequence(task_types) => {
+                let tasks: Vec<Task> = task_types.into_iter().enumerate().map(|(i, task_type)| {
+                    Task {
+                        id: i as u64 + 1,
+                        description: match &task_type {
+                            TaskType::Navigate(_) => format!("Navigate to point {}", i + 1),
+                            TaskType::Scan => "Scan surroundings".to_string(),
+                            TaskType::Wait(ms) => format!("Wait {} ms", ms),
+                        },
+                        task_type,
+                        status: TaskStatus::Pending,
+                    }
+                }).collect();
+
+                (format!("Sequence of {} tasks", tasks.len()), tasks, 1)
             }
             MissionCommand::FollowPath(waypoints) => {
                 let tasks: Vec<Task> = waypoints.iter().enumerate().map(|(i, wp)| {
@@ -119,63 +953,308 @@ impl TaskMissionManager {
                     }
                 }).collect();
 
-                (format!("Follow path with {} points", waypoints.len()), tasks)
+                (format!("Follow path with {} points", waypoints.len()), tasks, 1)
             }
+            // Goal Planning routes the way home around the geofence
             MissionCommand::ReturnHome => {
+                let home = self.home?.position;
+                priority = RETURN_HOME_PRIORITY;
                 (
                     "Return Home".to_string(),
                     vec![Task {
                         id: 1,
                         description: "Navigate home".to_string(),
                         task_type: TaskType::Navigate(crate::types::Waypoint {
-                            lat: 37.7749,
-                            lon: -122.4194,
+                            lat: home.lat,
+                            lon: home.lon,
                             tolerance: 1.0
                         }),
                         status: TaskStatus::Pending,
-                    }]
+                    }],
+                    1
                 )
             }
         };
 
-        Mission {
+        self.mission_counter += 1;
+        if returning_home {
+            self.return_home_mission = Some(self.mission_counter);
+        }
+        Some(Mission {
             id: self.mission_counter,
             name,
             tasks,
-            priority: 5,
+            priority,
+            loops,
+        })
+    }
+
+    // Starts the task at `current_task`. Past the last task the mission either
+    // starts its next loop or completes.
+    async fn start_task(&mut self) {
+        let Some(mission) = self.current_mission.as_mut() else {
+            return;
+        };
+
+        if self.current_task >= mission.tasks.len() {
+            let more_loops = mission.loops == 0 || self.current_loop < mission.loops;
+            if !more_loops || mission.tasks.is_empty() {
+                let _ = self.log_tx.send(create_log(
+                    "TaskMissionManager",
+                    LogLevel::Info,
+                    format!("Mission {} completed", mission.name)
+                )).await;
+                self.end_mission(AckStatus::Completed).await;
+                return;
+            }
+
+            for task in &mut mission.tasks {
+                task.status = TaskStatus::Pending;
+            }
+            self.current_task = 0;
+            self.current_loop += 1;
+
+            let _ = self.log_tx.send(create_log(
+                "TaskMissionManager",
+                LogLevel::Info,
+                format!("Mission {} starting loop {}", mission.name, self.current_loop)
+            )).await;
+        }
+
+        let task_count = mission.tasks.len();
+        let task = &mut mission.tasks[self.current_task];
+        task.status = TaskStatus::InProgress;
+
+        let _ = self.log_tx.send(create_log(
+            "TaskMissionManager",
+            LogLevel::Info,
+            format!("Starting task {}/{}: {}", self.current_task + 1, task_count, task.description)
+        )).await;
+
+        self.task_started = Instant::now();
+        self.persist().await;
+        self.dispatch_task().await;
+        self.report_progress().await;
+    }
+
+    // Sends the running task's goal, and starts its scan if it is one. Nothing
+    // is sent while suspended; resuming dispatches the task again.
+    async fn dispatch_task(&mut self) {
+        if self.suspended_since.is_some() {
+            return;
         }
+        let Some(task) = self.current_mission.as_ref().and_then(|mission| mission.tasks.get(self.current_task)) else {
+            return;
+        };
+        let goal = task_to_goal(task, self.config.scan_turn_rate);
+
+        if self.current_task_is_scan() {
+            self.scan_complete = false;
+            self.scan_tx.publish(ScanRequest::Start).await;
+        }
+
+        self.last_goal_sent = Instant::now();
+        self.goal_tx.publish(goal).await;
     }
 
-    async fn execute_mission(&mut self, mission: Mission) {
-        for task in &mission.tasks {
-            let goal = self.task_to_goal(task);
-            let _ = self.goal_tx.send(goal).await;
+    fn current_task_is_scan(&self) -> bool {
+        self.current_mission.as_ref()
+            .and_then(|mission| mission.tasks.get(self.current_task))
+            .is_some_and(|task| matches!(task.task_type, TaskType::Scan) && !matches!(task.status, TaskStatus::Completed))
+    }
+
+    async fn check_progress(&mut self) {
+        self.capture_home().await;
+        // Also while suspended, so the mission resumes by going home
+        self.check_reserve().await;
+
+        if self.suspended_since.is_some() {
+            return;
+        }
+        if self.current_mission.is_none() {
+            self.start_next_mission().await;
+            return;
+        }
+
+        let Some(task) = self.current_mission.as_ref().and_then(|mission| mission.tasks.get(self.current_task)) else {
+            return;
+        };
+
+        let done = match &task.task_type {
+            TaskType::Navigate(waypoint) => self.distance_to(waypoint.lat, waypoint.lon)
+                .is_some_and(|distance| distance <= waypoint.tolerance),
+            TaskType::Wait(ms) => self.task_started.elapsed() >= Duration::from_millis(*ms),
+            TaskType::Scan => self.scan_complete,
+        };
+
+        // Waits are as long as the operator asked for
+        let timeout = Duration::from_secs(self.config.task_timeout_s);
+        let timed_out = !matches!(task.task_type, TaskType::Wait(_)) && self.task_started.elapsed() > timeout;
+
+        if done {
+            self.finish_task(TaskStatus::Completed).await;
+        } else if timed_out {
+            let reason = format!("not completed within {} s", self.config.task_timeout_s);
+            self.finish_task(TaskStatus::Failed(reason)).await;
+        } else {
+            if self.last_goal_sent.elapsed() >= Duration::from_millis(self.config.goal_refresh_ms) {
+                // Keeps the goal alive in the arbiter and replans from the latest position
+                let goal = task_to_goal(task, self.config.scan_turn_rate);
+                self.last_goal_sent = Instant::now();
+                self.goal_tx.publish(goal).await;
+            }
+            if self.last_progress_report.elapsed() >= Duration::from_millis(self.config.progress_report_ms) {
+                self.report_progress().await;
+            }
         }
     }
 
-    fn task_to_goal(&self, task: &Task) -> Goal {
+    // Records the outcome of the running task, then moves on to the next one,
+    // or abandons the mission if the task failed
+    async fn finish_task(&mut self, status: TaskStatus) {
+        let Some(task) = self.current_mission.as_mut().and_then(|mission| mission.tasks.get_mut(self.current_task)) else {
+            return;
+        };
+        task.status = status.clone();
+        self.report_progress().await;
+
+        if let TaskStatus::Failed(reason) = status {
+            if let Some(mission) = &self.current_mission {
+                let _ = self.log_tx.send(create_log(
+                    "TaskMissionManager",
+                    LogLevel::Warn,
+                    format!("Mission {} failed at task {}: {}", mission.name, self.current_task + 1, reason)
+                )).await;
+            }
+            self.end_mission(AckStatus::Failed(format!("task {} {}", self.current_task + 1, reason))).await;
+            return;
+        }
+
+        self.current_task += 1;
+        self.start_task().await;
+    }
+
+    // Stops whatever the running task was doing and holds position. `outcome`
+    // goes to the command that created the mission.
+    async fn end_mission(&mut self, outcome: AckStatus) {
+        if self.current_task_is_scan() {
+            self.scan_tx.publish(ScanRequest::Cancel).await;
+        }
+        let Some(mission) = self.current_mission.take() else {
+            return;
+        };
+        self.finish_command(mission.id, outcome).await;
+        self.persist().await;
+        self.mission_tx.send_replace(None);
+
+        let hold = Goal { target_pose: origin_pose(), goal_type: GoalType::Hold };
+        self.goal_tx.publish(hold).await;
+    }
+
+    // Distance in meters from the position estimate to a target, if there is an estimate
+    fn distance_to(&self, lat: f64, lon: f64) -> Option<f32> {
+        self.position_rx.borrow().as_ref()
+            .map(|estimate| distance(estimate.position, GeoPoint { lat, lon }) as f32)
+    }
+
+    async fn report_progress(&mut self) {
+        let Some(mission) = &self.current_mission else {
+            return;
+        };
+        let Some(task) = mission.tasks.get(self.current_task) else {
+            return;
+        };
+
+        let distance_to_target = match &task.task_type {
+            TaskType::Navigate(waypoint) => self.distance_to(waypoint.lat, waypoint.lon),
+            _ => None,
+        };
+
+        let progress = MissionProgress {
+            timestamp: SystemTime::now(),
+            mission_id: mission.id,
+            mission_name: mission.name.clone(),
+            task_id: task.id,
+            task_description: task.description.clone(),
+            task_index: self.current_task,
+            task_count: mission.tasks.len(),
+            loop_number: self.current_loop,
+            loop_count: mission.loops,
+            status: task.status.clone(),
+            distance_to_target,
+        };
+
+        self.last_progress_report = Instant::now();
+        self.mission_tx.send_replace(Some(progress.clone()));
+        self.event_tx.publish(MissionEvent::Progress(progress)).await;
+    }
+}
+
+// Distance driven and time spent waiting or scanning to run `tasks` from
+// `start`, and where they end
+fn route_cost(tasks: &[Task], start: Option<GeoPoint>, scan_s: f64) -> (f64, f64, Option<GeoPoint>) {
+    let mut distance_m = 0.0;
+    let mut seconds = 0.0;
+    let mut position = start;
+
+    for task in tasks {
         match &task.task_type {
             TaskType::Navigate(waypoint) => {
-                Goal {
-                    target_pose: RobotPose {
-                        position: [waypoint.lat as f32, waypoint.lon as f32, 0.0],
-                        orientation: [1.0, 0.0, 0.0, 0.0],
-                        velocity: [0.0, 0.0, 0.0],
-                        angular_velocity: [0.0, 0.0, 0.0],
-                    },
-                    goal_type: GoalType::ReachPosition,
-                }
-            }
-            _ => {
-                Goal {
-                    target_pose: RobotPose {
-                        position: [0.0, 0.0, 0.0],
-                        orientation: [1.0, 0.0, 0.0, 0.0],
-                        velocity: [0.0, 0.0, 0.0],
-                        angular_velocity: [0.0, 0.0, 0.0],
-                    },
-                    goal_type: GoalType::ReachPosition,
+                let target = GeoPoint { lat: waypoint.lat, lon: waypoint.lon };
+                if let Some(from) = position {
+                    distance_m += distance(from, target);
                 }
+                position = Some(target);
+            }
+            TaskType::Wait(ms) => seconds += *ms as f64 / 1000.0,
+            TaskType::Scan => seconds += scan_s,
+        }
+    }
+
+    (distance_m, seconds, position)
+}
+
+fn returns_home(command: &MissionCommand) -> bool {
+    match command {
+        MissionCommand::ReturnHome => true,
+        MissionCommand::WithPriority(_, command) => returns_home(command),
+        _ => false,
+    }
+}
+
+fn origin_pose() -> RobotPose {
+    RobotPose {
+        position: [0.0, 0.0, 0.0],
+        orientation: [1.0, 0.0, 0.0, 0.0],
+        velocity: [0.0, 0.0, 0.0],
+        angular_velocity: [0.0, 0.0, 0.0],
+    }
+}
+
+fn task_to_goal(task: &Task, scan_turn_rate: f32) -> Goal {
+    match &task.task_type {
+        TaskType::Navigate(waypoint) => {
+            Goal {
+                target_pose: RobotPose {
+                    position: [waypoint.lat as f32, waypoint.lon as f32, 0.0],
+                    ..origin_pose()
+                },
+                goal_type: GoalType::ReachPosition,
+            }
+        }
+        TaskType::Wait(_) => {
+            Goal {
+                target_pose: origin_pose(),
+                goal_type: GoalType::Hold,
+            }
+        }
+        TaskType::Scan => {
+            Goal {
+                target_pose: RobotPose {
+                    angular_velocity: [0.0, 0.0, scan_turn_rate],
+                    ..origin_pose()
+                },
+                goal_type: GoalType::Scan,
             }
         }
     }
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::time::SystemTime;
 
 // ============================================================================
@@ -12,6 +13,28 @@ pub struct SensorData {
     pub imu: ImuData,
     pub gps: GpsData,
     pub battery_level: f32, // 0.0 to 1.0
+    pub trace: Trace,
+}
+
+// Stages a sensor reading passes on its way to a motor command
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum PipelineStage {
+    Sensor,
+    InputManager,
+    EnvUnderstanding,
+    ObstacleAvoidance,
+    Behaviour,
+    CommandArbiter,
+    Safety,
+    Hardware,
+}
+
+// Follows one sensor reading through the pipeline, with the time it left
+// each stage it has passed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Trace {
+    pub sequence: u64,
+    pub hops: Vec<(PipelineStage, SystemTime)>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,11 +56,21 @@ pub struct GpsData {
 // User Input Types
 // ============================================================================
 
+// Chosen by whoever sends a command, and repeated in every acknowledgement of it
+pub type CommandId = u64;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandRequest {
+    pub id: CommandId,
+    pub command: UserCommand,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum UserCommand {
     ManualControl(ManualControl),
     MissionCommand(MissionCommand),
     SystemCommand(SystemCommand),
+    GeofenceCommand(GeofenceCommand),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -53,7 +86,16 @@ pub enum ManualControl {
 pub enum MissionCommand {
     GoToWaypoint { lat: f64, lon: f64 },
     FollowPath(Vec<Waypoint>),
-    Patrol { waypoints: Vec<Waypoint>, loops: u32 },
+    Patrol { waypoints: Vec<Waypoint>, loops: u32 }, // 0 loops repeats until cancelled
+    Sequence(Vec<TaskType>), // Runs the given tasks in order
+    WithPriority(u8, Box<MissionCommand>), // Any of the above at a given priority, higher runs first
+    Cancel(u64), // Mission id, running or queued
+    ClearQueue,  // Drops all queued missions, the running one continues
+    ResumeSaved,  // Accepts the offer to resume missions saved before a restart
+    DiscardSaved, // Declines it
+    Import(String), // Runs a mission read from a file on the rover
+    Export { mission_id: u64, path: String }, // Writes a running or queued mission to a file
+    SetHome(HomeLocation), // Where ReturnHome goes
     ReturnHome,
 }
 
@@ -72,6 +114,85 @@ pub enum SystemCommand {
     Calibrate,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum GeofenceCommand {
+    Set(Geofence), // Replaces the active geofence
+    Clear,
+}
+
+// ============================================================================
+// Geographic Types
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GeoPoint {
+    pub lat: f64, // WGS84 degrees
+    pub lon: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GeoPolygon {
+    pub name: String,
+    pub vertices: Vec<GeoPoint>, // Implicitly closed
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Geofence {
+    pub keep_in: Vec<GeoPolygon>,  // When non-empty, the rover must stay inside one of these
+    pub keep_out: Vec<GeoPolygon>, // The rover must stay outside all of these
+    pub margin: f64,               // meters kept between planned routes and fence edges
+}
+
+impl Default for Geofence {
+    fn default() -> Self {
+        Self {
+            keep_in: Vec::new(),
+            keep_out: Vec::new(),
+            margin: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum HomeLocation {
+    Here,         // The current position estimate
+    At(GeoPoint),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum HomeSource {
+    Captured, // First good fix of a run, replaced by the next run's
+    Set,      // Given with SetHome, kept until set again
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct HomePosition {
+    pub position: GeoPoint,
+    pub source: HomeSource,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PositionEstimate {
+    pub timestamp: SystemTime,
+    pub position: GeoPoint,
+    pub accuracy: f32, // meters, 1 sigma
+    pub yaw: f32,      // radians, counter-clockwise from east
+}
+
+// Latest battery estimate from the energy manager. Rates include the
+// configured safety margin.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnergyEstimate {
+    pub timestamp: SystemTime,
+    pub battery_level: f32, // 0.0 to 1.0
+    pub reserve: f32,       // Battery fraction to keep for arriving home
+    pub per_second: f32,    // Battery fraction used per second
+    pub per_meter: f32,     // Battery fraction used per meter driven at cruise speed
+    pub observed: bool,     // False while the rates come from the configured default
+    pub range: f32,         // meters that can be driven before the reserve
+}
+
 // ============================================================================
 // Robot State Types
 // ============================================================================
@@ -84,7 +205,7 @@ pub struct RobotPose {
     pub angular_velocity: [f32; 3],
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum RobotState {
     Idle,
     ManualControl,
@@ -94,6 +215,25 @@ pub enum RobotState {
     Error(String),
 }
 
+// ============================================================================
+// Safety Fault Types
+// ============================================================================
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SafetyFault {
+    SensorDataStale { age_ms: u64 },   // No fresh SensorData within the sensor timeout
+    BehaviorCommandStale { age_ms: u64 }, // No BehaviorCommand within the deadman interval while moving
+    GeofenceBreach { reason: String },   // Fused position outside the geofence
+    ComponentFailure { component: String, message: String }, // Critical component in error or stale
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FaultReport {
+    pub timestamp: SystemTime,
+    pub fault: SafetyFault,
+    pub active: bool, // false when the fault has cleared
+}
+
 // ============================================================================
 // Environment Types
 // ============================================================================
@@ -103,6 +243,15 @@ pub struct EnvironmentState {
     pub obstacles: Vec<Obstacle>,
     pub terrain_type: TerrainType,
     pub confidence: f32, // 0.0 to 1.0
+    pub trace: Trace,    // Of the reading this was built from
+}
+
+// 360° view accumulated while scanning
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanResult {
+    pub timestamp: SystemTime,
+    pub sector_ranges: Vec<f32>, // Closest distance seen per equal heading sector, counter-clockwise from east
+    pub obstacles: Vec<Obstacle>, // Relative to the robot, in the east/north frame
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -137,6 +286,7 @@ pub struct Mission {
     pub name: String,
     pub tasks: Vec<Task>,
     pub priority: u8,
+    pub loops: u32, // Times the task list runs, 0 repeats until cancelled
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -154,7 +304,7 @@ pub enum TaskType {
     Wait(u64), // milliseconds
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum TaskStatus {
     Pending,
     InProgress,
@@ -162,6 +312,46 @@ pub enum TaskStatus {
     Failed(String),
 }
 
+// Reported to the operator whenever a task changes status, and periodically
+// while a task is in progress
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MissionProgress {
+    pub timestamp: SystemTime,
+    pub mission_id: u64,
+    pub mission_name: String,
+    pub task_id: u64,
+    pub task_description: String,
+    pub task_index: usize, // 0-based position of the task in the mission
+    pub task_count: usize,
+    pub loop_number: u32,  // 1-based pass through the task list
+    pub loop_count: u32,   // 0 repeats until cancelled
+    pub status: TaskStatus,
+    pub distance_to_target: Option<f32>, // meters, for navigation tasks with a position estimate
+}
+
+// Mission updates sent to the operator
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum MissionEvent {
+    Progress(MissionProgress),
+    // Missions were saved before a restart; MissionCommand::ResumeSaved
+    // resumes them, DiscardSaved drops them
+    ResumeOffer {
+        mission_id: u64,
+        mission_name: String,
+        task_index: usize, // 0-based task the mission resumes at
+        task_count: usize,
+        queued: usize,     // Saved missions waiting behind it
+    },
+    // The faults that suspended the running mission have cleared; the robot
+    // is Idle and SystemCommand::Resume continues the mission
+    FaultCleared {
+        mission_id: u64,
+        mission_name: String,
+        task_index: usize, // 0-based task the mission resumes at
+        task_count: usize,
+    },
+}
+
 // ============================================================================
 // Planning Types
 // ============================================================================
@@ -177,6 +367,8 @@ pub enum GoalType {
     ReachPosition,
     OrientTowards,
     FollowTrajectory,
+    Hold, // Stop and stay in place
+    Scan, // Rotate in place at target_pose.angular_velocity[2] (normalised)
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -184,6 +376,7 @@ pub struct Path {
     pub waypoints: Vec<RobotPose>,
     pub total_distance: f32,
     pub estimated_time: f32, // seconds
+    pub trace: Option<Trace>, // Of the reading the path was checked against
 }
 
 // ============================================================================
@@ -213,6 +406,18 @@ pub struct BehaviorCommand {
     pub timestamp: SystemTime,
     pub behavior: Behavior,
     pub priority: u8,
+    pub source: CommandSource,
+    pub command_id: Option<CommandId>, // Operator command this carries out, for acknowledgements
+    pub trace: Option<Trace>,          // Of the sensor reading behind the command
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CommandSource {
+    GoalPath,
+    ObstaclePath,
+    Stance,
+    Manual,
+    Safety,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -220,6 +425,7 @@ pub enum Behavior {
     Idle,
     MoveTowards { target: [f32; 3], speed: f32 },
     AvoidObstacle { direction: [f32; 3] },
+    Drive { linear: f32, angular: f32 }, // -1.0 to 1.0, fraction of max speed / max angular velocity
     AdjustStance(StanceConfig),
     EmergencyStop,
 }
@@ -242,13 +448,52 @@ pub struct HardwareStatus {
     pub health: HealthStatus,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum HealthStatus {
     Healthy,
     Warning(String),
     Critical(String),
 }
 
+// ============================================================================
+// Diagnostics Types
+// ============================================================================
+
+// Worst last: a roll-up takes the highest level of its components
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum DiagnosticLevel {
+    Ok,
+    Warn,
+    Stale, // The component stopped reporting
+    Error,
+}
+
+// Status of one component, reported by the module that owns it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DiagnosticStatus {
+    pub name: String, // Group and component, such as "motors/driver"
+    pub level: DiagnosticLevel,
+    pub message: String,
+    pub values: Vec<(String, String)>,
+}
+
+// Components of one group, rolled up to their worst level
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiagnosticGroup {
+    pub name: String,
+    pub level: DiagnosticLevel,
+    pub message: String,
+    pub components: Vec<DiagnosticStatus>,
+}
+
+// Every component the diagnostics aggregator knows of
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiagnosticSummary {
+    pub timestamp: SystemTime,
+    pub level: DiagnosticLevel,
+    pub groups: Vec<DiagnosticGroup>,
+}
+
 // ============================================================================
 // Calibration Types
 // ============================================================================
@@ -272,13 +517,30 @@ pub struct LogEntry {
     pub level: LogLevel,
     pub module: String,
     pub message: String,
+    pub file: Option<String>, // Source location, set by rlog!
+    pub line: u32,
+    pub fields: BTreeMap<String, LogValue>,
+}
+
+// A structured log field, kept typed so it can be filtered on
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum LogValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Text(String),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
 pub enum LogLevel {
+    #[serde(alias = "debug")]
     Debug,
+    #[serde(alias = "info")]
     Info,
+    #[serde(alias = "warn")]
     Warn,
+    #[serde(alias = "error")]
     Error,
 }
 
@@ -290,9 +552,10 @@ pub enum LogLevel {
 pub struct StatusUpdate {
     pub timestamp: SystemTime,
     pub state: RobotState,
-    pub pose: RobotPose,
-    pub current_mission: Option<String>,
-    pub battery_level: f32,
+    pub position: Option<PositionEstimate>,  // None until the first GPS fix
+    pub mission: Option<MissionProgress>,    // Latest progress of the running mission
+    pub battery_level: Option<f32>,          // 0.0 to 1.0, None until the first estimate
+    pub health: Option<HealthStatus>,        // None until the first hardware report
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -308,3 +571,80 @@ pub enum FeedbackType {
     Error,
     Success,
 }
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum AckStatus {
+    Accepted,         // Taken on, the outcome follows later
+    Rejected(String), // Refused, nothing was done
+    Completed,        // Done
+    Failed(String),   // Taken on but not finished
+}
+
+// One module's answer to a command. A command can be answered by several
+// modules, and a module can answer it more than once as it progresses.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandAck {
+    pub timestamp: SystemTime,
+    pub command_id: CommandId,
+    pub module: String,
+    pub status: AckStatus,
+    pub mission_id: Option<u64>, // Mission the command created or acted on
+}
+
+impl CommandAck {
+    pub fn new(module: &str, command_id: CommandId, status: AckStatus) -> Self {
+        Self {
+            timestamp: SystemTime::now(),
+            command_id,
+            module: module.to_string(),
+            status,
+            mission_id: None,
+        }
+    }
+}
+
+// Kinds of message streamed to operators, highest priority first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum TelemetryTopic {
+    Alert,       // Emergency stops and safety faults
+    Ack,         // Command acknowledgements
+    Feedback,    // Feedback messages
+    Status,      // Robot status
+    Pose,        // Position estimate
+    Diagnostics, // Component health from the diagnostics aggregator
+}
+
+// Adjusts the telemetry sent to the operator's own connection
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum TelemetryCommand {
+    SetRate { topic: TelemetryTopic, hz: f32 }, // 0 pauses the topic
+    SetByteBudget(u32),                         // Bytes per second, 0 for no limit
+    Reset,                                      // Back to the configured rates and budget
+}
+
+// Changes which log entries modules send, for every operator
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum LoggingCommand {
+    SetLevel { module: Option<String>, level: LogLevel }, // None sets the default level
+    ClearLevel(String),                                   // The module follows the default level again
+    Reset,                                                // Back to the configured levels
+}
+
+// Sent by an operator over the operator link
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum OperatorMessage {
+    Command(CommandRequest),
+    Telemetry { id: CommandId, command: TelemetryCommand },
+    Logging { id: CommandId, command: LoggingCommand },
+}
+
+// Streamed to every operator connected to the operator link
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RoverMessage {
+    Status(StatusUpdate),
+    Feedback(UserFeedback),
+    Ack(CommandAck),
+    Pose(PositionEstimate),
+    Fault(FaultReport),
+    Diagnostics(DiagnosticSummary),
+}